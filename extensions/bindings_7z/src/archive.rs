@@ -0,0 +1,484 @@
+use std::fmt::Debug;
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use sevenz_rust::{Password, SevenZReader};
+use tokio::sync::mpsc;
+use zip::ZipArchive;
+use crate::command::OverwriteMode;
+use crate::output::{ArchiveEntry, SevenZipError, SevenZipEvent};
+use crate::SevenZip;
+
+use log::info;
+
+pub type ArchiveFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, SevenZipError>> + Send + 'a>>;
+
+/*
+ * 压缩包的读取接口
+ * `read` 的条目名中 `/` 与 `\` 视为相同
+ * 解压时 7z 后端跳过 `out` 中已存在的文件（`-aos`），内置后端覆盖
+ */
+pub trait Archive: Debug + Send + Sync {
+  fn list<'a>(&'a self, archive: &'a Path) -> ArchiveFuture<'a, Vec<ArchiveEntry>>;
+  // 读出单个文件的内容
+  fn read<'a>(&'a self, archive: &'a Path, entry: &'a str) -> ArchiveFuture<'a, Vec<u8>>;
+  fn extract_all<'a>(&'a self, archive: &'a Path, out: &'a Path) -> ArchiveFuture<'a, ()> {
+    self.extract_with_events(archive, out, None)
+  }
+  // 解压全部内容，进度发送到 `events`；内置后端只在完成时报告 100
+  fn extract_with_events<'a>(
+    &'a self,
+    archive: &'a Path,
+    out: &'a Path,
+    events: Option<mpsc::UnboundedSender<SevenZipEvent>>,
+  ) -> ArchiveFuture<'a, ()>;
+  // 解压全部内容并校验，不写入磁盘
+  fn test<'a>(&'a self, archive: &'a Path) -> ArchiveFuture<'a, ()>;
+}
+
+fn same_entry(a: &str, b: &str) -> bool {
+  a.replace('\\', "/").trim_matches('/') == b.replace('\\', "/").trim_matches('/')
+}
+
+impl Debug for SevenZip {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("SevenZip").field("exe_path", &self.exe_path).finish()
+  }
+}
+
+// 调用外部的 7z
+impl Archive for SevenZip {
+  fn list<'a>(&'a self, archive: &'a Path) -> ArchiveFuture<'a, Vec<ArchiveEntry>> {
+    Box::pin(async move { SevenZip::list(self, archive).entries().await })
+  }
+
+  fn read<'a>(&'a self, archive: &'a Path, entry: &'a str) -> ArchiveFuture<'a, Vec<u8>> {
+    Box::pin(async move {
+      let listed = SevenZip::list(self, archive).entries().await?;
+      let name = listed.iter()
+        .find(|e| !e.is_dir && same_entry(&e.path, entry))
+        .map(|e| e.path.clone())
+        .ok_or(SevenZipError::NotFound { path: Some(entry.to_string()) })?;
+      self.print(archive, &name).bytes().await
+    })
+  }
+
+  fn extract_with_events<'a>(
+    &'a self,
+    archive: &'a Path,
+    out: &'a Path,
+    events: Option<mpsc::UnboundedSender<SevenZipEvent>>,
+  ) -> ArchiveFuture<'a, ()> {
+    Box::pin(async move {
+      self.extract(archive, out).overwrite(OverwriteMode::Skip).run_with_events(events).await?;
+      Ok(())
+    })
+  }
+
+  fn test<'a>(&'a self, archive: &'a Path) -> ArchiveFuture<'a, ()> {
+    Box::pin(async move {
+      SevenZip::test(self, archive).run().await?;
+      Ok(())
+    })
+  }
+}
+
+/*
+ * 纯 Rust 实现，按文件头识别 7z 与 zip，不依赖外部程序
+ * 不支持加密的压缩包
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NativeArchive;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NativeFormat {
+  SevenZ,
+  Zip,
+}
+
+const MAGIC_7Z: &[u8] = &[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C];
+
+fn format_of(path: &Path) -> Result<NativeFormat, SevenZipError> {
+  let mut magic = [0u8; 6];
+  let mut file = File::open(path).map_err(|e| io_error(e, path))?;
+  let n = file.read(&mut magic).map_err(|e| io_error(e, path))?;
+  match &magic[..n] {
+    m if m == MAGIC_7Z => Ok(NativeFormat::SevenZ),
+    [b'P', b'K', 3, 4, ..] | [b'P', b'K', 5, 6, ..] => Ok(NativeFormat::Zip),
+    _ => Err(SevenZipError::CannotOpen { path: Some(path.to_string_lossy().to_string()) }),
+  }
+}
+
+fn io_error(e: io::Error, path: &Path) -> SevenZipError {
+  match e.kind() {
+    ErrorKind::NotFound => SevenZipError::NotFound { path: Some(path.to_string_lossy().to_string()) },
+    ErrorKind::UnexpectedEof => SevenZipError::UnexpectedEnd,
+    _ if e.to_string().contains("checksum") => SevenZipError::CrcFailed { path: None },
+    _ => SevenZipError::Backend(e.to_string()),
+  }
+}
+
+// 条目名中的 `..`、盘符与根目录会让文件写到解压目录之外
+fn is_safe_entry(name: &str) -> bool {
+  Path::new(&name.replace('\\', "/")).components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn sevenz_error(e: sevenz_rust::Error, path: &Path) -> SevenZipError {
+  use sevenz_rust::Error;
+  match e {
+    Error::PasswordRequired => SevenZipError::WrongPassword,
+    Error::ChecksumVerificationFailed | Error::NextHeaderCrcMismatch => SevenZipError::CrcFailed { path: None },
+    Error::BadSignature(_) => SevenZipError::CannotOpen { path: Some(path.to_string_lossy().to_string()) },
+    Error::Io(e, _) | Error::FileOpen(e, _) => io_error(e, path),
+    e => SevenZipError::Backend(e.to_string()),
+  }
+}
+
+fn zip_error(e: zip::result::ZipError, path: &Path) -> SevenZipError {
+  use zip::result::ZipError;
+  match e {
+    ZipError::Io(e) => io_error(e, path),
+    ZipError::InvalidArchive(_) => SevenZipError::CannotOpen { path: Some(path.to_string_lossy().to_string()) },
+    ZipError::UnsupportedArchive(m) if m.contains("Password") => SevenZipError::WrongPassword,
+    ZipError::FileNotFound => SevenZipError::NotFound { path: None },
+    e => SevenZipError::Backend(e.to_string()),
+  }
+}
+
+// 与 `7z l -slt` 相同的 `YYYY-MM-DD HH:MM:SS`
+fn format_time(year: i64, month: u32, day: u32, secs_of_day: u32) -> String {
+  format!(
+    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+    year, month, day, secs_of_day / 3600, secs_of_day / 60 % 60, secs_of_day % 60
+  )
+}
+
+// Windows FILETIME（1601 年起的 100ns 数）转为 UTC 时间
+fn format_filetime(raw: u64) -> String {
+  let secs = (raw / 10_000_000) as i64 - 11_644_473_600;
+  let days = secs.div_euclid(86_400);
+  // 公历日期换算，见 Howard Hinnant 的 civil_from_days
+  let z = days + 719_468;
+  let era = z.div_euclid(146_097);
+  let doe = z - era * 146_097;
+  let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  format_time(year, month, day, secs.rem_euclid(86_400) as u32)
+}
+
+impl NativeArchive {
+  fn list_blocking(archive: &Path) -> Result<Vec<ArchiveEntry>, SevenZipError> {
+    match format_of(archive)? {
+      NativeFormat::SevenZ => {
+        let reader = SevenZReader::open(archive, Password::empty()).map_err(|e| sevenz_error(e, archive))?;
+        Ok(reader.archive().files.iter()
+          // 压缩目录时会带上名字为空的根目录
+          .filter(|f| !f.name.is_empty())
+          .map(|f| ArchiveEntry {
+            path: f.name.clone(),
+            size: f.size,
+            packed_size: None,
+            attributes: match f.is_directory {
+              true => "D".to_string(),
+              false => "A".to_string(),
+            },
+            crc: if f.has_crc { Some(f.crc as u32) } else { None },
+            modified: if f.has_last_modified_date { Some(format_filetime(f.last_modified_date.to_raw())) } else { None },
+            is_dir: f.is_directory,
+            encrypted: false,
+          })
+          .collect())
+      }
+      NativeFormat::Zip => {
+        let mut zip = ZipArchive::new(File::open(archive).map_err(|e| io_error(e, archive))?)
+          .map_err(|e| zip_error(e, archive))?;
+        let mut entries = vec![];
+        for i in 0..zip.len() {
+          let f = zip.by_index_raw(i).map_err(|e| zip_error(e, archive))?;
+          let t = f.last_modified();
+          let secs = t.hour() as u32 * 3600 + t.minute() as u32 * 60 + t.second() as u32;
+          entries.push(ArchiveEntry {
+            path: f.name().trim_end_matches('/').to_string(),
+            size: f.size(),
+            packed_size: Some(f.compressed_size()),
+            attributes: match f.is_dir() {
+              true => "D".to_string(),
+              false => "A".to_string(),
+            },
+            crc: Some(f.crc32()),
+            modified: Some(format_time(t.year() as i64, t.month() as u32, t.day() as u32, secs)),
+            is_dir: f.is_dir(),
+            encrypted: false,
+          });
+        }
+        Ok(entries)
+      }
+    }
+  }
+
+  fn read_blocking(archive: &Path, entry: &str) -> Result<Vec<u8>, SevenZipError> {
+    let not_found = || SevenZipError::NotFound { path: Some(entry.to_string()) };
+    match format_of(archive)? {
+      NativeFormat::SevenZ => {
+        let mut reader = SevenZReader::open(archive, Password::empty()).map_err(|e| sevenz_error(e, archive))?;
+        let mut content = None;
+        reader.for_each_entries(|e, r| {
+          if e.is_directory || !same_entry(&e.name, entry) {
+            return Ok(true);
+          }
+          let mut buf = vec![];
+          r.read_to_end(&mut buf)?;
+          content = Some(buf);
+          Ok(false)
+        }).map_err(|e| sevenz_error(e, archive))?;
+        content.ok_or_else(not_found)
+      }
+      NativeFormat::Zip => {
+        let mut zip = ZipArchive::new(File::open(archive).map_err(|e| io_error(e, archive))?)
+          .map_err(|e| zip_error(e, archive))?;
+        let name = zip.file_names()
+          .find(|n| !n.ends_with('/') && same_entry(n, entry))
+          .map(|n| n.to_string())
+          .ok_or_else(not_found)?;
+        let mut f = zip.by_name(&name).map_err(|e| zip_error(e, archive))?;
+        let mut buf = vec![];
+        f.read_to_end(&mut buf).map_err(|e| io_error(e, archive))?;
+        Ok(buf)
+      }
+    }
+  }
+
+  fn extract_blocking(archive: &Path, out: &Path) -> Result<(), SevenZipError> {
+    std::fs::create_dir_all(out).map_err(|e| io_error(e, out))?;
+    match format_of(archive)? {
+      NativeFormat::SevenZ => sevenz_rust::decompress_file_with_extract_fn(archive, out, |entry, reader, dest| {
+        if !is_safe_entry(entry.name()) {
+          return Err(sevenz_rust::Error::other(format!("unsafe entry path {:?}", entry.name())));
+        }
+        sevenz_rust::default_entry_extract_fn(entry, reader, dest)
+      }).map_err(|e| sevenz_error(e, archive)),
+      NativeFormat::Zip => ZipArchive::new(File::open(archive).map_err(|e| io_error(e, archive))?)
+        .and_then(|mut zip| zip.extract(out))
+        .map_err(|e| zip_error(e, archive)),
+    }
+  }
+
+  fn test_blocking(archive: &Path) -> Result<(), SevenZipError> {
+    match format_of(archive)? {
+      NativeFormat::SevenZ => {
+        let mut reader = SevenZReader::open(archive, Password::empty()).map_err(|e| sevenz_error(e, archive))?;
+        reader.for_each_entries(|_, r| {
+          io::copy(r, &mut io::sink())?;
+          Ok(true)
+        }).map_err(|e| sevenz_error(e, archive))
+      }
+      NativeFormat::Zip => {
+        let mut zip = ZipArchive::new(File::open(archive).map_err(|e| io_error(e, archive))?)
+          .map_err(|e| zip_error(e, archive))?;
+        for i in 0..zip.len() {
+          let mut f = zip.by_index(i).map_err(|e| zip_error(e, archive))?;
+          let name = f.name().to_string();
+          // 读到结尾时校验 CRC
+          io::copy(&mut f, &mut io::sink()).map_err(|e| match io_error(e, archive) {
+            SevenZipError::CrcFailed { .. } => SevenZipError::CrcFailed { path: Some(name) },
+            e => e,
+          })?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+// 解压是同步的，放到阻塞线程池中执行
+async fn blocking<T, F>(f: F) -> Result<T, SevenZipError>
+where
+  T: Send + 'static,
+  F: FnOnce() -> Result<T, SevenZipError> + Send + 'static,
+{
+  tokio::task::spawn_blocking(f)
+    .await
+    .map_err(|e| SevenZipError::Backend(e.to_string()))?
+}
+
+impl Archive for NativeArchive {
+  fn list<'a>(&'a self, archive: &'a Path) -> ArchiveFuture<'a, Vec<ArchiveEntry>> {
+    let archive = archive.to_path_buf();
+    Box::pin(blocking(move || Self::list_blocking(&archive)))
+  }
+
+  fn read<'a>(&'a self, archive: &'a Path, entry: &'a str) -> ArchiveFuture<'a, Vec<u8>> {
+    let archive = archive.to_path_buf();
+    let entry = entry.to_string();
+    Box::pin(blocking(move || Self::read_blocking(&archive, &entry)))
+  }
+
+  fn extract_with_events<'a>(
+    &'a self,
+    archive: &'a Path,
+    out: &'a Path,
+    events: Option<mpsc::UnboundedSender<SevenZipEvent>>,
+  ) -> ArchiveFuture<'a, ()> {
+    info!("extract {:?} to {:?} natively", archive, out);
+    let archive = archive.to_path_buf();
+    let out = out.to_path_buf();
+    Box::pin(async move {
+      blocking(move || Self::extract_blocking(&archive, &out)).await?;
+      if let Some(tx) = events {
+        let _ = tx.send(SevenZipEvent::Progress(100));
+      }
+      Ok(())
+    })
+  }
+
+  fn test<'a>(&'a self, archive: &'a Path) -> ArchiveFuture<'a, ()> {
+    let archive = archive.to_path_buf();
+    Box::pin(blocking(move || Self::test_blocking(&archive)))
+  }
+}
+
+/*
+ * 运行时选择的后端
+ * sevenzip: 外部的 7z，支持所有格式与加密
+ * native: 内置的 7z 与 zip 解压
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveBackend {
+  SevenZip(PathBuf),
+  Native,
+}
+
+impl ArchiveBackend {
+  pub fn open(&self) -> anyhow::Result<Arc<dyn Archive>> {
+    match self {
+      Self::SevenZip(exe) => Ok(Arc::new(SevenZip::new(exe.clone())?)),
+      Self::Native => Ok(Arc::new(NativeArchive)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Write;
+  use sevenz_rust::{SevenZArchiveEntry, SevenZWriter};
+  use zip::write::{FileOptions, ZipWriter};
+  use zip::CompressionMethod;
+
+  use super::{format_filetime, Archive, ArchiveBackend, NativeArchive};
+  use crate::output::SevenZipError;
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    // 1970-01-01 与 2021-05-01 12:00:01 UTC
+    assert_eq!(format_filetime(116_444_736_000_000_000), "1970-01-01 00:00:00");
+    assert_eq!(format_filetime(116_444_736_000_000_000 + 1_619_870_401 * 10_000_000), "2021-05-01 12:00:01");
+
+    let dir = tempfile::tempdir()?;
+    let src = dir.path().join("src");
+    std::fs::create_dir_all(src.join("Chrome"))?;
+    std::fs::write(src.join("Chrome/chrome.exe"), "MZ!")?;
+    std::fs::write(src.join("Chrome.wcs"), "EXEC chrome.exe")?;
+
+    let zip_path = dir.path().join("Chrome_1.0_Cno.zip");
+    let mut zip = ZipWriter::new(std::fs::File::create(&zip_path)?);
+    zip.add_directory("Chrome/", FileOptions::default())?;
+    // 不压缩，方便下面改坏内容
+    zip.start_file("Chrome/chrome.exe", FileOptions::default().compression_method(CompressionMethod::Stored))?;
+    zip.write_all(b"MZ!")?;
+    zip.start_file("Chrome.wcs", FileOptions::default())?;
+    zip.write_all(b"EXEC chrome.exe")?;
+    zip.finish()?;
+
+    let sevenz_path = dir.path().join("Chrome_1.0_Cno.7z");
+    sevenz_rust::compress_to_path(&src, &sevenz_path)?;
+
+    let archive = ArchiveBackend::Native.open()?;
+    for path in [&zip_path, &sevenz_path] {
+      let mut entries = archive.list(path).await?;
+      entries.sort_by(|a, b| a.path.cmp(&b.path));
+      let names = entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>();
+      assert_eq!(names, vec!["Chrome", "Chrome.wcs", "Chrome/chrome.exe"], "{:?}", path);
+      assert!(entries[0].is_dir);
+      assert_eq!(entries[2].size, 3);
+      assert_eq!(archive.read(path, "Chrome\\chrome.exe").await?, b"MZ!");
+      assert!(matches!(archive.read(path, "missing.txt").await, Err(SevenZipError::NotFound { .. })));
+      archive.test(path).await?;
+
+      let out = dir.path().join(format!("out_{}", path.extension().unwrap().to_string_lossy()));
+      archive.extract_all(path, &out).await?;
+      assert_eq!(std::fs::read_to_string(out.join("Chrome.wcs"))?, "EXEC chrome.exe");
+    }
+
+    // 损坏的 zip：改掉文件内容，CRC 不再匹配
+    let bytes = std::fs::read(&zip_path)?;
+    let at = bytes.windows(3).position(|w| w == b"MZ!").unwrap();
+    let mut broken = bytes.clone();
+    broken[at] = b'P';
+    let broken_path = dir.path().join("Broken.zip");
+    std::fs::write(&broken_path, broken)?;
+    assert!(matches!(NativeArchive.test(&broken_path).await, Err(SevenZipError::CrcFailed { .. })));
+
+    let text = dir.path().join("a.txt");
+    std::fs::write(&text, "not an archive")?;
+    assert!(matches!(NativeArchive.list(&text).await, Err(SevenZipError::CannotOpen { .. })));
+    assert!(matches!(NativeArchive.list(&dir.path().join("nope.7z")).await, Err(SevenZipError::NotFound { .. })));
+
+    // 条目名指向解压目录之外时拒绝解压
+    let evil_path = dir.path().join("Evil.7z");
+    let mut writer = SevenZWriter::create(&evil_path)?;
+    let mut entry = SevenZArchiveEntry::new();
+    entry.name = "../evil".to_string();
+    entry.has_stream = true;
+    writer.push_archive_entry(entry, Some(&b"evil"[..]))?;
+    writer.finish()?;
+    let out = dir.path().join("out_evil");
+    assert!(matches!(NativeArchive.extract_all(&evil_path, &out).await, Err(SevenZipError::Backend(_))));
+    assert!(!dir.path().join("evil").exists());
+
+    Ok(())
+  }
+
+  // 用 sh 脚本代替 7z，记录参数并报告进度
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn seven_zip() -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use tokio::sync::mpsc;
+    use crate::output::SevenZipEvent;
+
+    let dir = tempfile::tempdir()?;
+    let exe = dir.path().join("7z");
+    let args = dir.path().join("args.txt");
+    let script = [
+      "#!/bin/sh".to_string(),
+      format!("echo \"$@\" > '{}'", args.display()),
+      "printf ' 40%%\\b\\b\\b\\b100%%\\nEverything is Ok\\n'".to_string(),
+    ].join("\n");
+    std::fs::write(&exe, script)?;
+    std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755))?;
+
+    let archive = ArchiveBackend::SevenZip(exe).open()?;
+    let out = dir.path().join("out");
+    archive.extract_all(Path::new("a.7z"), &out).await?;
+    // 已存在的文件保持不变
+    assert!(std::fs::read_to_string(&args)?.split(' ').any(|a| a == "-aos"));
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    archive.extract_with_events(Path::new("a.7z"), &out, Some(tx)).await?;
+    let mut progress = vec![];
+    while let Some(SevenZipEvent::Progress(p)) = rx.recv().await {
+      progress.push(p);
+    }
+    assert_eq!(progress, vec![40, 100]);
+
+    Ok(())
+  }
+}
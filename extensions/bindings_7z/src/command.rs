@@ -0,0 +1,369 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+use crate::output::{parse_listing, ArchiveEntry, ProgressParser, SevenZipError, SevenZipEvent, SevenZipOutput};
+
+use log::info;
+
+// 解压时目标文件已存在的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwriteMode {
+  // -aoa
+  #[default]
+  Overwrite,
+  // -aos
+  Skip,
+  // -aou，重命名解压出的文件
+  RenameNew,
+  // -aot，重命名已有的文件
+  RenameExisting,
+}
+
+impl OverwriteMode {
+  fn switch(&self) -> &'static str {
+    match self {
+      Self::Overwrite => "-aoa",
+      Self::Skip => "-aos",
+      Self::RenameNew => "-aou",
+      Self::RenameExisting => "-aot",
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+  // `x`，保留目录结构；`files` 为空时解压全部
+  Extract { out: PathBuf, files: Vec<String> },
+  // `l -slt`
+  List,
+  // `t`
+  Test,
+  // `a`，压缩包不存在时新建
+  Add { files: Vec<PathBuf> },
+  // `e -so`，把文件内容写到标准输出
+  Print { files: Vec<String> },
+}
+
+/*
+ * 一次 7z 调用
+ * 所有参数都作为独立的 argv 元素传递，路径不加引号，由 `Command` 负责转义
+ * 开关放在 `--` 之前，以 `-` 开头的文件名不会被当成开关
+ */
+#[derive(Debug, Clone)]
+pub struct SevenZipCommand {
+  exe: PathBuf,
+  archive: PathBuf,
+  operation: Operation,
+  overwrite: OverwriteMode,
+  include: Vec<String>,
+  exclude: Vec<String>,
+  recursive: bool,
+  password: Option<String>,
+  threads: Option<usize>,
+  progress: bool,
+}
+
+impl SevenZipCommand {
+  pub(crate) fn new(exe: &Path, archive: &Path, operation: Operation) -> Self {
+    Self {
+      exe: exe.to_path_buf(),
+      archive: archive.to_path_buf(),
+      operation,
+      overwrite: OverwriteMode::default(),
+      include: vec![],
+      exclude: vec![],
+      recursive: false,
+      password: None,
+      threads: None,
+      progress: false,
+    }
+  }
+
+  pub fn overwrite(mut self, mode: OverwriteMode) -> Self {
+    self.overwrite = mode;
+    self
+  }
+
+  // 通配符，如 `*.wcs`
+  pub fn include(mut self, wildcard: &str) -> Self {
+    self.include.push(wildcard.to_string());
+    self
+  }
+
+  pub fn exclude(mut self, wildcard: &str) -> Self {
+    self.exclude.push(wildcard.to_string());
+    self
+  }
+
+  // 通配符匹配子目录，`-r`
+  pub fn recursive(mut self, recursive: bool) -> Self {
+    self.recursive = recursive;
+    self
+  }
+
+  // 密码通过 `-p` 参数传给 7z，同一系统中的其他进程可以从命令行看到；日志中会隐去
+  pub fn password(mut self, password: &str) -> Self {
+    self.password = Some(password.to_string());
+    self
+  }
+
+  pub fn threads(mut self, threads: usize) -> Self {
+    self.threads = Some(threads);
+    self
+  }
+
+  // 在标准输出中报告进度，`-bsp1`
+  pub fn progress(mut self, progress: bool) -> Self {
+    self.progress = progress;
+    self
+  }
+
+  pub fn operation(&self) -> &Operation {
+    &self.operation
+  }
+
+  pub fn args(&self) -> Vec<OsString> {
+    fn switch(prefix: &str, value: &OsStr) -> OsString {
+      let mut s = OsString::from(prefix);
+      s.push(value);
+      s
+    }
+
+    let mut args: Vec<OsString> = vec![];
+    let command = match &self.operation {
+      Operation::Extract { .. } => "x",
+      Operation::List => "l",
+      Operation::Test => "t",
+      Operation::Add { .. } => "a",
+      Operation::Print { .. } => "e",
+    };
+    args.push(command.into());
+    args.push("-y".into());
+    match &self.operation {
+      Operation::Extract { out, .. } => {
+        args.push(self.overwrite.switch().into());
+        args.push(switch("-o", out.as_os_str()));
+      }
+      Operation::List => args.push("-slt".into()),
+      Operation::Print { .. } => args.push("-so".into()),
+      _ => {}
+    }
+    if self.recursive {
+      args.push("-r".into());
+    }
+    for w in &self.include {
+      args.push(switch("-i!", OsStr::new(w)));
+    }
+    for w in &self.exclude {
+      args.push(switch("-x!", OsStr::new(w)));
+    }
+    if let Some(p) = &self.password {
+      args.push(switch("-p", OsStr::new(p)));
+    }
+    if let Some(n) = self.threads {
+      args.push(format!("-mmt={}", n).into());
+    }
+    if self.progress {
+      args.push("-bsp1".into());
+    }
+    args.push("--".into());
+    args.push(self.archive.clone().into());
+    match &self.operation {
+      Operation::Extract { files, .. } => args.extend(files.iter().map(OsString::from)),
+      Operation::Add { files } => args.extend(files.iter().map(|f| f.clone().into_os_string())),
+      Operation::Print { files } => args.extend(files.iter().map(OsString::from)),
+      _ => {}
+    }
+    args
+  }
+
+  pub fn command(&self) -> Command {
+    let mut command = Command::new(&self.exe);
+    command
+      .args(self.args())
+      // 加密的压缩包读不到密码时直接失败，不会卡在输入提示
+      .stdin(Stdio::null())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .kill_on_drop(true);
+    command
+  }
+
+  pub fn spawn(&self) -> anyhow::Result<Child> {
+    info!("run {}", self);
+    Ok(self.command().spawn()?)
+  }
+
+  pub async fn output(&self) -> anyhow::Result<Output> {
+    Ok(self.spawn()?.wait_with_output().await?)
+  }
+
+  pub async fn run(&self) -> Result<SevenZipOutput, SevenZipError> {
+    self.run_with_events(None).await
+  }
+
+  // 有 `events` 时打开 `-bsp1`，边读输出边发送进度
+  pub async fn run_with_events(&self, events: Option<mpsc::UnboundedSender<SevenZipEvent>>) -> Result<SevenZipOutput, SevenZipError> {
+    let command = self.clone().progress(self.progress || events.is_some());
+    info!("run {}", command);
+    let mut child = command.command().spawn().map_err(|e| SevenZipError::Spawn(e.to_string()))?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    let read_stdout = async move {
+      let mut out = vec![];
+      if let Some(mut stdout) = stdout {
+        let mut parser = ProgressParser::new();
+        let mut buf = [0u8; 4096];
+        loop {
+          let n = stdout.read(&mut buf).await?;
+          if n == 0 {
+            break;
+          }
+          out.extend_from_slice(&buf[..n]);
+          if let Some(tx) = &events {
+            for p in parser.feed(&String::from_utf8_lossy(&buf[..n])) {
+              let _ = tx.send(SevenZipEvent::Progress(p));
+            }
+          }
+        }
+      }
+      Ok::<_, std::io::Error>(out)
+    };
+    let (stdout, stderr, status) = tokio::join!(read_stdout, read_all(stderr), child.wait());
+    let io = |e: std::io::Error| SevenZipError::Spawn(e.to_string());
+    SevenZipOutput::new(
+      status.map_err(io)?.code(),
+      String::from_utf8_lossy(&stdout.map_err(io)?).to_string(),
+      String::from_utf8_lossy(&stderr.map_err(io)?).to_string(),
+    )
+  }
+
+  // 只对 `print` 有意义，标准输出是文件内容，不做文本转换
+  pub async fn bytes(&self) -> Result<Vec<u8>, SevenZipError> {
+    info!("run {}", self);
+    let output = self.command().output().await.map_err(|e| SevenZipError::Spawn(e.to_string()))?;
+    SevenZipOutput::new(output.status.code(), String::new(), String::from_utf8_lossy(&output.stderr).to_string())?;
+    Ok(output.stdout)
+  }
+
+  // 只对 `list` 有意义
+  pub async fn entries(&self) -> Result<Vec<ArchiveEntry>, SevenZipError> {
+    Ok(parse_listing(&self.run().await?.stdout))
+  }
+}
+
+// 用于日志，密码显示为 `-p***`
+impl fmt::Display for SevenZipCommand {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.exe.display())?;
+    let mut switches = true;
+    for a in self.args() {
+      let a = a.to_string_lossy();
+      if a == "--" {
+        switches = false;
+      }
+      if switches && a.starts_with("-p") {
+        write!(f, " -p***")?;
+      } else if a.contains(char::is_whitespace) {
+        write!(f, " \"{}\"", a)?;
+      } else {
+        write!(f, " {}", a)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+async fn read_all<R: AsyncRead + Unpin>(reader: Option<R>) -> std::io::Result<Vec<u8>> {
+  let mut out = vec![];
+  if let Some(mut r) = reader {
+    r.read_to_end(&mut out).await?;
+  }
+  Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::ffi::OsString;
+  use std::path::{Path, PathBuf};
+
+  use super::{Operation, OverwriteMode, SevenZipCommand};
+
+  #[test]
+  fn it_works() {
+    let out = Path::new("X:/Program Files/Edgeless/Chrome");
+    let cmd = SevenZipCommand::new(
+      Path::new("7z"),
+      Path::new("-odd name.7z"),
+      Operation::Extract { out: out.to_path_buf(), files: vec!["a b.wcs".to_string()] },
+    )
+      .overwrite(OverwriteMode::Skip)
+      .exclude("*.tmp")
+      .password("p w")
+      .threads(2);
+    let args = cmd.args();
+    let expected = [
+      "x", "-y", "-aos", "-oX:/Program Files/Edgeless/Chrome", "-x!*.tmp", "-pp w", "-mmt=2", "--", "-odd name.7z", "a b.wcs",
+    ].iter().map(OsString::from).collect::<Vec<_>>();
+    assert_eq!(args, expected);
+    assert_eq!(
+      cmd.to_string(),
+      "7z x -y -aos \"-oX:/Program Files/Edgeless/Chrome\" -x!*.tmp -p*** -mmt=2 -- \"-odd name.7z\" \"a b.wcs\""
+    );
+
+    let list = SevenZipCommand::new(Path::new("7z"), Path::new("a.7z"), Operation::List).args();
+    assert_eq!(list, ["l", "-y", "-slt", "--", "a.7z"].iter().map(OsString::from).collect::<Vec<_>>());
+    let add = SevenZipCommand::new(Path::new("7z"), Path::new("a.7z"), Operation::Add { files: vec![PathBuf::from("dir")] })
+      .include("*.wcs")
+      .recursive(true)
+      .args();
+    assert_eq!(add, ["a", "-y", "-r", "-i!*.wcs", "--", "a.7z", "dir"].iter().map(OsString::from).collect::<Vec<_>>());
+    let print = SevenZipCommand::new(Path::new("7z"), Path::new("a.7z"), Operation::Print { files: vec!["a.wcs".to_string()] }).args();
+    assert_eq!(print, ["e", "-y", "-so", "--", "a.7z", "a.wcs"].iter().map(OsString::from).collect::<Vec<_>>());
+  }
+
+  // 用 sh 脚本代替 7z，按压缩包名决定输出
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn run() -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::sync::mpsc;
+    use crate::output::{SevenZipError, SevenZipEvent};
+
+    let dir = tempfile::tempdir()?;
+    let exe = dir.path().join("7z");
+    let script = [
+      "#!/bin/sh",
+      "for a; do archive=\"$a\"; done",
+      "case \"$archive\" in",
+      "  *locked*) echo 'ERROR: Wrong password : a.txt' >&2; exit 2;;",
+      "  *) printf '  0%%\\b\\b\\b\\b 50%%\\b\\b\\b\\b100%%\\nEverything is Ok\\n';;",
+      "esac",
+    ].join("\n");
+    tokio::fs::write(&exe, script).await?;
+    tokio::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).await?;
+
+    let out = dir.path().join("out");
+    let cmd = SevenZipCommand::new(&exe, Path::new("ok.7z"), Operation::Extract { out: out.clone(), files: vec![] });
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let output = cmd.run_with_events(Some(tx)).await?;
+    assert!(output.stdout.contains("Everything is Ok"));
+    let mut progress = vec![];
+    while let Some(SevenZipEvent::Progress(p)) = rx.recv().await {
+      progress.push(p);
+    }
+    assert_eq!(progress, vec![0, 50, 100]);
+
+    let locked = SevenZipCommand::new(&exe, Path::new("locked.7z"), Operation::Test);
+    assert_eq!(locked.run().await.unwrap_err(), SevenZipError::WrongPassword);
+    let missing = SevenZipCommand::new(&dir.path().join("nope"), Path::new("a.7z"), Operation::Test);
+    assert!(matches!(missing.run().await, Err(SevenZipError::Spawn(_))));
+
+    Ok(())
+  }
+}
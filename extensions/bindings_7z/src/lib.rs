@@ -1,49 +1,96 @@
-use anyhow::anyhow;
-use tokio::process::Child;
-use std::path::PathBuf;
-use tokio::process::Command;
-use std::env;
-use std::process;
-
-use log::{info, error};
-
-pub struct SevenZip {
-  exe_path: PathBuf
-}
-
-impl SevenZip {
-  pub fn new(exe: PathBuf) -> anyhow::Result<Self> {
-    info!("create with {:?}", exe);
-    if !exe.exists() {
-      error!("not found bin file, at {:?}", exe);
-      return Err(anyhow!("not found bin file"));
-    }
-
-    Ok(Self {
-      exe_path: exe,
-    })
-  }
-
-  pub async fn extract_all_files(&self, file: &str, out: &str) -> anyhow::Result<Child> {
-    info!("extract all files with file = {:?}, out = {:?}", file, out);
-    Ok(Command::new(&self.exe_path)
-      .args(&["x", file, "-y", "-aos", &format!("-o\"{}\"", out)])
-      .current_dir(env::current_dir()?)
-      .stdout(process::Stdio::piped())
-      .stderr(process::Stdio::piped())
-      .stdin(process::Stdio::piped())
-      .spawn()?)
-  }
-
-  pub async fn run(&self, args: &Vec<&str>) -> anyhow::Result<Child> {
-    info!("run command, args = {:#?}", args);
-    Ok(Command::new(&self.exe_path)
-      .args(args)
-      .current_dir(env::current_dir()?)
-      .stdout(process::Stdio::piped())
-      .stderr(process::Stdio::piped())
-      .stdin(process::Stdio::piped())
-      .spawn()?)
-  }
-}
-
+pub mod archive;
+pub mod command;
+pub mod output;
+
+use anyhow::anyhow;
+use tokio::process::Child;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use std::env;
+use std::process;
+
+use log::{info, error};
+
+pub use archive::{Archive, ArchiveBackend, ArchiveFuture, NativeArchive};
+pub use command::{Operation, OverwriteMode, SevenZipCommand};
+pub use output::{ArchiveEntry, SevenZipError, SevenZipEvent, SevenZipOutput};
+
+pub struct SevenZip {
+  exe_path: PathBuf
+}
+
+impl SevenZip {
+  pub fn new(exe: PathBuf) -> anyhow::Result<Self> {
+    info!("create with {:?}", exe);
+    if !exe.exists() {
+      error!("not found bin file, at {:?}", exe);
+      return Err(anyhow!("not found bin file"));
+    }
+
+    Ok(Self {
+      exe_path: exe,
+    })
+  }
+
+  pub fn extract(&self, archive: &Path, out: &Path) -> SevenZipCommand {
+    SevenZipCommand::new(&self.exe_path, archive, Operation::Extract {
+      out: out.to_path_buf(),
+      files: vec![],
+    })
+  }
+
+  // 只解压压缩包中的指定文件，如 `Chrome/manifest.json`
+  pub fn extract_files(&self, archive: &Path, out: &Path, files: &[&str]) -> SevenZipCommand {
+    SevenZipCommand::new(&self.exe_path, archive, Operation::Extract {
+      out: out.to_path_buf(),
+      files: files.iter().map(|f| f.to_string()).collect(),
+    })
+  }
+
+  pub fn list(&self, archive: &Path) -> SevenZipCommand {
+    SevenZipCommand::new(&self.exe_path, archive, Operation::List)
+  }
+
+  pub fn test(&self, archive: &Path) -> SevenZipCommand {
+    SevenZipCommand::new(&self.exe_path, archive, Operation::Test)
+  }
+
+  // 读出单个文件的内容
+  pub fn print(&self, archive: &Path, file: &str) -> SevenZipCommand {
+    SevenZipCommand::new(&self.exe_path, archive, Operation::Print {
+      files: vec![file.to_string()],
+    })
+  }
+
+  pub fn add(&self, archive: &Path, files: &[&Path]) -> SevenZipCommand {
+    SevenZipCommand::new(&self.exe_path, archive, Operation::Add {
+      files: files.iter().map(|f| f.to_path_buf()).collect(),
+    })
+  }
+
+  // 已存在的文件跳过
+  pub async fn extract_all_files(&self, file: &Path, out: &Path) -> anyhow::Result<Child> {
+    info!("extract all files with file = {:?}, out = {:?}", file, out);
+    self.extract(file, out).overwrite(OverwriteMode::Skip).spawn()
+  }
+
+  pub async fn test_archive(&self, file: &Path) -> anyhow::Result<Child> {
+    info!("test archive with file = {:?}", file);
+    self.test(file).spawn()
+  }
+
+  pub async fn run<I, S>(&self, args: I) -> anyhow::Result<Child>
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+  {
+    Ok(Command::new(&self.exe_path)
+      .args(args)
+      .current_dir(env::current_dir()?)
+      .stdout(process::Stdio::piped())
+      .stderr(process::Stdio::piped())
+      .stdin(process::Stdio::piped())
+      .spawn()?)
+  }
+}
@@ -0,0 +1,322 @@
+use std::fmt;
+
+// `7z l -slt` 中的一项
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveEntry {
+  pub path: String,
+  pub size: u64,
+  pub packed_size: Option<u64>,
+  pub attributes: String,
+  pub crc: Option<u32>,
+  // 原样保留，如 `2021-05-01 12:00:00`
+  pub modified: Option<String>,
+  pub is_dir: bool,
+  pub encrypted: bool,
+}
+
+/*
+ * 解析 `7z l -slt` 的输出
+ * `----------` 之前是压缩包本身的信息，之后每项以空行分隔，每行为 `Key = Value`
+ */
+pub fn parse_listing(stdout: &str) -> Vec<ArchiveEntry> {
+  let mut entries = vec![];
+  let body = match stdout.find("\n----------") {
+    Some(i) => &stdout[i + 1..],
+    None => return entries,
+  };
+
+  let mut current: Option<ArchiveEntry> = None;
+  for line in body.lines().skip(1).map(|l| l.trim_end_matches('\r')) {
+    let (key, value) = match line.split_once(" = ").or_else(|| line.strip_suffix(" =").map(|k| (k, ""))) {
+      Some(kv) => kv,
+      None => {
+        entries.extend(current.take());
+        continue;
+      }
+    };
+    if key == "Path" {
+      entries.extend(current.take());
+      current = Some(ArchiveEntry {
+        path: value.to_string(),
+        ..Default::default()
+      });
+      continue;
+    }
+    let entry = match current.as_mut() {
+      Some(e) => e,
+      None => continue,
+    };
+    match key {
+      "Size" => entry.size = value.parse().unwrap_or_default(),
+      "Packed Size" => entry.packed_size = value.parse().ok(),
+      "Modified" if !value.is_empty() => entry.modified = Some(value.to_string()),
+      "Attributes" => {
+        entry.attributes = value.to_string();
+        entry.is_dir |= value.starts_with('D');
+      }
+      "CRC" => entry.crc = u32::from_str_radix(value, 16).ok(),
+      "Folder" => entry.is_dir |= value == "+",
+      "Encrypted" => entry.encrypted = value == "+",
+      _ => {}
+    }
+  }
+  entries.extend(current.take());
+  entries
+}
+
+/*
+ * 从 `-bsp1` 的输出中提取进度百分比
+ * 7z 用退格覆盖同一行，输出可能在数字中间断开，末尾的数字留到下一块再处理
+ */
+#[derive(Debug, Default)]
+pub struct ProgressParser {
+  carry: String,
+  last: Option<u8>,
+}
+
+impl ProgressParser {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // 返回新出现的百分比，与上一次相同的不重复返回
+  pub fn feed(&mut self, chunk: &str) -> Vec<u8> {
+    let text = format!("{}{}", std::mem::take(&mut self.carry), chunk);
+    let tail = text.len() - text.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    self.carry = text[text.len() - tail..].to_string();
+    let text = &text[..text.len() - tail];
+
+    let mut out = vec![];
+    let mut digits = String::new();
+    for c in text.chars() {
+      match c {
+        '0'..='9' => digits.push(c),
+        '%' if !digits.is_empty() => {
+          if let Ok(p @ 0..=100) = digits.parse::<u8>() {
+            if self.last != Some(p) {
+              self.last = Some(p);
+              out.push(p);
+            }
+          }
+          digits.clear();
+        }
+        _ => digits.clear(),
+      }
+    }
+    out
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SevenZipError {
+  // 密码错误或缺少密码
+  WrongPassword,
+  CrcFailed { path: Option<String> },
+  DataError { path: Option<String> },
+  UnexpectedEnd,
+  DiskFull,
+  OutOfMemory,
+  // 不是压缩包或格式不支持
+  CannotOpen { path: Option<String> },
+  NotFound { path: Option<String> },
+  // 命令行错误，退出码 7
+  Usage(String),
+  // 无法启动 7z
+  Spawn(String),
+  Failed { code: Option<i32>, message: String },
+  // 内置解压后端的其他错误
+  Backend(String),
+}
+
+impl fmt::Display for SevenZipError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn with_path(f: &mut fmt::Formatter<'_>, what: &str, path: &Option<String>) -> fmt::Result {
+      match path {
+        Some(p) => write!(f, "{}: {}", what, p),
+        None => write!(f, "{}", what),
+      }
+    }
+    match self {
+      Self::WrongPassword => write!(f, "wrong password"),
+      Self::CrcFailed { path } => with_path(f, "CRC failed", path),
+      Self::DataError { path } => with_path(f, "data error", path),
+      Self::UnexpectedEnd => write!(f, "unexpected end of archive"),
+      Self::DiskFull => write!(f, "not enough disk space"),
+      Self::OutOfMemory => write!(f, "not enough memory"),
+      Self::CannotOpen { path } => with_path(f, "cannot open as archive", path),
+      Self::NotFound { path } => with_path(f, "file not found", path),
+      Self::Usage(message) => write!(f, "command line error, {}", message),
+      Self::Spawn(message) => write!(f, "failed to run 7z, {}", message),
+      Self::Failed { code: Some(code), message } => write!(f, "exit code {}, {}", code, message),
+      Self::Failed { code: None, message } => write!(f, "terminated, {}", message),
+      Self::Backend(message) => write!(f, "{}", message),
+    }
+  }
+}
+
+impl std::error::Error for SevenZipError {}
+
+// `ERROR: Data Error : a.txt` 中冒号后的路径
+fn path_after(line: &str, what: &str) -> Option<String> {
+  let i = line.to_lowercase().find(&what.to_lowercase())?;
+  let rest = line[i + what.len()..].trim_start().strip_prefix(':')?.trim();
+  match rest.is_empty() {
+    true => None,
+    false => Some(rest.to_string()),
+  }
+}
+
+/*
+ * 按退出码与输出判断失败原因，成功（退出码 0 或 1）时返回 `None`
+ * 7z 的退出码：1 警告，2 致命错误，7 命令行错误，8 内存不足，255 被中止
+ */
+pub fn classify(code: Option<i32>, stdout: &str, stderr: &str) -> Option<SevenZipError> {
+  if matches!(code, Some(0) | Some(1)) {
+    return None;
+  }
+  let lines = stderr.lines().chain(stdout.lines())
+    .map(|l| l.trim())
+    .filter(|l| !l.is_empty())
+    .collect::<Vec<_>>();
+  let find = |what: &str| lines.iter().find(|l| l.to_lowercase().contains(&what.to_lowercase())).copied();
+
+  if find("Wrong password").is_some() {
+    return Some(SevenZipError::WrongPassword);
+  }
+  if let Some(l) = find("CRC Failed") {
+    return Some(SevenZipError::CrcFailed { path: path_after(l, "CRC Failed") });
+  }
+  if let Some(l) = find("Data Error") {
+    return Some(SevenZipError::DataError { path: path_after(l, "Data Error") });
+  }
+  if find("Unexpected end").is_some() {
+    return Some(SevenZipError::UnexpectedEnd);
+  }
+  if find("not enough space").is_some() || find("No space left").is_some() {
+    return Some(SevenZipError::DiskFull);
+  }
+  if let Some(i) = lines.iter().position(|l| l.to_lowercase().contains("open the file as archive")) {
+    // 路径在上一行的 `ERROR: <path>`
+    let path = i.checked_sub(1)
+      .and_then(|i| lines[i].strip_prefix("ERROR:"))
+      .map(|p| p.trim().to_string());
+    return Some(SevenZipError::CannotOpen { path });
+  }
+  if let Some(l) = find("cannot find the file").or_else(|| find("No such file")) {
+    let path = lines.iter().position(|x| *x == l).and_then(|i| lines.get(i + 1)).map(|s| s.to_string());
+    return Some(SevenZipError::NotFound { path });
+  }
+  let message = lines.iter()
+    .find(|l| l.starts_with("ERROR") || l.starts_with("Command Line Error"))
+    .or_else(|| lines.last())
+    .map(|l| l.to_string())
+    .unwrap_or_default();
+  match code {
+    Some(7) => Some(SevenZipError::Usage(message)),
+    Some(8) => Some(SevenZipError::OutOfMemory),
+    _ => Some(SevenZipError::Failed { code, message }),
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SevenZipOutput {
+  pub code: Option<i32>,
+  pub stdout: String,
+  pub stderr: String,
+  // 以 `WARNING` 开头的行
+  pub warnings: Vec<String>,
+}
+
+impl SevenZipOutput {
+  pub fn new(code: Option<i32>, stdout: String, stderr: String) -> Result<Self, SevenZipError> {
+    if let Some(e) = classify(code, &stdout, &stderr) {
+      return Err(e);
+    }
+    let warnings = stderr.lines().chain(stdout.lines())
+      .map(|l| l.trim())
+      .filter(|l| l.to_uppercase().starts_with("WARNING"))
+      .map(|l| l.to_string())
+      .collect();
+    Ok(Self {
+      code,
+      stdout,
+      stderr,
+      warnings,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SevenZipEvent {
+  Progress(u8),
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{classify, parse_listing, ProgressParser, SevenZipError, SevenZipOutput};
+
+  #[test]
+  fn it_works() {
+    let listing = [
+      "7-Zip 21.07 (x64) : Copyright (c) 1999-2021 Igor Pavlov : 2021-12-26",
+      "",
+      "Listing archive: Chrome_90.0_Cno.7z",
+      "",
+      "--",
+      "Path = Chrome_90.0_Cno.7z",
+      "Type = 7z",
+      "",
+      "----------",
+      "Path = Chrome",
+      "Size = 0",
+      "Packed Size = 0",
+      "Modified = 2021-05-01 12:00:00",
+      "Attributes = D",
+      "CRC = ",
+      "Encrypted = -",
+      "",
+      "Path = Chrome\\chrome.exe",
+      "Size = 2048",
+      "Packed Size = 1024",
+      "Modified = 2021-05-01 12:00:01",
+      "Attributes = A",
+      "CRC = 1A2B3C4D",
+      "Encrypted = +",
+      "",
+    ].join("\r\n");
+    let entries = parse_listing(&listing);
+    assert_eq!(entries.len(), 2);
+    assert!(entries[0].is_dir);
+    assert_eq!(entries[0].crc, None);
+    assert_eq!(entries[1].path, "Chrome\\chrome.exe");
+    assert_eq!((entries[1].size, entries[1].packed_size), (2048, Some(1024)));
+    assert_eq!(entries[1].crc, Some(0x1A2B3C4D));
+    assert_eq!(entries[1].modified.as_deref(), Some("2021-05-01 12:00:01"));
+    assert!(entries[1].encrypted && !entries[1].is_dir);
+    assert!(parse_listing("no listing").is_empty());
+
+    let mut p = ProgressParser::new();
+    assert_eq!(p.feed("\u{8}\u{8}  0%\u{8}\u{8}\u{8}\u{8} 1"), vec![0]);
+    assert_eq!(p.feed("2% 3 - a.exe\u{8}\u{8} 12%\u{8} 100%"), vec![12, 100]);
+    assert_eq!(p.feed("250%"), Vec::<u8>::new());
+
+    assert_eq!(classify(Some(0), "", ""), None);
+    assert_eq!(classify(Some(2), "", "ERROR: Wrong password : a.txt"), Some(SevenZipError::WrongPassword));
+    assert_eq!(
+      classify(Some(2), "", "ERROR: CRC Failed : Chrome\\a.exe"),
+      Some(SevenZipError::CrcFailed { path: Some("Chrome\\a.exe".to_string()) })
+    );
+    assert_eq!(classify(Some(2), "", "Data Error"), Some(SevenZipError::DataError { path: None }));
+    assert_eq!(classify(Some(2), "ERRORS:\nUnexpected end of archive", ""), Some(SevenZipError::UnexpectedEnd));
+    assert_eq!(classify(Some(2), "", "ERROR: There is not enough space on the disk."), Some(SevenZipError::DiskFull));
+    assert_eq!(
+      classify(Some(2), "", "ERROR: a.txt\nCan not open the file as archive"),
+      Some(SevenZipError::CannotOpen { path: Some("a.txt".to_string()) })
+    );
+    assert_eq!(classify(Some(7), "", "Command Line Error:\nUnknown switch:\n-q"), Some(SevenZipError::Usage("Command Line Error:".to_string())));
+    assert_eq!(classify(Some(2), "", "boom").unwrap().to_string(), "exit code 2, boom");
+
+    let out = SevenZipOutput::new(Some(1), "WARNING: a.txt\nok".to_string(), String::new()).unwrap();
+    assert_eq!(out.warnings, vec!["WARNING: a.txt"]);
+  }
+}
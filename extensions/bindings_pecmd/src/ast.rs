@@ -0,0 +1,162 @@
+use std::fmt;
+
+// 源码中的位置，`start` 与 `end` 为字节偏移，`line` 与 `column` 从 1 开始
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordPart {
+    Text(String),
+    // `%name%`
+    Var(String),
+}
+
+// 可能含 `%` 变量的参数
+// 逗号分隔的参数项（如 `LINK` 的各项）已去掉包围的引号，其他参数保持原文
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+    pub span: Span,
+}
+
+impl Word {
+    pub fn vars(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|p| match p {
+            WordPart::Var(v) => Some(v.as_str()),
+            WordPart::Text(_) => None,
+        })
+    }
+
+    // 用 `lookup` 展开变量，未定义的变量保留原样
+    pub fn expand<F: Fn(&str) -> Option<String>>(&self, lookup: F) -> String {
+        self.parts.iter()
+            .map(|p| match p {
+                WordPart::Text(t) => t.clone(),
+                WordPart::Var(v) => lookup(v).unwrap_or_else(|| format!("%{}%", v)),
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for p in &self.parts {
+            match p {
+                WordPart::Text(t) => write!(f, "{}", t.replace('%', "%%"))?,
+                WordPart::Var(v) => write!(f, "%{}%", v)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/*
+ * EXEC 命令行前的标记
+ * `=` 等待结束，`!` 隐藏窗口，其余标记原样保留在 `flags`
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecFlags {
+    pub wait: bool,
+    pub hide: bool,
+    pub flags: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileOp {
+    // FILE src=>dst
+    Copy { from: Word, to: Word },
+    // FILE src->dst
+    Move { from: Word, to: Word },
+    // FILE path
+    Delete { path: Word },
+}
+
+/*
+ * ENVI name=value   当前进程
+ * ENVI $name=value  写入系统环境变量
+ * ENVI @name=value  PECMD 内部变量
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnviScope {
+    Process,
+    System,
+    Local,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IfexCondition {
+    pub negate: bool,
+    pub kind: IfexConditionKind,
+    pub text: Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IfexConditionKind {
+    // IFEX path,...  文件或目录存在
+    Exists,
+    // IFEX $expr,...  表达式
+    Expr,
+    // IFEX [cond],...
+    Bracket,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StmtKind {
+    Comment(String),
+    Exec { flags: ExecFlags, command: Word },
+    // LINK 快捷方式,目标[,参数,图标,描述...]
+    Link { path: Word, target: Word, rest: Vec<Word> },
+    File(FileOp),
+    Envi { scope: EnviScope, name: String, value: Option<Word> },
+    Ifex { cond: IfexCondition, then: Vec<Stmt>, otherwise: Vec<Stmt> },
+    Sub { name: String, body: Vec<Stmt> },
+    Call { name: Word, args: Vec<Word> },
+    // 其他命令，参数不再细分
+    Command { name: String, args: Word },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    pub body: Vec<Stmt>,
+}
+
+impl Script {
+    // 深度优先遍历所有语句，包括 IFEX 分支与 _SUB 中的语句
+    pub fn walk<'a, F: FnMut(&'a Stmt)>(&'a self, mut f: F) {
+        fn visit<'a, F: FnMut(&'a Stmt)>(stmts: &'a [Stmt], f: &mut F) {
+            for s in stmts {
+                f(s);
+                match &s.kind {
+                    StmtKind::Ifex { then, otherwise, .. } => {
+                        visit(then, f);
+                        visit(otherwise, f);
+                    }
+                    StmtKind::Sub { body, .. } => visit(body, f),
+                    _ => {}
+                }
+            }
+        }
+        visit(&self.body, &mut f);
+    }
+}
@@ -0,0 +1,308 @@
+use std::path::{Path, PathBuf};
+use anyhow::anyhow;
+use tokio::fs;
+
+/*
+ * 生成 PECMD 命令行
+ * `&str` 参数按 PECMD 语法原样输出，可以含 `%Desktop%` 这样的变量；
+ * `Path` 参数视为字面量，其中的 `%` 会转义为 `%%`
+ */
+
+// 路径转为 PECMD 字面量
+pub fn literal(path: &Path) -> String {
+    path.to_string_lossy().replace('%', "%%")
+}
+
+// 逗号分隔的参数，含逗号或首尾空白时加引号；不能含引号与换行
+fn field(s: &str) -> anyhow::Result<String> {
+    if s.contains(['"', '\r', '\n']) {
+        return Err(anyhow!("invalid pecmd argument {:?}", s));
+    }
+    match s.contains(',') || s.trim() != s {
+        true => Ok(format!("\"{}\"", s)),
+        false => Ok(s.to_string()),
+    }
+}
+
+// 单独成行的参数只需排除换行
+fn plain(s: &str) -> anyhow::Result<String> {
+    match s.contains(['\r', '\n']) {
+        true => Err(anyhow!("invalid pecmd argument {:?}", s)),
+        false => Ok(s.to_string()),
+    }
+}
+
+// LINK 快捷方式,目标[,参数[,图标[#序号][,描述]]]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PecmdLink {
+    pub path: String,
+    pub target: String,
+    pub args: Option<String>,
+    pub icon: Option<(String, u32)>,
+    pub description: Option<String>,
+}
+
+impl PecmdLink {
+    // `path` 不含 `.lnk` 扩展名
+    pub fn new(path: &str, target: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            target: target.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn args(mut self, args: &str) -> Self {
+        self.args = Some(args.to_string());
+        self
+    }
+
+    pub fn icon(mut self, icon: &str, index: u32) -> Self {
+        self.icon = Some((icon.to_string(), index));
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    fn line(&self) -> anyhow::Result<String> {
+        let mut fields = vec![field(&self.path)?, field(&self.target)?];
+        let icon = match &self.icon {
+            Some((icon, 0)) => Some(field(icon)?),
+            Some((icon, index)) => Some(format!("{}#{}", field(icon)?, index)),
+            None => None,
+        };
+        let rest = [
+            self.args.as_deref().map(field).transpose()?,
+            icon,
+            self.description.as_deref().map(field).transpose()?,
+        ];
+        // 去掉末尾未设置的参数，中间的留空
+        let used = rest.iter().rposition(|f| f.is_some()).map(|i| i + 1).unwrap_or(0);
+        fields.extend(rest[..used].iter().map(|f| f.clone().unwrap_or_default()));
+        Ok(format!("LINK {}", fields.join(",")))
+    }
+}
+
+/*
+ * TEXT 文字[#颜色][ L T R B]
+ * 在桌面或加载界面上显示文字，没有文字时清除
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PecmdText {
+    pub text: Option<String>,
+    // `0xRRGGBB`
+    pub color: Option<u32>,
+    pub rect: Option<[i32; 4]>,
+}
+
+impl PecmdText {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: Some(text.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn clear() -> Self {
+        Self::default()
+    }
+
+    pub fn color(mut self, color: u32) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn rect(mut self, left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        self.rect = Some([left, top, right, bottom]);
+        self
+    }
+
+    // 按百分比显示进度，如 `message 40%`
+    pub fn progress(message: &str, percent: u8) -> Self {
+        Self::new(&format!("{} {}%%", message, percent.min(100)))
+    }
+
+    fn line(&self) -> anyhow::Result<String> {
+        let text = match &self.text {
+            Some(text) => text,
+            None => return Ok("TEXT".to_string()),
+        };
+        let mut line = format!("TEXT {}", plain(text)?);
+        if let Some(color) = self.color {
+            line.push_str(&format!("#0x{:06X}", color));
+        }
+        if let Some([l, t, r, b]) = self.rect {
+            line.push_str(&format!(" {} {} {} {}", l, t, r, b));
+        }
+        Ok(line)
+    }
+}
+
+// SHOW 窗口,状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PecmdShowState {
+    Hide = 0,
+    Normal = 1,
+    Minimize = 2,
+    Maximize = 3,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PecmdCommand {
+    // LOAD 脚本
+    Load(PathBuf),
+    Link(PecmdLink),
+    // WALL 图片
+    Wall(PathBuf),
+    Text(PecmdText),
+    Show { window: String, state: PecmdShowState },
+    // EXEC [=][!]命令行
+    Exec { command: String, wait: bool, hide: bool },
+}
+
+impl From<PecmdLink> for PecmdCommand {
+    fn from(link: PecmdLink) -> Self {
+        Self::Link(link)
+    }
+}
+
+impl From<PecmdText> for PecmdCommand {
+    fn from(text: PecmdText) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl PecmdCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Load(_) => "LOAD",
+            Self::Link(_) => "LINK",
+            Self::Wall(_) => "WALL",
+            Self::Text(_) => "TEXT",
+            Self::Show { .. } => "SHOW",
+            Self::Exec { .. } => "EXEC",
+        }
+    }
+
+    // 脚本中的一行
+    pub fn line(&self) -> anyhow::Result<String> {
+        match self {
+            Self::Load(path) => Ok(format!("LOAD {}", plain(&literal(path))?)),
+            Self::Link(link) => link.line(),
+            Self::Wall(path) => Ok(format!("WALL {}", plain(&literal(path))?)),
+            Self::Text(text) => text.line(),
+            Self::Show { window, state } => Ok(format!("SHOW {},{}", field(window)?, *state as i32)),
+            Self::Exec { command, wait, hide } => {
+                let flags = format!("{}{}", if *wait { "=" } else { "" }, if *hide { "!" } else { "" });
+                Ok(format!("EXEC {}{}", flags, plain(command)?))
+            }
+        }
+    }
+
+    /*
+     * 命令行参数，第一个为命令名
+     * LOAD 的路径单独作为一个参数，由 `Command` 负责加引号；
+     * 其他命令的参数原样拼在命令名之后，见 `Pecmd::command_for`
+     */
+    pub fn argv(&self) -> anyhow::Result<Vec<String>> {
+        if let Self::Load(path) = self {
+            return Ok(vec!["LOAD".to_string(), plain(&path.to_string_lossy())?]);
+        }
+        let line = self.line()?;
+        let args = line[self.name().len()..].trim_start();
+        let mut argv = vec![self.name().to_string()];
+        if !args.is_empty() {
+            argv.push(args.to_string());
+        }
+        Ok(argv)
+    }
+}
+
+// 生成的 `.wcs` 脚本
+#[derive(Debug, Clone, Default)]
+pub struct PecmdScript {
+    lines: Vec<String>,
+}
+
+impl PecmdScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn comment(mut self, text: &str) -> Self {
+        for l in text.lines() {
+            self.lines.push(format!("// {}", l));
+        }
+        self
+    }
+
+    pub fn push<C: Into<PecmdCommand>>(mut self, command: C) -> anyhow::Result<Self> {
+        self.lines.push(command.into().line()?);
+        Ok(self)
+    }
+
+    // 以 CRLF 换行，内容相同时输出相同
+    pub fn render(&self) -> String {
+        self.lines.iter().map(|l| format!("{}\r\n", l)).collect()
+    }
+
+    pub async fn write(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, self.render()).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{PecmdCommand, PecmdLink, PecmdScript, PecmdShowState, PecmdText};
+    use crate::interpreter::{Effect, Interpreter};
+    use crate::vfs::VirtualFs;
+
+    #[test]
+    fn it_works() -> anyhow::Result<()> {
+        let link = PecmdLink::new("%Desktop%\\Chrome", "%CurDir%\\Chrome\\chrome.exe")
+            .args("--a,--b")
+            .description("Google Chrome");
+        assert_eq!(
+            PecmdCommand::from(link.clone()).line()?,
+            "LINK %Desktop%\\Chrome,%CurDir%\\Chrome\\chrome.exe,\"--a,--b\",,Google Chrome"
+        );
+        let load = PecmdCommand::Load(PathBuf::from("X:\\Program Files\\100%\\a.wcs"));
+        assert_eq!(load.line()?, "LOAD X:\\Program Files\\100%%\\a.wcs");
+        assert_eq!(load.argv()?, vec!["LOAD", "X:\\Program Files\\100%\\a.wcs"]);
+        assert_eq!(PecmdCommand::from(PecmdText::clear()).argv()?, vec!["TEXT"]);
+        assert_eq!(PecmdCommand::from(PecmdText::progress("Loading", 40).color(0xff00)).line()?, "TEXT Loading 40%%#0x00FF00");
+        let show = PecmdCommand::Show { window: "Edgeless".to_string(), state: PecmdShowState::Hide };
+        assert_eq!(show.argv()?, vec!["SHOW", "Edgeless,0"]);
+        assert!(PecmdCommand::Exec { command: "a\r\nFILE X:\\".to_string(), wait: false, hide: false }.line().is_err());
+        assert!(PecmdCommand::from(PecmdLink::new("a\"", "b")).line().is_err());
+
+        // 生成的脚本能被解析并模拟执行
+        let script = PecmdScript::new()
+            .comment("generated")
+            .push(link)?
+            .push(PecmdCommand::Exec { command: "%CurDir%\\Chrome\\chrome.exe".to_string(), wait: true, hide: true })?
+            .push(PecmdText::new("done"))?;
+        let src = script.render();
+        assert!(src.ends_with("TEXT done\r\n"));
+        let mut fs = VirtualFs::new();
+        fs.write("X:\\Program Files\\Edgeless\\Chrome\\chrome.exe", 1);
+        let mut i = Interpreter::new(fs).with_cur_dir("X:\\Program Files\\Edgeless");
+        i.run_source(&src)?;
+        match &i.effects()[0] {
+            Effect::Link { path, args, .. } => {
+                assert_eq!(path, "X:\\Users\\Default\\Desktop\\Chrome");
+                assert_eq!(args, &vec!["--a,--b".to_string(), "".to_string(), "Google Chrome".to_string()]);
+            }
+            e => panic!("{:?}", e),
+        }
+        assert!(matches!(&i.effects()[1], Effect::Exec { wait: true, hide: true, .. }));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use std::fmt;
+use anyhow::anyhow;
+use log::info;
+use crate::ast::{EnviScope, FileOp, IfexCondition, IfexConditionKind, Script, Span, Stmt, StmtKind, Word};
+use crate::parser::parse;
+use crate::vfs::{self, VirtualFs};
+
+// `CALL` 的最大嵌套层数，防止子程序无限递归
+const MAX_DEPTH: usize = 64;
+
+// 模拟执行产生的副作用，路径均已展开并规范化
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Effect {
+    Link { path: String, target: String, args: Vec<String> },
+    Copy { from: String, to: String },
+    Move { from: String, to: String },
+    Delete { path: String },
+    MakeDir { path: String },
+    SetEnv { scope: EnviScope, name: String, value: Option<String> },
+    // 只记录，不执行
+    Exec { command: String, wait: bool, hide: bool },
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Link { path, target, .. } => write!(f, "link {} -> {}", path, target),
+            Self::Copy { from, to } => write!(f, "copy {} => {}", from, to),
+            Self::Move { from, to } => write!(f, "move {} -> {}", from, to),
+            Self::Delete { path } => write!(f, "delete {}", path),
+            Self::MakeDir { path } => write!(f, "mkdir {}", path),
+            Self::SetEnv { name, value: Some(value), .. } => write!(f, "set {}={}", name, value),
+            Self::SetEnv { name, value: None, .. } => write!(f, "unset {}", name),
+            Self::Exec { command, .. } => write!(f, "exec {}", command),
+        }
+    }
+}
+
+// 执行中发现的问题，不中断执行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)
+    }
+}
+
+/*
+ * PECMD 常用命令的模拟执行器
+ * 支持 LINK、FILE、MDIR、ENVI、EXEC、IFEX、_SUB 与 CALL，在 `VirtualFs` 上执行并记录副作用
+ * EXEC 只记录命令行；其余命令记为 `Diagnostic` 后跳过
+ */
+pub struct Interpreter {
+    fs: VirtualFs,
+    vars: HashMap<String, String>,
+    subs: HashMap<String, Vec<Stmt>>,
+    effects: Vec<Effect>,
+    diagnostics: Vec<Diagnostic>,
+    depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new(VirtualFs::new())
+    }
+}
+
+impl Interpreter {
+    // 预置 PE 中常见的环境变量，系统盘为 `X:`
+    pub fn new(fs: VirtualFs) -> Self {
+        let mut interpreter = Self {
+            fs,
+            vars: HashMap::new(),
+            subs: HashMap::new(),
+            effects: vec![],
+            diagnostics: vec![],
+            depth: 0,
+        };
+        let profile = "X:\\Users\\Default";
+        let programs = format!("{}\\AppData\\Roaming\\Microsoft\\Windows\\Start Menu\\Programs", profile);
+        for (name, value) in [
+            ("SystemDrive", "X:".to_string()),
+            ("SystemRoot", "X:\\Windows".to_string()),
+            ("WinDir", "X:\\Windows".to_string()),
+            ("ProgramFiles", "X:\\Program Files".to_string()),
+            ("ProgramFiles(x86)", "X:\\Program Files (x86)".to_string()),
+            ("ProgramData", "X:\\ProgramData".to_string()),
+            ("Temp", "X:\\Windows\\Temp".to_string()),
+            ("UserProfile", profile.to_string()),
+            ("AppData", format!("{}\\AppData\\Roaming", profile)),
+            ("Desktop", format!("{}\\Desktop", profile)),
+            ("StartMenu", format!("{}\\AppData\\Roaming\\Microsoft\\Windows\\Start Menu", profile)),
+            ("Programs", programs),
+            ("CurDir", "X:\\Windows\\System32".to_string()),
+        ] {
+            interpreter.set_var(name, value);
+        }
+        interpreter
+    }
+
+    pub fn with_var(mut self, name: &str, value: &str) -> Self {
+        self.set_var(name, value.to_string());
+        self
+    }
+
+    // 脚本所在目录，相对路径也以此为准
+    pub fn with_cur_dir(self, dir: &str) -> Self {
+        let dir = vfs::normalize(dir);
+        self.with_var("CurDir", &dir)
+    }
+
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.vars.get(&name.to_lowercase()).map(|v| v.as_str())
+    }
+
+    pub fn fs(&self) -> &VirtualFs {
+        &self.fs
+    }
+
+    pub fn effects(&self) -> &[Effect] {
+        &self.effects
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    fn set_var(&mut self, name: &str, value: String) {
+        self.vars.insert(name.to_lowercase(), value);
+    }
+
+    fn diagnose(&mut self, span: Span, message: String) {
+        info!("{}: {}", span, message);
+        self.diagnostics.push(Diagnostic { message, span });
+    }
+
+    pub fn run_source(&mut self, src: &str) -> anyhow::Result<()> {
+        let script = parse(src)?;
+        self.run(&script)
+    }
+
+    // `_SUB` 可以在定义之前调用，先收集全部子程序
+    pub fn run(&mut self, script: &Script) -> anyhow::Result<()> {
+        let mut subs = vec![];
+        script.walk(|s| {
+            if let StmtKind::Sub { name, body } = &s.kind {
+                subs.push((name.to_lowercase(), body.clone()));
+            }
+        });
+        self.subs.extend(subs);
+        self.block(&script.body)
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> anyhow::Result<()> {
+        for s in stmts {
+            self.stmt(s)?;
+        }
+        Ok(())
+    }
+
+    // 展开变量，未定义的变量保留原样并记录
+    fn expand(&mut self, word: &Word) -> String {
+        let undefined = word.vars()
+            .filter(|v| self.var(v).is_none())
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+        for v in undefined {
+            self.diagnose(word.span, format!("undefined variable `%{}%`", v));
+        }
+        word.expand(|v| self.var(v).map(|s| s.to_string()))
+    }
+
+    fn path(&mut self, word: &Word) -> String {
+        let raw = self.expand(word);
+        let raw = raw.trim().trim_matches('"');
+        match vfs::is_absolute(raw) {
+            true => vfs::normalize(raw),
+            false => {
+                let cur = self.var("CurDir").unwrap_or_default().to_string();
+                vfs::normalize(&vfs::join(&cur, raw))
+            }
+        }
+    }
+
+    fn stmt(&mut self, s: &Stmt) -> anyhow::Result<()> {
+        match &s.kind {
+            StmtKind::Comment(_) | StmtKind::Sub { .. } => {}
+            StmtKind::Exec { flags, command } => {
+                let command = self.expand(command);
+                self.effects.push(Effect::Exec {
+                    command,
+                    wait: flags.wait,
+                    hide: flags.hide,
+                });
+            }
+            StmtKind::Link { path, target, rest } => {
+                let path = self.path(path);
+                let target = self.path(target);
+                let args = rest.iter().map(|w| self.expand(w)).collect();
+                if !self.fs.exists(&target) {
+                    self.diagnose(s.span, format!("shortcut target `{}` does not exist", target));
+                }
+                self.fs.write(&format!("{}.lnk", path), 0);
+                self.effects.push(Effect::Link { path, target, args });
+            }
+            StmtKind::File(op) => self.file(op, s.span),
+            StmtKind::Envi { scope, name, value } => {
+                let value = value.as_ref().map(|v| self.expand(v));
+                match &value {
+                    Some(v) => self.set_var(name, v.clone()),
+                    None => {
+                        self.vars.remove(&name.to_lowercase());
+                    }
+                }
+                self.effects.push(Effect::SetEnv {
+                    scope: *scope,
+                    name: name.clone(),
+                    value,
+                });
+            }
+            StmtKind::Ifex { cond, then, otherwise } => {
+                match self.condition(cond) {
+                    true => self.block(then)?,
+                    false => self.block(otherwise)?,
+                }
+            }
+            StmtKind::Call { name, .. } => {
+                // 参数暂不绑定到子程序
+                let name = self.expand(name);
+                let body = match self.subs.get(&name.to_lowercase()) {
+                    Some(body) => body.clone(),
+                    None => {
+                        self.diagnose(s.span, format!("unknown subroutine `{}`", name));
+                        return Ok(());
+                    }
+                };
+                if self.depth >= MAX_DEPTH {
+                    return Err(anyhow!("{}: `CALL {}` nested too deep", s.span, name));
+                }
+                self.depth += 1;
+                let res = self.block(&body);
+                self.depth -= 1;
+                res?;
+            }
+            StmtKind::Command { name, args } if name == "MDIR" => {
+                let path = self.path(args);
+                self.fs.create_dir_all(&path);
+                self.effects.push(Effect::MakeDir { path });
+            }
+            StmtKind::Command { name, .. } => {
+                self.diagnose(s.span, format!("`{}` is not simulated", name));
+            }
+        }
+        Ok(())
+    }
+
+    // 目标是已有目录或以 `\` 结尾时放到目录中
+    fn target(&self, to: &str, raw: &str, from: &str) -> String {
+        match self.fs.is_dir(to) || raw.trim_end_matches('"').ends_with('\\') {
+            true => vfs::join(to, vfs::file_name(from)),
+            false => to.to_string(),
+        }
+    }
+
+    fn file(&mut self, op: &FileOp, span: Span) {
+        match op {
+            FileOp::Copy { from, to } | FileOp::Move { from, to } => {
+                let copy = matches!(op, FileOp::Copy { .. });
+                let pattern = self.path(from);
+                let raw = to.to_string();
+                let dest = self.path(to);
+                let sources = self.fs.glob(&pattern);
+                if sources.is_empty() {
+                    self.diagnose(span, format!("`{}` does not exist", pattern));
+                }
+                // 多个源时目标视为目录
+                if sources.len() > 1 || vfs::file_name(&pattern).contains('*') {
+                    self.fs.create_dir_all(&dest);
+                }
+                for from in sources {
+                    let to = self.target(&dest, &raw, &from);
+                    match copy {
+                        true => {
+                            self.fs.copy(&from, &to);
+                            self.effects.push(Effect::Copy { from, to });
+                        }
+                        false => {
+                            self.fs.rename(&from, &to);
+                            self.effects.push(Effect::Move { from, to });
+                        }
+                    }
+                }
+            }
+            FileOp::Delete { path } => {
+                let pattern = self.path(path);
+                for path in self.fs.glob(&pattern) {
+                    self.fs.remove(&path);
+                    self.effects.push(Effect::Delete { path });
+                }
+            }
+        }
+    }
+
+    fn condition(&mut self, cond: &IfexCondition) -> bool {
+        let text = self.expand(&cond.text);
+        let result = match cond.kind {
+            IfexConditionKind::Exists => self.exists(&text),
+            IfexConditionKind::Expr => self.compare(text.trim_start_matches('$')),
+            IfexConditionKind::Bracket => {
+                let inner = text.trim().trim_start_matches('[').trim_end_matches(']');
+                // `|` 任一成立，`&` 全部成立
+                inner.split('|').any(|any| any.split('&').all(|all| self.atom(all)))
+            }
+        };
+        result != cond.negate
+    }
+
+    fn atom(&self, text: &str) -> bool {
+        let text = text.trim();
+        match text.strip_prefix('!') {
+            Some(rest) => !self.atom(rest),
+            None if ["=", "<", ">"].iter().any(|op| text.contains(op)) => self.compare(text),
+            None => self.exists(text),
+        }
+    }
+
+    fn exists(&self, text: &str) -> bool {
+        let text = text.trim().trim_matches('"');
+        let path = match vfs::is_absolute(text) {
+            true => text.to_string(),
+            false => vfs::join(self.var("CurDir").unwrap_or_default(), text),
+        };
+        !self.fs.glob(&path).is_empty()
+    }
+
+    // 两侧都是整数时按数值比较，否则按不区分大小写的字符串比较
+    fn compare(&self, text: &str) -> bool {
+        for op in ["<>", "!=", ">=", "<=", "==", "=", ">", "<"] {
+            if let Some(i) = text.find(op) {
+                let l = text[..i].trim().trim_matches('"');
+                let r = text[i + op.len()..].trim().trim_matches('"');
+                let ord = match (l.parse::<i64>(), r.parse::<i64>()) {
+                    (Ok(l), Ok(r)) => l.cmp(&r),
+                    _ => l.to_lowercase().cmp(&r.to_lowercase()),
+                };
+                return match op {
+                    "<>" | "!=" => ord.is_ne(),
+                    ">=" => ord.is_ge(),
+                    "<=" => ord.is_le(),
+                    ">" => ord.is_gt(),
+                    "<" => ord.is_lt(),
+                    _ => ord.is_eq(),
+                };
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Effect, Interpreter};
+    use crate::ast::EnviScope;
+    use crate::vfs::VirtualFs;
+
+    #[test]
+    fn it_works() -> anyhow::Result<()> {
+        let mut fs = VirtualFs::new();
+        fs.write("X:\\Program Files\\Edgeless\\Chrome\\chrome.exe", 100);
+        fs.write("X:\\Program Files\\Edgeless\\Chrome\\a.ini", 1);
+        fs.write("X:\\Program Files\\Edgeless\\Chrome\\b.ini", 1);
+
+        let src = [
+            "// Chrome",
+            "ENVI @Dir=%CurDir%\\Chrome",
+            "MDIR %AppData%\\Chrome",
+            "FILE %Dir%\\*.ini=>%AppData%\\Chrome",
+            "IFEX %Dir%\\chrome.exe,{",
+            "  CALL Links",
+            "}!{",
+            "  EXEC missing",
+            "}",
+            "IFEX [%Dir%\\old.exe|%Version%=2],FILE %Dir%\\a.ini ! FILE %Dir%\\b.ini->%Dir%\\c.ini",
+            "IFEX $%Version%>1,ENVI $Big=1",
+            "EXEC =!%Dir%\\chrome.exe --x",
+            "TEXT hello",
+            "_SUB Links",
+            "  LINK %Desktop%\\Chrome,%Dir%\\chrome.exe,--no-sandbox",
+            "_END",
+        ].join("\r\n");
+
+        let mut i = Interpreter::new(fs)
+            .with_cur_dir("X:\\Program Files\\Edgeless")
+            .with_var("Version", "2");
+        i.run_source(&src)?;
+
+        let dir = "X:\\Program Files\\Edgeless\\Chrome";
+        let effects = i.effects().iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(effects, vec![
+            format!("set Dir={}", dir),
+            "mkdir X:\\Users\\Default\\AppData\\Roaming\\Chrome".to_string(),
+            format!("copy {}\\a.ini => X:\\Users\\Default\\AppData\\Roaming\\Chrome\\a.ini", dir),
+            format!("copy {}\\b.ini => X:\\Users\\Default\\AppData\\Roaming\\Chrome\\b.ini", dir),
+            format!("link X:\\Users\\Default\\Desktop\\Chrome -> {}\\chrome.exe", dir),
+            format!("delete {}\\a.ini", dir),
+            "set Big=1".to_string(),
+            format!("exec {}\\chrome.exe --x", dir),
+        ]);
+        assert!(matches!(&i.effects()[4], Effect::Link { args, .. } if args == &vec!["--no-sandbox".to_string()]));
+        assert!(matches!(&i.effects()[6], Effect::SetEnv { scope: EnviScope::System, .. }));
+        assert!(matches!(&i.effects()[7], Effect::Exec { wait: true, hide: true, .. }));
+
+        assert!(i.fs().is_file("X:\\Users\\Default\\Desktop\\Chrome.lnk"));
+        assert!(i.fs().is_file("X:\\Users\\Default\\AppData\\Roaming\\Chrome\\b.ini"));
+        assert!(!i.fs().exists(&format!("{}\\a.ini", dir)));
+        assert_eq!(i.var("big"), Some("1"));
+        assert_eq!(i.diagnostics().len(), 1);
+        assert_eq!(i.diagnostics()[0].span.line, 13);
+
+        let mut i = Interpreter::default();
+        i.run_source("FILE a=>b\nCALL Nope\nEXEC %Nope%")?;
+        assert_eq!(i.diagnostics().len(), 3);
+        assert!(Interpreter::default().run_source("_SUB a\nCALL a\n_END\nCALL a").is_err());
+
+        Ok(())
+    }
+}
@@ -1,4 +1,11 @@
-use std::path::PathBuf;
+pub mod ast;
+pub mod command;
+pub mod parser;
+pub mod vfs;
+pub mod interpreter;
+
+use std::path::{Path, PathBuf};
+use command::PecmdCommand;
 use anyhow::anyhow;
 use tokio::process::{Child, Command};
 use std::process::Stdio;
@@ -24,15 +31,50 @@ impl Pecmd {
 }
 
 impl Pecmd {
-    pub async fn run(&self, options: &Vec<&str>) -> anyhow::Result<Child> {
-        info!("run command, args = {:#?}", options);
-        Ok(Command::new(&self.exepath)
+    // 未启动的命令，调用方可以再设置工作目录与环境变量
+    pub fn command(&self, options: &[&str]) -> anyhow::Result<Command> {
+        let mut command = Command::new(&self.exepath);
+        command
             .args(options)
             .current_dir(env::current_dir()?)
             .stdin(Stdio::piped())
             .stderr(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()?)
+            .stdout(Stdio::piped());
+        Ok(command)
+    }
+
+    /*
+     * 执行单条命令
+     * Windows 上命令名之后的内容原样传给 PECMD，避免 `Command` 给含空格的参数整体加引号
+     */
+    pub fn command_for(&self, command: &PecmdCommand) -> anyhow::Result<Command> {
+        let argv = command.argv()?;
+        let (name, rest) = argv.split_first().ok_or(anyhow!("empty pecmd command"))?;
+        let mut cmd = self.command(&[name])?;
+        for arg in rest {
+            #[cfg(windows)]
+            match command {
+                PecmdCommand::Load(_) => cmd.arg(arg),
+                _ => cmd.raw_arg(arg),
+            };
+            #[cfg(not(windows))]
+            cmd.arg(arg);
+        }
+        Ok(cmd)
+    }
+
+    pub async fn exec(&self, command: &PecmdCommand) -> anyhow::Result<Child> {
+        info!("exec {:?}", command);
+        Ok(self.command_for(command)?.spawn()?)
+    }
+
+    pub async fn load(&self, script: &Path) -> anyhow::Result<Child> {
+        self.exec(&PecmdCommand::Load(script.to_path_buf())).await
+    }
+
+    pub async fn run(&self, options: &Vec<&str>) -> anyhow::Result<Child> {
+        info!("run command, args = {:#?}", options);
+        Ok(self.command(options)?.spawn()?)
     }
 }
 
@@ -0,0 +1,533 @@
+use std::fmt;
+use crate::ast::{
+    EnviScope,
+    ExecFlags,
+    FileOp,
+    IfexCondition,
+    IfexConditionKind,
+    Script,
+    Span,
+    Stmt,
+    StmtKind,
+    Word,
+    WordPart,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// 语句块的结束方式
+enum BlockEnd {
+    Eof,
+    // `}` 之后的内容与位置
+    Brace(String, usize),
+    SubEnd,
+}
+
+struct Line {
+    start: usize,
+    text: String,
+}
+
+pub struct Parser<'a> {
+    src: &'a str,
+    lines: Vec<Line>,
+    line_starts: Vec<usize>,
+    pos: usize,
+}
+
+fn is_space(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(src: &'a str) -> Self {
+        let mut lines = vec![];
+        let mut line_starts = vec![];
+        let mut start = 0;
+        for raw in src.split('\n') {
+            line_starts.push(start);
+            lines.push(Line {
+                start,
+                text: raw.strip_suffix('\r').unwrap_or(raw).to_string(),
+            });
+            start += raw.len() + 1;
+        }
+        Self {
+            src,
+            lines,
+            line_starts,
+            pos: 0,
+        }
+    }
+
+    pub fn parse(mut self) -> Result<Script, ParseError> {
+        let body = match self.block()? {
+            (body, BlockEnd::Eof) => body,
+            (_, BlockEnd::Brace(_, at)) => return Err(self.error("unexpected `}`", at, at + 1)),
+            (_, BlockEnd::SubEnd) => {
+                let at = self.lines[self.pos - 1].start;
+                return Err(self.error("`_END` without `_SUB`", at, at + 4));
+            }
+        };
+        Ok(Script { body })
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        let line = match self.line_starts.binary_search(&start) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        let column = self.src[self.line_starts[line]..start.min(self.src.len())].chars().count() + 1;
+        Span {
+            start,
+            end,
+            line: line + 1,
+            column,
+        }
+    }
+
+    fn error(&self, message: &str, start: usize, end: usize) -> ParseError {
+        ParseError {
+            message: message.to_string(),
+            span: self.span(start, end),
+        }
+    }
+
+    // 读到文件结尾、`}` 或 `_END` 为止
+    fn block(&mut self) -> Result<(Vec<Stmt>, BlockEnd), ParseError> {
+        let mut body = vec![];
+        while self.pos < self.lines.len() {
+            let line = &self.lines[self.pos];
+            let trimmed = line.text.trim_start_matches(is_space);
+            let start = line.start + line.text.len() - trimmed.len();
+            let text = trimmed.trim_end().to_string();
+            self.pos += 1;
+
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(rest) = text.strip_prefix('}') {
+                return Ok((body, BlockEnd::Brace(rest.trim().to_string(), start)));
+            }
+            if text.eq_ignore_ascii_case("_END") {
+                return Ok((body, BlockEnd::SubEnd));
+            }
+            let mut stmts = self.statement(&text, start)?;
+            body.append(&mut stmts);
+        }
+        Ok((body, BlockEnd::Eof))
+    }
+
+    // 一行可能因 `IFEX ...,{` 展开为多行
+    fn statement(&mut self, text: &str, start: usize) -> Result<Vec<Stmt>, ParseError> {
+        let end = start + text.len();
+        if let Some(comment) = text.strip_prefix("//") {
+            return Ok(vec![Stmt {
+                kind: StmtKind::Comment(comment.trim().to_string()),
+                span: self.span(start, end),
+            }]);
+        }
+
+        let name_len = text.find(is_space).unwrap_or(text.len());
+        let name = &text[..name_len];
+        let args = text[name_len..].trim_start_matches(is_space);
+        let args_start = end - args.len();
+
+        let kind = match name.to_uppercase().as_str() {
+            "EXEC" => self.exec(args, args_start),
+            "LINK" => self.link(args, args_start, start, end)?,
+            "FILE" => self.file(args, args_start),
+            "ENVI" => self.envi(args, args_start, start, end)?,
+            "CALL" => self.call(args, args_start, start, end)?,
+            "IFEX" => return self.ifex(args, args_start, start),
+            "_SUB" => {
+                let sub = args.trim().to_string();
+                if sub.is_empty() {
+                    return Err(self.error("`_SUB` needs a name", start, end));
+                }
+                match self.block()? {
+                    (body, BlockEnd::SubEnd) => StmtKind::Sub { name: sub, body },
+                    _ => return Err(self.error("`_SUB` without `_END`", start, end)),
+                }
+            }
+            _ => StmtKind::Command {
+                name: name.to_uppercase(),
+                args: self.word(args, args_start),
+            },
+        };
+        Ok(vec![Stmt {
+            kind,
+            span: self.span(start, end),
+        }])
+    }
+
+    fn word(&self, text: &str, start: usize) -> Word {
+        let mut parts = vec![];
+        let mut buf = String::new();
+        let mut rest = text;
+        while let Some(i) = rest.find('%') {
+            buf.push_str(&rest[..i]);
+            let after = &rest[i + 1..];
+            if let Some(after) = after.strip_prefix('%') {
+                buf.push('%');
+                rest = after;
+                continue;
+            }
+            let name = after.find('%').map(|j| &after[..j]);
+            match name {
+                Some(name) if !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || c == ',') => {
+                    if !buf.is_empty() {
+                        parts.push(WordPart::Text(std::mem::take(&mut buf)));
+                    }
+                    parts.push(WordPart::Var(name.to_string()));
+                    rest = &after[name.len() + 1..];
+                }
+                _ => {
+                    buf.push('%');
+                    rest = after;
+                }
+            }
+        }
+        buf.push_str(rest);
+        if !buf.is_empty() {
+            parts.push(WordPart::Text(buf));
+        }
+        Word {
+            parts,
+            span: self.span(start, start + text.len()),
+        }
+    }
+
+    // 按顶层逗号切分，`[]` 与引号中的逗号不切分；各项去掉首尾空白与包围的引号
+    fn split_args(&self, text: &str, start: usize, limit: usize) -> Vec<Word> {
+        let mut words = vec![];
+        let mut depth = 0;
+        let mut quoted = false;
+        let mut from = 0;
+        for (i, c) in text.char_indices() {
+            match c {
+                '"' => quoted = !quoted,
+                '[' if !quoted => depth += 1,
+                ']' if !quoted && depth > 0 => depth -= 1,
+                ',' if !quoted && depth == 0 && words.len() + 1 < limit => {
+                    words.push(self.arg_word(&text[from..i], start + from));
+                    from = i + 1;
+                }
+                _ => {}
+            }
+        }
+        words.push(self.arg_word(&text[from..], start + from));
+        words
+    }
+
+    // `"--a,--b"` 的内容为 `--a,--b`，位置仍包括引号
+    fn arg_word(&self, text: &str, start: usize) -> Word {
+        let trimmed = text.trim();
+        match trimmed.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+            Some(inner) => {
+                let at = start + text.len() - text.trim_start().len();
+                Word {
+                    span: self.span(at, at + trimmed.len()),
+                    ..self.word(inner, at + 1)
+                }
+            }
+            None => self.trimmed_word(text, start),
+        }
+    }
+
+    fn trimmed_word(&self, text: &str, start: usize) -> Word {
+        let trimmed = text.trim_start();
+        let start = start + text.len() - trimmed.len();
+        self.word(trimmed.trim_end(), start)
+    }
+
+    fn exec(&self, args: &str, start: usize) -> StmtKind {
+        let command = args.trim_start_matches(|c| "=!@*".contains(c));
+        let flags = &args[..args.len() - command.len()];
+        let trimmed = command.trim_start_matches(is_space);
+        StmtKind::Exec {
+            flags: ExecFlags {
+                wait: flags.contains('='),
+                hide: flags.contains('!'),
+                flags: flags.to_string(),
+            },
+            command: self.word(trimmed, start + args.len() - trimmed.len()),
+        }
+    }
+
+    fn link(&self, args: &str, start: usize, line_start: usize, end: usize) -> Result<StmtKind, ParseError> {
+        let mut words = self.split_args(args, start, usize::MAX).into_iter();
+        match (words.next(), words.next()) {
+            (Some(path), Some(target)) if !path.is_empty() => Ok(StmtKind::Link {
+                path,
+                target,
+                rest: words.collect(),
+            }),
+            _ => Err(self.error("`LINK` needs a shortcut path and a target", line_start, end)),
+        }
+    }
+
+    fn file(&self, args: &str, start: usize) -> StmtKind {
+        for (sep, copy) in [("=>", true), ("->", false)] {
+            if let Some(i) = args.find(sep) {
+                let from = self.trimmed_word(&args[..i], start);
+                let to = self.trimmed_word(&args[i + 2..], start + i + 2);
+                return StmtKind::File(match copy {
+                    true => FileOp::Copy { from, to },
+                    false => FileOp::Move { from, to },
+                });
+            }
+        }
+        StmtKind::File(FileOp::Delete {
+            path: self.trimmed_word(args, start),
+        })
+    }
+
+    fn envi(&self, args: &str, start: usize, line_start: usize, end: usize) -> Result<StmtKind, ParseError> {
+        let (scope, body, start) = match args.chars().next() {
+            Some('$') => (EnviScope::System, &args[1..], start + 1),
+            Some('@') => (EnviScope::Local, &args[1..], start + 1),
+            _ => (EnviScope::Process, args, start),
+        };
+        let (name, value) = match body.find('=') {
+            Some(i) => (&body[..i], Some(self.word(&body[i + 1..], start + i + 1))),
+            None => (body, None),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(self.error("`ENVI` needs a variable name", line_start, end));
+        }
+        Ok(StmtKind::Envi {
+            scope,
+            name: name.to_string(),
+            value,
+        })
+    }
+
+    fn call(&self, args: &str, start: usize, line_start: usize, end: usize) -> Result<StmtKind, ParseError> {
+        let mut words = vec![];
+        let mut from = None;
+        let mut quoted = false;
+        for (i, c) in args.char_indices().chain([(args.len(), ' ')]) {
+            match (c, from) {
+                ('"', _) => {
+                    quoted = !quoted;
+                    from.get_or_insert(i);
+                }
+                (c, Some(f)) if is_space(c) && !quoted => {
+                    words.push(self.word(&args[f..i], start + f));
+                    from = None;
+                }
+                (c, None) if !is_space(c) => from = Some(i),
+                _ => {}
+            }
+        }
+        let mut words = words.into_iter();
+        let name = words.next()
+            .ok_or_else(|| self.error("`CALL` needs a name", line_start, end))?;
+        Ok(StmtKind::Call {
+            name,
+            args: words.collect(),
+        })
+    }
+
+    fn condition(&self, text: &str, start: usize) -> IfexCondition {
+        let trimmed = text.trim();
+        let start = start + text.find(trimmed).unwrap_or(0);
+        let (negate, body, start) = match trimmed.strip_prefix('!') {
+            Some(rest) => (true, rest.trim_start(), start + trimmed.len() - rest.trim_start().len()),
+            None => (false, trimmed, start),
+        };
+        let kind = match body.chars().next() {
+            Some('$') => IfexConditionKind::Expr,
+            Some('[') => IfexConditionKind::Bracket,
+            _ => IfexConditionKind::Exists,
+        };
+        IfexCondition {
+            negate,
+            kind,
+            text: self.word(body, start),
+        }
+    }
+
+    /*
+     * IFEX 条件, 命令 ! 否则执行的命令
+     * IFEX 条件,{
+     *   ...
+     * }!{
+     *   ...
+     * }
+     */
+    fn ifex(&mut self, args: &str, start: usize, line_start: usize) -> Result<Vec<Stmt>, ParseError> {
+        let line_end = start + args.len();
+        let mut split = self.split_args(args, start, 2).into_iter();
+        let cond_word = split.next().unwrap();
+        let body = match split.next() {
+            Some(body) => body,
+            None => return Err(self.error("`IFEX` needs a condition and a command", line_start, line_end)),
+        };
+        let cond = self.condition(&args[..cond_word.span.end - start], start);
+        let body_text = &self.src[body.span.start..body.span.end].to_string();
+        let body_start = body.span.start;
+
+        let (then, otherwise) = if let Some(rest) = body_text.strip_prefix('{') {
+            if !rest.trim().is_empty() {
+                return Err(self.error("unexpected text after `{`", body_start + 1, line_end));
+            }
+            let then = self.braced(line_start, line_end)?;
+            let (then, tail, tail_at) = then;
+            let otherwise = match tail.strip_prefix('!') {
+                None if tail.is_empty() => vec![],
+                Some(rest) if rest.trim() == "{" => self.braced(line_start, line_end)?.0,
+                Some(rest) => {
+                    let rest = rest.trim();
+                    self.statement(rest, tail_at + tail.len() - rest.len())?
+                }
+                None => return Err(self.error("unexpected text after `}`", tail_at, tail_at + tail.len())),
+            };
+            (then, otherwise)
+        } else {
+            match body_text.find(" ! ") {
+                Some(i) => {
+                    let then = body_text[..i].trim_end();
+                    let other = body_text[i + 3..].trim_start();
+                    let other_at = body_start + body_text.len() - other.len();
+                    (self.statement(then, body_start)?, self.statement(other, other_at)?)
+                }
+                None => (self.statement(body_text, body_start)?, vec![]),
+            }
+        };
+
+        let end = self.lines.get(self.pos.saturating_sub(1))
+            .map(|l| l.start + l.text.len())
+            .unwrap_or(line_end)
+            .max(line_end);
+        Ok(vec![Stmt {
+            kind: StmtKind::Ifex { cond, then, otherwise },
+            span: self.span(line_start, end),
+        }])
+    }
+
+    // 解析 `{` 之后的语句直到 `}`，返回 `}` 之后的内容
+    fn braced(&mut self, line_start: usize, line_end: usize) -> Result<(Vec<Stmt>, String, usize), ParseError> {
+        match self.block()? {
+            (body, BlockEnd::Brace(tail, at)) => {
+                let tail_at = at + 1 + self.src[at + 1..].len() - self.src[at + 1..].trim_start().len();
+                Ok((body, tail, tail_at))
+            }
+            _ => Err(self.error("unclosed `{`", line_start, line_end)),
+        }
+    }
+}
+
+pub fn parse(src: &str) -> Result<Script, ParseError> {
+    Parser::new(src).parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::ast::{EnviScope, FileOp, IfexConditionKind, StmtKind, WordPart};
+
+    #[test]
+    fn it_works() {
+        let src = [
+            "// Chrome 插件",
+            "ENVI @Dir=%ProgramFiles%\\Edgeless\\Chrome",
+            "EXEC =!%Dir%\\setup.exe /S",
+            "LINK %Desktop%\\Chrome,%Dir%\\chrome.exe,--no-sandbox,%Dir%\\chrome.exe#0",
+            "FILE %Dir%\\a.ini=>%AppData%\\a.ini",
+            "IFEX [%Dir%\\old],{",
+            "  FILE %Dir%\\old",
+            "}!{",
+            "  CALL Setup 1 \"two words\"",
+            "}",
+            "IFEX $%x%>1, TEXT big ! TEXT small",
+            "_SUB Setup",
+            "  MESS 100%% done",
+            "_END",
+        ].join("\r\n");
+        let src = src.as_str();
+        let script = parse(src).unwrap();
+        let kinds = script.body.iter().map(|s| &s.kind).collect::<Vec<_>>();
+        assert_eq!(kinds.len(), 8);
+        assert!(matches!(kinds[0], StmtKind::Comment(c) if c == "Chrome 插件"));
+
+        match kinds[1] {
+            StmtKind::Envi { scope, name, value } => {
+                assert_eq!(*scope, EnviScope::Local);
+                assert_eq!(name, "Dir");
+                let value = value.as_ref().unwrap();
+                assert_eq!(value.parts[0], WordPart::Var("ProgramFiles".to_string()));
+                assert_eq!(value.to_string(), "%ProgramFiles%\\Edgeless\\Chrome");
+            }
+            k => panic!("{:?}", k),
+        }
+        match kinds[2] {
+            StmtKind::Exec { flags, command } => {
+                assert!(flags.wait && flags.hide);
+                assert_eq!(command.vars().collect::<Vec<_>>(), vec!["Dir"]);
+                assert_eq!(&src[command.span.start..command.span.end], "%Dir%\\setup.exe /S");
+            }
+            k => panic!("{:?}", k),
+        }
+        match kinds[3] {
+            StmtKind::Link { path, target, rest } => {
+                assert_eq!(path.to_string(), "%Desktop%\\Chrome");
+                assert_eq!(target.to_string(), "%Dir%\\chrome.exe");
+                assert_eq!(rest.len(), 2);
+            }
+            k => panic!("{:?}", k),
+        }
+        assert!(matches!(kinds[4], StmtKind::File(FileOp::Copy { .. })));
+        match kinds[5] {
+            StmtKind::Ifex { cond, then, otherwise } => {
+                assert_eq!(cond.kind, IfexConditionKind::Bracket);
+                assert!(matches!(then[0].kind, StmtKind::File(FileOp::Delete { .. })));
+                assert!(matches!(&otherwise[0].kind, StmtKind::Call { name, args } if name.to_string() == "Setup" && args.len() == 2));
+                assert_eq!(script.body[5].span.line, 6);
+                assert_eq!(then[0].span.line, 7);
+                assert_eq!(then[0].span.column, 3);
+            }
+            k => panic!("{:?}", k),
+        }
+        match kinds[6] {
+            StmtKind::Ifex { cond, then, otherwise } => {
+                assert_eq!(cond.kind, IfexConditionKind::Expr);
+                assert!(matches!(&then[0].kind, StmtKind::Command { name, .. } if name == "TEXT"));
+                assert_eq!(otherwise.len(), 1);
+            }
+            k => panic!("{:?}", k),
+        }
+        match kinds[7] {
+            StmtKind::Sub { name, body } => {
+                assert_eq!(name, "Setup");
+                assert!(matches!(&body[0].kind, StmtKind::Command { args, .. } if args.parts == vec![WordPart::Text("100% done".to_string())]));
+            }
+            k => panic!("{:?}", k),
+        }
+
+        let mut count = 0;
+        script.walk(|_| count += 1);
+        assert_eq!(count, 13);
+
+        let e = parse("EXEC a\nIFEX a,{\nEXEC b\n").unwrap_err();
+        assert_eq!((e.span.line, e.span.column), (2, 1));
+        assert!(parse("}").is_err());
+        assert!(parse("_END").is_err());
+        assert!(parse("_SUB a\nEXEC b").is_err());
+        assert!(parse("LINK a").is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VfsNodeKind {
+    Dir,
+    File { size: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VfsNode {
+    // 保留创建时的大小写
+    pub path: String,
+    pub kind: VfsNodeKind,
+}
+
+/*
+ * 模拟 Windows 文件系统，只记录文件与目录是否存在，不保存内容
+ * 路径不区分大小写，`/` 视同 `\`，如 `X:\Program Files\Edgeless`
+ */
+#[derive(Debug, Clone, Default)]
+pub struct VirtualFs {
+    nodes: BTreeMap<String, VfsNode>,
+}
+
+// 规范化为 `X:\a\b`，处理 `.` 与 `..`，去掉末尾的 `\`
+pub fn normalize(path: &str) -> String {
+    let path = path.trim().trim_matches('"').replace('/', "\\");
+    let (prefix, rest) = match path.find(':') {
+        Some(1) => (path[..2].to_uppercase(), &path[2..]),
+        _ => (String::new(), path.as_str()),
+    };
+    let mut parts: Vec<&str> = vec![];
+    for part in rest.split('\\') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            p => parts.push(p),
+        }
+    }
+    match prefix.is_empty() {
+        true => parts.join("\\"),
+        false if parts.is_empty() => format!("{}\\", prefix),
+        false => format!("{}\\{}", prefix, parts.join("\\")),
+    }
+}
+
+pub fn is_absolute(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+pub fn parent(path: &str) -> Option<&str> {
+    let i = path.trim_end_matches('\\').rfind('\\')?;
+    match path[..i].ends_with(':') {
+        true => Some(&path[..i + 1]),
+        false => Some(&path[..i]),
+    }
+}
+
+pub fn file_name(path: &str) -> &str {
+    let path = path.trim_end_matches('\\');
+    path.rsplit('\\').next().unwrap_or(path)
+}
+
+pub fn join(dir: &str, name: &str) -> String {
+    match dir.ends_with('\\') {
+        true => format!("{}{}", dir, name),
+        false => format!("{}\\{}", dir, name),
+    }
+}
+
+// 文件名通配，支持 `*` 与 `?`，不区分大小写；`*.*` 也匹配没有扩展名的文件
+pub fn matches(pattern: &str, name: &str) -> bool {
+    fn inner(p: &[char], n: &[char]) -> bool {
+        match (p.first(), n.first()) {
+            (None, None) => true,
+            (Some('*'), _) => inner(&p[1..], n) || (!n.is_empty() && inner(p, &n[1..])),
+            (Some('?'), Some(_)) => inner(&p[1..], &n[1..]),
+            (Some(a), Some(b)) => a == b && inner(&p[1..], &n[1..]),
+            _ => false,
+        }
+    }
+    if pattern == "*.*" {
+        return true;
+    }
+    let p = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let n = name.to_lowercase().chars().collect::<Vec<_>>();
+    inner(&p, &n)
+}
+
+fn key(path: &str) -> String {
+    normalize(path).to_lowercase()
+}
+
+impl VirtualFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, path: &str) -> Option<&VfsNode> {
+        self.nodes.get(&key(path))
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.get(path).is_some()
+    }
+
+    pub fn is_dir(&self, path: &str) -> bool {
+        matches!(self.get(path), Some(VfsNode { kind: VfsNodeKind::Dir, .. }))
+    }
+
+    pub fn is_file(&self, path: &str) -> bool {
+        matches!(self.get(path), Some(VfsNode { kind: VfsNodeKind::File { .. }, .. }))
+    }
+
+    // 同时创建上级目录
+    pub fn create_dir_all(&mut self, path: &str) {
+        let path = normalize(path);
+        if let Some(p) = parent(&path) {
+            if !self.is_dir(p) {
+                self.create_dir_all(p);
+            }
+        }
+        self.nodes.entry(path.to_lowercase()).or_insert(VfsNode {
+            path,
+            kind: VfsNodeKind::Dir,
+        });
+    }
+
+    pub fn write(&mut self, path: &str, size: u64) {
+        let path = normalize(path);
+        if let Some(p) = parent(&path) {
+            self.create_dir_all(p);
+        }
+        self.nodes.insert(path.to_lowercase(), VfsNode {
+            path,
+            kind: VfsNodeKind::File { size },
+        });
+    }
+
+    // 直接子项
+    pub fn children(&self, dir: &str) -> Vec<&VfsNode> {
+        let dir = key(dir);
+        self.nodes.values()
+            .filter(|n| parent(&n.path).map(|p| p.to_lowercase() == dir).unwrap_or(false))
+            .collect()
+    }
+
+    // `path` 自身及其下的所有项
+    pub fn tree(&self, path: &str) -> Vec<&VfsNode> {
+        let k = key(path);
+        let prefix = join(&k, "");
+        self.nodes.iter()
+            .filter(|(n, _)| **n == k || n.starts_with(&prefix))
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    // 展开文件名中的通配符，没有通配符时原样返回已存在的路径
+    pub fn glob(&self, path: &str) -> Vec<String> {
+        let path = normalize(path);
+        let name = file_name(&path);
+        if !name.contains(['*', '?']) {
+            return match self.exists(&path) {
+                true => vec![path],
+                false => vec![],
+            };
+        }
+        let dir = parent(&path).unwrap_or_default();
+        self.children(dir)
+            .into_iter()
+            .filter(|n| matches(name, file_name(&n.path)))
+            .map(|n| n.path.clone())
+            .collect()
+    }
+
+    pub fn remove(&mut self, path: &str) -> bool {
+        let keys = self.tree(path).iter().map(|n| key(&n.path)).collect::<Vec<_>>();
+        for k in &keys {
+            self.nodes.remove(k);
+        }
+        !keys.is_empty()
+    }
+
+    // 复制文件或整个目录到 `to`
+    pub fn copy(&mut self, from: &str, to: &str) -> bool {
+        let from = normalize(from);
+        let to = normalize(to);
+        let nodes = self.tree(&from).into_iter().cloned().collect::<Vec<_>>();
+        for n in &nodes {
+            let target = format!("{}{}", to, &n.path[from.len()..]);
+            match n.kind {
+                VfsNodeKind::Dir => self.create_dir_all(&target),
+                VfsNodeKind::File { size } => self.write(&target, size),
+            }
+        }
+        !nodes.is_empty()
+    }
+
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        self.copy(from, to) && self.remove(from)
+    }
+
+    // 把宿主机上的目录 `host` 载入到 `at`
+    pub fn load_dir(&mut self, host: &Path, at: &str) -> io::Result<()> {
+        self.create_dir_all(at);
+        let mut stack = vec![(host.to_path_buf(), normalize(at))];
+        while let Some((dir, to)) = stack.pop() {
+            for f in fs::read_dir(&dir)? {
+                let f = f?;
+                let target = join(&to, &f.file_name().to_string_lossy());
+                let meta = f.metadata()?;
+                if meta.is_dir() {
+                    self.create_dir_all(&target);
+                    stack.push((f.path(), target));
+                } else {
+                    self.write(&target, meta.len());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.nodes.values().map(|n| n.path.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, normalize, parent, VirtualFs};
+
+    #[test]
+    fn it_works() {
+        assert_eq!(normalize("x:/Program Files\\.\\Edgeless\\a\\..\\b\\"), "X:\\Program Files\\Edgeless\\b");
+        assert_eq!(normalize("\"X:\\\""), "X:\\");
+        assert_eq!(parent("X:\\a\\b"), Some("X:\\a"));
+        assert_eq!(parent("X:\\a"), Some("X:\\"));
+        assert_eq!(parent("X:\\"), None);
+        assert!(matches("*.exe", "Chrome.EXE"));
+        assert!(matches("c?rome*", "chrome.exe"));
+        assert!(!matches("*.exe", "chrome.ini"));
+
+        let mut fs = VirtualFs::new();
+        fs.write("X:\\Edgeless\\Chrome\\chrome.exe", 10);
+        fs.write("X:\\Edgeless\\Chrome\\a.ini", 1);
+        assert!(fs.is_dir("x:\\edgeless"));
+        assert!(fs.is_file("X:/EDGELESS/chrome/CHROME.exe"));
+        assert_eq!(fs.glob("X:\\Edgeless\\Chrome\\*.exe"), vec!["X:\\Edgeless\\Chrome\\chrome.exe"]);
+
+        assert!(fs.copy("X:\\Edgeless\\Chrome", "X:\\Apps\\Chrome"));
+        assert!(fs.is_file("X:\\Apps\\Chrome\\a.ini"));
+        assert!(fs.rename("X:\\Apps", "X:\\Moved"));
+        assert!(!fs.exists("X:\\Apps"));
+        assert!(fs.is_file("X:\\Moved\\Chrome\\chrome.exe"));
+        assert!(fs.remove("X:\\Edgeless"));
+        assert!(!fs.exists("X:\\Edgeless\\Chrome\\chrome.exe"));
+        assert!(!fs.remove("X:\\Edgeless"));
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use edgeless_core::found::ProfileEntry;
+use edgeless_core::options::define::PATH_PLUGIN_INDEX;
+use serde::{Deserialize, Serialize};
+use super::manifest::PluginManifest;
+use super::scan::{PluginScanOptions, PluginScanReport};
+use super::{PluginEntry, PATH_PLUGIN_RESOURCES};
+
+use log::{info, warn};
+use tokio::fs;
+
+const PLUGIN_INDEX_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginIndexRecord {
+  pub size: u64,
+  pub mtime: u64,
+  // 清单旁路文件的修改时间，没有清单时为 None
+  pub manifest_mtime: Option<u64>,
+  pub manifest: Option<PluginManifest>,
+  pub sha256: Option<String>,
+}
+
+/*
+ * 插件索引缓存，以相对于 Resource 的路径为键
+ * 盘符在每次启动时可能不同，因此不使用绝对路径
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginIndex {
+  pub version: u32,
+  pub records: BTreeMap<String, PluginIndexRecord>,
+}
+
+impl Default for PluginIndex {
+  fn default() -> Self {
+    Self {
+      version: PLUGIN_INDEX_VERSION,
+      records: BTreeMap::new(),
+    }
+  }
+}
+
+pub fn mtime_of(meta: &Metadata) -> u64 {
+  meta.modified().ok()
+    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map(|d| d.as_nanos() as u64)
+    .unwrap_or(0)
+}
+
+impl PluginIndex {
+  pub fn profile_path(entry: &ProfileEntry) -> PathBuf {
+    entry.path.join(PATH_PLUGIN_INDEX.as_path())
+  }
+
+  pub fn temp_path() -> PathBuf {
+    env::temp_dir().join("Edgeless").join(PATH_PLUGIN_INDEX.as_path())
+  }
+
+  pub fn key(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+      .unwrap_or(path)
+      .to_string_lossy()
+      .replace('\\', "/")
+  }
+
+  // 缓存不存在或已损坏时返回空索引
+  pub async fn load(path: &Path) -> Self {
+    let text = match fs::read_to_string(path).await {
+      Ok(t) => t,
+      Err(_) => {
+        info!("plugin index {:?} not found, use empty", path);
+        return Self::default();
+      }
+    };
+
+    match serde_json::from_str::<Self>(&text) {
+      Ok(index) if index.version == PLUGIN_INDEX_VERSION => {
+        info!("loaded plugin index {:?}, {} records", path, index.records.len());
+        index
+      }
+      Ok(_) => {
+        warn!("plugin index {:?} is outdated, discard", path);
+        Self::default()
+      }
+      Err(e) => {
+        warn!("plugin index {:?} is broken, discard, {}", path, e);
+        Self::default()
+      }
+    }
+  }
+
+  pub async fn save(&self, path: &Path) -> anyhow::Result<()> {
+    info!("save plugin index to {:?}", path);
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent).await?;
+    }
+    fs::write(path, serde_json::to_string(self)?).await?;
+    Ok(())
+  }
+
+  // 只有大小与修改时间均一致时才命中
+  pub fn get(&self, key: &str, meta: &Metadata) -> Option<&PluginIndexRecord> {
+    self.records.get(key)
+      .filter(|r| r.size == meta.len() && r.mtime == mtime_of(meta))
+  }
+
+  // `manifest_mtime` 取自目录列表，命中时不再访问插件与清单文件
+  pub fn lookup(&self, key: &str, meta: &Metadata, manifest_mtime: Option<u64>) -> Option<&PluginIndexRecord> {
+    self.get(key, meta)
+      .filter(|r| r.manifest_mtime == manifest_mtime)
+  }
+
+  pub fn sha256(&self, root: &Path, entry: &PluginEntry) -> Option<&str> {
+    self.get(&Self::key(root, &entry.path), &entry.filemeta)
+      .and_then(|r| r.sha256.as_deref())
+  }
+
+  pub fn set_sha256(&mut self, root: &Path, entry: &PluginEntry, hash: String) {
+    if let Some(r) = self.records.get_mut(&Self::key(root, &entry.path)) {
+      r.sha256 = Some(hash);
+    }
+  }
+
+  // 未命中时新建的记录，插件文件未变化时保留已计算的哈希
+  pub fn record_of(&self, key: &str, entry: &PluginEntry, manifest_mtime: Option<u64>) -> PluginIndexRecord {
+    PluginIndexRecord {
+      size: entry.filemeta.len(),
+      mtime: mtime_of(&entry.filemeta),
+      manifest_mtime,
+      manifest: entry.manifest.clone(),
+      sha256: self.get(key, &entry.filemeta).and_then(|r| r.sha256.clone()),
+    }
+  }
+}
+
+impl PluginEntry {
+  /*
+   * 命中索引的插件直接使用缓存的清单，不打开插件包，也不逐个读取清单的元数据
+   * 扫描结束后用本次的结果整体替换索引，已不存在的插件被删除；清单有错误的插件不缓存
+   */
+  pub async fn scan_dir_indexed(pb: PathBuf, options: &PluginScanOptions, index: &mut PluginIndex) -> anyhow::Result<PluginScanReport> {
+    Self::scan_dir_with_index(pb, options, Some(index)).await
+  }
+
+  // 索引保存在配置目录中，只读介质上改存到临时目录
+  pub async fn from_profile_indexed(entry: &ProfileEntry, options: &PluginScanOptions) -> anyhow::Result<PluginScanReport> {
+    let profile_path = PluginIndex::profile_path(entry);
+    let temp_path = PluginIndex::temp_path();
+    let mut index = if profile_path.exists() {
+      PluginIndex::load(&profile_path).await
+    } else {
+      PluginIndex::load(&temp_path).await
+    };
+
+    let res_pb = entry.path.join(PATH_PLUGIN_RESOURCES.as_path());
+    let report = Self::scan_dir_indexed(res_pb, options, &mut index).await?;
+
+    if let Err(e) = index.save(&profile_path).await {
+      warn!("failed to save plugin index to profile, {}", e);
+      index.save(&temp_path).await?;
+    }
+    Ok(report)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use std::time::Duration;
+  use tokio::fs;
+  use crate::testing::touch;
+
+  use super::PluginIndex;
+  use crate::found::PluginEntry;
+  use crate::found::scan::PluginScanOptions;
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().join("Resource");
+    let index_path = dir.path().join("Cache").join("PluginIndex.json");
+    touch(&root.join("Chrome_90.0_Cno.7z"), "chrome").await?;
+    touch(&root.join("Chrome_90.0_Cno.json"), r#"{ "depends": ["VC"] }"#).await?;
+    touch(&root.join("Tools").join("Tool_1.0_Cno.7z"), "tool").await?;
+
+    let options = PluginScanOptions::default();
+    let mut index = PluginIndex::load(&index_path).await;
+    let report = PluginEntry::scan_dir_indexed(root.clone(), &options, &mut index).await?;
+    assert_eq!(report.progress.cached, 0);
+    assert_eq!(index.records.len(), 2);
+    assert!(index.records.contains_key("Tools/Tool_1.0_Cno.7z"));
+
+    let chrome = report.entries.iter().find(|e| e.name() == Some("Chrome")).unwrap();
+    index.set_sha256(&root, chrome, "abc".into());
+    index.save(&index_path).await?;
+
+    let mut index = PluginIndex::load(&index_path).await;
+    let report = PluginEntry::scan_dir_indexed(root.clone(), &options, &mut index).await?;
+    assert_eq!(report.progress.cached, 2);
+    let chrome = report.entries.iter().find(|e| e.name() == Some("Chrome")).unwrap();
+    assert_eq!(chrome.depends(), &["VC".to_string()]);
+    assert_eq!(index.sha256(&root, chrome), Some("abc"));
+
+    // 修改插件包与清单后缓存失效
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    touch(&root.join("Chrome_90.0_Cno.7z"), "chrome 2").await?;
+    touch(&root.join("Tools").join("Tool_1.0_Cno.json"), r#"{ "depends": ["Chrome"] }"#).await?;
+    fs::remove_file(root.join("Chrome_90.0_Cno.json")).await?;
+    touch(&root.join("New_1.0_Cno.7z"), "").await?;
+
+    let report = PluginEntry::scan_dir_indexed(root.clone(), &options, &mut index).await?;
+    assert_eq!(report.progress.cached, 0);
+    assert_eq!(index.records.len(), 3);
+    let chrome = report.entries.iter().find(|e| e.name() == Some("Chrome")).unwrap();
+    assert!(chrome.depends().is_empty());
+    assert_eq!(index.sha256(&root, chrome), None);
+    let tool = report.entries.iter().find(|e| e.name() == Some("Tool")).unwrap();
+    assert_eq!(tool.depends(), &["Chrome".to_string()]);
+
+    fs::remove_file(root.join("New_1.0_Cno.7z")).await?;
+    PluginEntry::scan_dir_indexed(root.clone(), &options, &mut index).await?;
+    assert_eq!(index.records.len(), 2);
+
+    fs::write(&index_path, "broken").await?;
+    assert_eq!(PluginIndex::load(&index_path).await, PluginIndex::default());
+
+    Ok(())
+  }
+}
@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use super::{PluginEntry, PluginEntries};
+
+use lazy_static::lazy_static;
+use log::info;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PluginCategory {
+  pub id: &'static str,
+  pub display_name: &'static str,
+}
+
+lazy_static! {
+  // 与插件中心的分类保持一致
+  pub static ref KNOWN_PLUGIN_CATEGORIES: Vec<PluginCategory> = vec![
+    PluginCategory { id: "utility", display_name: "实用工具" },
+    PluginCategory { id: "develop", display_name: "开发辅助" },
+    PluginCategory { id: "hardware", display_name: "配置检测" },
+    PluginCategory { id: "explorer", display_name: "资源管理" },
+    PluginCategory { id: "office", display_name: "办公编辑" },
+    PluginCategory { id: "input", display_name: "输入法" },
+    PluginCategory { id: "ide", display_name: "集成开发" },
+    PluginCategory { id: "capture", display_name: "录屏看图" },
+    PluginCategory { id: "disk", display_name: "磁盘数据" },
+    PluginCategory { id: "security", display_name: "安全急救" },
+    PluginCategory { id: "meeting", display_name: "网课会议" },
+    PluginCategory { id: "im", display_name: "即时通讯" },
+    PluginCategory { id: "backup", display_name: "安装备份" },
+    PluginCategory { id: "game", display_name: "游戏娱乐" },
+    PluginCategory { id: "runtime", display_name: "运行环境" },
+    PluginCategory { id: "archive", display_name: "压缩镜像" },
+    PluginCategory { id: "theme", display_name: "美化增强" },
+    PluginCategory { id: "driver", display_name: "驱动管理" },
+    PluginCategory { id: "transfer", display_name: "下载上传" },
+    PluginCategory { id: "browser", display_name: "浏览器" },
+    PluginCategory { id: "player", display_name: "影音播放" },
+    PluginCategory { id: "remote", display_name: "远程连接" },
+  ];
+}
+
+impl PluginCategory {
+  pub fn lookup(s: &str) -> Option<&'static PluginCategory> {
+    let s = s.trim();
+    KNOWN_PLUGIN_CATEGORIES.iter().find(
+      |c| c.id.eq_ignore_ascii_case(s) || c.display_name == s
+    )
+  }
+
+  // 已知分类统一为 id，未知分类保留原样
+  pub fn normalize(s: &str) -> String {
+    Self::lookup(s)
+      .map(|c| c.id.to_string())
+      .unwrap_or_else(|| s.trim().to_string())
+  }
+
+  // 未知分类同样不区分大小写
+  pub fn same(a: &str, b: &str) -> bool {
+    Self::normalize(a).to_lowercase() == Self::normalize(b).to_lowercase()
+  }
+
+  pub fn display_name(s: &str) -> String {
+    Self::lookup(s)
+      .map(|c| c.display_name.to_string())
+      .unwrap_or_else(|| s.trim().to_string())
+  }
+}
+
+impl PluginEntry {
+  // 所在分类文件夹优先于文件名中的分类
+  pub fn category(&self) -> Option<String> {
+    self.categories().into_iter().next()
+  }
+
+  pub fn categories(&self) -> Vec<String> {
+    let mut v: Vec<String> = vec![];
+    let from_meta = self.meta.as_ref().and_then(|m| m.category.as_deref());
+    for c in self.category_folder.as_deref().into_iter().chain(from_meta) {
+      let c = PluginCategory::normalize(c);
+      if !c.is_empty() && !v.iter().any(|x| PluginCategory::same(x, &c)) {
+        v.push(c);
+      }
+    }
+    v
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PluginCategoryFilter {
+  // 为空时包含全部分类
+  pub include: Vec<String>,
+  pub exclude: Vec<String>,
+}
+
+impl PluginCategoryFilter {
+  pub fn new(include: Vec<String>, exclude: Vec<String>) -> Self {
+    Self {
+      include: include.iter().map(|s| PluginCategory::normalize(s)).collect(),
+      exclude: exclude.iter().map(|s| PluginCategory::normalize(s)).collect(),
+    }
+  }
+
+  pub fn matches(&self, entry: &PluginEntry) -> bool {
+    let cates = entry.categories();
+    let has = |list: &Vec<String>| list.iter().any(
+      |c| cates.iter().any(|x| PluginCategory::same(x, c))
+    );
+
+    if has(&self.exclude) {
+      return false;
+    }
+    self.include.is_empty() || has(&self.include)
+  }
+}
+
+impl PluginEntries {
+  pub fn by_category(&self, category: &str) -> Vec<&PluginEntry> {
+    self.0.iter()
+      .filter(|e| e.categories().iter().any(|c| PluginCategory::same(c, category)))
+      .collect()
+  }
+
+  pub fn uncategorized(&self) -> Vec<&PluginEntry> {
+    self.0.iter()
+      .filter(|e| e.category().is_none())
+      .collect()
+  }
+
+  pub fn categories(&self) -> BTreeMap<String, Vec<&PluginEntry>> {
+    let mut m = BTreeMap::new();
+    for e in &self.0 {
+      for c in e.categories() {
+        // 大小写不同的同名分类合并，沿用先出现的写法
+        let key = m.keys().find(|k: &&String| PluginCategory::same(k, &c)).cloned().unwrap_or(c);
+        m.entry(key).or_insert_with(Vec::new).push(e);
+      }
+    }
+    m
+  }
+
+  pub fn filter(&self, filter: &PluginCategoryFilter) -> Vec<&PluginEntry> {
+    info!("filter plugins with {:?}", filter);
+    self.0.iter()
+      .filter(|e| filter.matches(e))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use tokio::fs;
+  use crate::testing::touch;
+
+  use super::{PluginCategory, PluginCategoryFilter};
+  use crate::found::PluginEntry;
+
+  fn names(v: &[&PluginEntry]) -> Vec<String> {
+    let mut v = v.iter().map(|e| e.name().unwrap_or("").to_string()).collect::<Vec<_>>();
+    v.sort();
+    v
+  }
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    assert_eq!(PluginCategory::normalize("浏览器"), "browser");
+    assert_eq!(PluginCategory::normalize("Browser"), "browser");
+    assert_eq!(PluginCategory::display_name("browser"), "浏览器");
+    assert_eq!(PluginCategory::normalize("Mine"), "Mine");
+    assert!(PluginCategory::same("Mine", "mine"));
+    assert!(!PluginCategory::same("Mine", "Mined"));
+
+    let dir = tempfile::tempdir()?;
+    touch(&dir.path().join("浏览器").join("Chrome_90.0_Cno.7z"), "").await?;
+    touch(&dir.path().join("浏览器").join("Old").join("IE_8.0_Cno.7z"), "").await?;
+    touch(&dir.path().join("Tools").join("Everything_1.4_Cno_实用工具.7z"), "").await?;
+    touch(&dir.path().join("Notepad++_8.0_Cno_tools.7z"), "").await?;
+    touch(&dir.path().join("VCRuntime_2019_Cno_运行环境.7z"), "").await?;
+    touch(&dir.path().join("Notepad_1.0_Cno.7z"), "").await?;
+
+    let entries = PluginEntry::scan_dir(dir.path().to_path_buf()).await?;
+
+    assert_eq!(names(&entries.by_category("browser")), vec!["Chrome", "IE"]);
+    assert_eq!(names(&entries.by_category("Tools")), vec!["Everything", "Notepad++"]);
+    assert_eq!(names(&entries.by_category("tools")), vec!["Everything", "Notepad++"]);
+    assert_eq!(names(&entries.by_category("utility")), vec!["Everything"]);
+    assert_eq!(names(&entries.uncategorized()), vec!["Notepad"]);
+    assert_eq!(entries.categories().len(), 4);
+
+    let filter = PluginCategoryFilter::new(
+      vec!["浏览器".into(), "runtime".into()],
+      vec![],
+    );
+    assert_eq!(names(&entries.filter(&filter)), vec!["Chrome", "IE", "VCRuntime"]);
+
+    let filter = PluginCategoryFilter::new(vec![], vec!["Browser".into()]);
+    assert_eq!(names(&entries.filter(&filter)), vec!["Everything", "Notepad", "Notepad++", "VCRuntime"]);
+
+    Ok(())
+  }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use super::{PluginEntry, PluginEntries, PluginExtension};
+
+use anyhow::anyhow;
+use log::{info, warn};
+use tokio::fs;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginRenameStatus {
+  // 预演模式下可以执行
+  Planned,
+  Renamed,
+  // 已是目标扩展名
+  Unchanged,
+  // 目标文件已存在，值为占用的路径
+  Conflict(PathBuf),
+  // 只读介质或没有写权限
+  ReadOnly,
+  Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginRenameOp {
+  pub from: PathBuf,
+  pub to: PathBuf,
+  pub extension: PluginExtension,
+  pub status: PluginRenameStatus,
+}
+
+impl PluginRenameOp {
+  pub fn is_ok(&self) -> bool {
+    matches!(
+      self.status,
+      PluginRenameStatus::Planned | PluginRenameStatus::Renamed | PluginRenameStatus::Unchanged
+    )
+  }
+}
+
+fn is_read_only(e: &io::Error) -> bool {
+  matches!(e.kind(), io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem)
+    // ERROR_WRITE_PROTECT
+    || (cfg!(windows) && e.raw_os_error() == Some(19))
+}
+
+// 同一目录下大小写不同的文件名在 Windows 上视为同一文件
+fn same_file_name(a: &Path, b: &Path) -> bool {
+  a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+}
+
+/*
+ * 不覆盖已有文件的重命名，`fs::rename` 在 Windows 上会直接替换目标
+ * 先独占创建目标占位，已存在时返回 `AlreadyExists`，再用重命名替换占位文件；
+ * 只改大小写时目标就是源文件本身，不创建占位
+ */
+async fn rename_no_replace(from: &Path, to: &Path) -> io::Result<()> {
+  let placeholder = !same_file_name(from, to);
+  if placeholder {
+    fs::OpenOptions::new().write(true).create_new(true).open(to).await?;
+  }
+  if let Err(e) = fs::rename(from, to).await {
+    if placeholder {
+      let _ = fs::remove_file(to).await;
+    }
+    return Err(e);
+  }
+  Ok(())
+}
+
+impl PluginEntry {
+  pub fn path_with_extension(&self, ext: PluginExtension) -> PathBuf {
+    self.path.with_extension(ext.as_ext())
+  }
+
+  pub fn rename_plan(&self, ext: PluginExtension) -> PluginRenameOp {
+    let to = self.path_with_extension(ext);
+    let status = if self.extension == Some(ext) && to == self.path {
+      PluginRenameStatus::Unchanged
+    } else if to.exists() && !same_file_name(&to, &self.path) {
+      PluginRenameStatus::Conflict(to.clone())
+    } else {
+      PluginRenameStatus::Planned
+    };
+
+    PluginRenameOp {
+      from: self.path.clone(),
+      to,
+      extension: ext,
+      status,
+    }
+  }
+
+  /*
+   * 修改插件扩展名，用于启用、禁用或转为 LocalBoost
+   * 只重命名插件包本身，旁路文件（清单、校验值、签名）与插件包同名，无需改动；
+   * 重命名不改变文件内容与时间戳，已解析的元数据、清单和信任状态都会保留
+   */
+  pub async fn set_extension(&mut self, ext: PluginExtension) -> anyhow::Result<PluginRenameOp> {
+    let op = self.rename_plan(ext);
+    match &op.status {
+      PluginRenameStatus::Unchanged => return Ok(op),
+      PluginRenameStatus::Conflict(p) => {
+        return Err(anyhow!("cannot rename {:?}, {:?} already exists", op.from, p));
+      }
+      _ => {}
+    }
+
+    let op = self.apply_rename(op).await;
+    match &op.status {
+      PluginRenameStatus::Conflict(p) => Err(anyhow!("cannot rename {:?}, {:?} already exists", op.from, p)),
+      PluginRenameStatus::ReadOnly => Err(anyhow!("cannot rename {:?}, read-only media", op.from)),
+      PluginRenameStatus::Failed(e) => Err(anyhow!("cannot rename {:?}, {}", op.from, e)),
+      _ => Ok(op),
+    }
+  }
+
+  async fn apply_rename(&mut self, mut op: PluginRenameOp) -> PluginRenameOp {
+    info!("rename plugin {:?} -> {:?}", op.from, op.to);
+    if let Err(e) = rename_no_replace(&op.from, &op.to).await {
+      warn!("failed to rename {:?}, {}", op.from, e);
+      op.status = if e.kind() == io::ErrorKind::AlreadyExists {
+        PluginRenameStatus::Conflict(op.to.clone())
+      } else if is_read_only(&e) {
+        PluginRenameStatus::ReadOnly
+      } else {
+        PluginRenameStatus::Failed(e.to_string())
+      };
+      return op;
+    }
+
+    self.path = op.to.clone();
+    self.extension = Some(op.extension);
+    if let Ok(m) = fs::metadata(&self.path).await {
+      self.filemeta = m;
+    }
+    op.status = PluginRenameStatus::Renamed;
+    op
+  }
+}
+
+impl PluginEntries {
+  /*
+   * 批量修改扩展名，`filter` 选出要处理的插件
+   * `dry_run` 时只返回计划，不改动任何文件；
+   * 单个插件失败不会中断整批操作，结果逐项记录在返回值中
+   */
+  pub async fn set_extension<F>(&mut self, ext: PluginExtension, filter: F, dry_run: bool) -> Vec<PluginRenameOp>
+  where
+    F: Fn(&PluginEntry) -> bool,
+  {
+    let mut ops = vec![];
+    // 同一批中多个插件可能映射到同一个目标文件
+    let mut claimed = HashSet::new();
+
+    for e in self.0.iter_mut().filter(|e| filter(e)) {
+      let mut op = e.rename_plan(ext);
+      if op.status == PluginRenameStatus::Planned && !claimed.insert(op.to.to_string_lossy().to_lowercase()) {
+        op.status = PluginRenameStatus::Conflict(op.to.clone());
+      }
+
+      if dry_run || op.status != PluginRenameStatus::Planned {
+        if let PluginRenameStatus::Conflict(p) = &op.status {
+          warn!("skip {:?}, {:?} already exists", op.from, p);
+        }
+        ops.push(op);
+        continue;
+      }
+
+      op = e.apply_rename(op).await;
+      ops.push(op);
+    }
+
+    ops
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use tokio::fs;
+  use crate::testing::touch;
+
+  use super::PluginRenameStatus;
+  use crate::found::{PluginEntry, PluginExtension};
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    touch(&root.join("Chrome_90.0_Cno.7z"), "chrome").await?;
+    touch(&root.join("Chrome_90.0_Cno.json"), r#"{ "depends": ["VCRuntime"] }"#).await?;
+    touch(&root.join("VCRuntime_1.0_Cno.7z"), "vc").await?;
+    touch(&root.join("VCRuntime_1.0_Cno.7zf"), "vc old").await?;
+    touch(&root.join("Notepad_1.0_Cno.7zl"), "np").await?;
+
+    let mut chrome = PluginEntry::new(root.join("Chrome_90.0_Cno.7z")).await?;
+    assert_eq!(chrome.set_extension(PluginExtension::Normal).await?.status, PluginRenameStatus::Unchanged);
+
+    let op = chrome.set_extension(PluginExtension::Disabled).await?;
+    assert_eq!(op.status, PluginRenameStatus::Renamed);
+    assert_eq!(chrome.path, root.join("Chrome_90.0_Cno.7zf"));
+    assert_eq!(chrome.extension, Some(PluginExtension::Disabled));
+    assert_eq!(chrome.depends(), ["VCRuntime".to_string()]);
+    assert!(!root.join("Chrome_90.0_Cno.7z").exists());
+
+    let mut vc = PluginEntry::new(root.join("VCRuntime_1.0_Cno.7z")).await?;
+    assert!(vc.set_extension(PluginExtension::Disabled).await.is_err());
+    assert_eq!(fs::read_to_string(root.join("VCRuntime_1.0_Cno.7zf")).await?, "vc old");
+
+    let mut entries = PluginEntry::scan_dir(root.to_path_buf()).await?;
+    let ops = entries.set_extension(PluginExtension::Normal, |e| e.name() != Some("VCRuntime"), true).await;
+    assert_eq!(ops.len(), 2);
+    assert!(ops.iter().all(|o| o.status == PluginRenameStatus::Planned));
+    assert!(root.join("Notepad_1.0_Cno.7zl").exists());
+
+    let ops = entries.set_extension(PluginExtension::Normal, |_| true, false).await;
+    let renamed = ops.iter().filter(|o| o.status == PluginRenameStatus::Renamed).count();
+    let conflicts = ops.iter().filter(|o| matches!(o.status, PluginRenameStatus::Conflict(_))).count();
+    assert_eq!((renamed, conflicts), (2, 1));
+    assert!(root.join("Notepad_1.0_Cno.7z").exists());
+    assert!(root.join("Chrome_90.0_Cno.7z").exists());
+
+    // 计划之后目标才出现，重命名时不会覆盖
+    let mut np = PluginEntry::new(root.join("Notepad_1.0_Cno.7z")).await?;
+    let op = np.rename_plan(PluginExtension::Disabled);
+    assert_eq!(op.status, PluginRenameStatus::Planned);
+    touch(&root.join("Notepad_1.0_Cno.7zf"), "np new").await?;
+    let op = np.apply_rename(op).await;
+    assert_eq!(op.status, PluginRenameStatus::Conflict(root.join("Notepad_1.0_Cno.7zf")));
+    assert_eq!(fs::read_to_string(root.join("Notepad_1.0_Cno.7zf")).await?, "np new");
+    assert_eq!(fs::read_to_string(root.join("Notepad_1.0_Cno.7z")).await?, "np");
+
+    Ok(())
+  }
+}
@@ -0,0 +1,261 @@
+use std::collections::{BTreeSet, HashMap};
+use super::{PluginEntry, PluginExtension};
+
+use log::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginDependIssueKind {
+  // 没有任何插件提供该依赖
+  Missing,
+  // 提供该依赖的插件均被禁用
+  Disabled,
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginDependIssue<'a> {
+  pub entry: &'a PluginEntry,
+  pub depend: String,
+  pub kind: PluginDependIssueKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginLoadOrder<'a> {
+  // 按依赖排序后的加载顺序，成环的插件排在最后
+  pub order: Vec<&'a PluginEntry>,
+  pub cycles: Vec<Vec<&'a PluginEntry>>,
+  pub issues: Vec<PluginDependIssue<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginGraph<'a> {
+  pub entries: Vec<&'a PluginEntry>,
+  pub issues: Vec<PluginDependIssue<'a>>,
+  // depends[i] 为 entries[i] 所依赖的插件下标
+  depends: Vec<Vec<usize>>,
+}
+
+fn depend_key(name: &str) -> String {
+  name.trim().to_lowercase()
+}
+
+impl<'a> PluginGraph<'a> {
+  pub fn new<I: IntoIterator<Item = &'a PluginEntry>>(entries: I) -> Self {
+    let entries = entries.into_iter().collect::<Vec<_>>();
+    info!("build plugin dependency graph, {} entries", entries.len());
+
+    let mut providers: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, e) in entries.iter().enumerate() {
+      if let Some(name) = e.name() {
+        providers.entry(depend_key(name)).or_default().push(i);
+      }
+    }
+
+    let mut depends = vec![vec![]; entries.len()];
+    let mut issues = vec![];
+    for (i, e) in entries.iter().enumerate() {
+      for d in e.depends() {
+        let found = providers.get(&depend_key(d))
+          .map(|v| v.as_slice())
+          .unwrap_or(&[]);
+        let enabled = found.iter()
+          .copied()
+          .filter(|&p| p != i)
+          .filter(|&p| entries[p].extension != Some(PluginExtension::Disabled))
+          .collect::<Vec<_>>();
+
+        if !enabled.is_empty() {
+          depends[i].extend(enabled);
+          continue;
+        }
+
+        let kind = if found.iter().any(|&p| p != i) {
+          PluginDependIssueKind::Disabled
+        } else {
+          PluginDependIssueKind::Missing
+        };
+        warn!("plugin {:?} depends on {:?}, {:?}", e.path, d, kind);
+        issues.push(PluginDependIssue {
+          entry: e,
+          depend: d.to_string(),
+          kind,
+        });
+      }
+    }
+
+    Self {
+      entries,
+      issues,
+      depends,
+    }
+  }
+
+  pub fn depends_of(&self, entry: &PluginEntry) -> Vec<&'a PluginEntry> {
+    self.entries.iter()
+      .position(|e| e.path == entry.path)
+      .map(|i| self.depends[i].iter().map(|&d| self.entries[d]).collect())
+      .unwrap_or_default()
+  }
+
+  pub fn load_order(&self) -> PluginLoadOrder<'a> {
+    let n = self.entries.len();
+    let mut pending = self.depends.iter().map(|d| d.len()).collect::<Vec<_>>();
+    let mut dependents = vec![vec![]; n];
+    for (i, d) in self.depends.iter().enumerate() {
+      for &p in d {
+        dependents[p].push(i);
+      }
+    }
+
+    // 同层级保持原有的扫描顺序
+    let mut ready = (0..n).filter(|&i| pending[i] == 0).collect::<BTreeSet<_>>();
+    let mut order = vec![];
+    while let Some(i) = ready.iter().next().copied() {
+      ready.remove(&i);
+      order.push(i);
+      for &d in &dependents[i] {
+        pending[d] -= 1;
+        if pending[d] == 0 {
+          ready.insert(d);
+        }
+      }
+    }
+
+    let mut cycles = vec![];
+    if order.len() < n {
+      let rest = (0..n).filter(|&i| pending[i] > 0).collect::<Vec<_>>();
+      for c in self.strongly_connected(&rest) {
+        if c.len() > 1 {
+          warn!("found dependency cycle {:?}", c.iter().map(|&i| &self.entries[i].path).collect::<Vec<_>>());
+          cycles.push(c.iter().map(|&i| self.entries[i]).collect());
+        }
+      }
+      order.extend(rest);
+    }
+
+    PluginLoadOrder {
+      order: order.into_iter().map(|i| self.entries[i]).collect(),
+      cycles,
+      issues: self.issues.clone(),
+    }
+  }
+
+  // Tarjan
+  fn strongly_connected(&self, nodes: &[usize]) -> Vec<Vec<usize>> {
+    struct State {
+      index: usize,
+      indices: HashMap<usize, usize>,
+      lowlink: HashMap<usize, usize>,
+      stack: Vec<usize>,
+      on_stack: BTreeSet<usize>,
+      result: Vec<Vec<usize>>,
+    }
+
+    fn visit(g: &[Vec<usize>], nodes: &BTreeSet<usize>, v: usize, s: &mut State) {
+      s.indices.insert(v, s.index);
+      s.lowlink.insert(v, s.index);
+      s.index += 1;
+      s.stack.push(v);
+      s.on_stack.insert(v);
+
+      for &w in g[v].iter().filter(|w| nodes.contains(w)) {
+        if !s.indices.contains_key(&w) {
+          visit(g, nodes, w, s);
+          let low = s.lowlink[&v].min(s.lowlink[&w]);
+          s.lowlink.insert(v, low);
+        } else if s.on_stack.contains(&w) {
+          let low = s.lowlink[&v].min(s.indices[&w]);
+          s.lowlink.insert(v, low);
+        }
+      }
+
+      if s.lowlink[&v] == s.indices[&v] {
+        let mut c = vec![];
+        while let Some(w) = s.stack.pop() {
+          s.on_stack.remove(&w);
+          c.push(w);
+          if w == v {
+            break;
+          }
+        }
+        c.sort_unstable();
+        s.result.push(c);
+      }
+    }
+
+    let set = nodes.iter().copied().collect::<BTreeSet<_>>();
+    let mut s = State {
+      index: 0,
+      indices: HashMap::new(),
+      lowlink: HashMap::new(),
+      stack: vec![],
+      on_stack: BTreeSet::new(),
+      result: vec![],
+    };
+    for &v in nodes {
+      if !s.indices.contains_key(&v) {
+        visit(&self.depends, &set, v, &mut s);
+      }
+    }
+    s.result.sort();
+    s.result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use tokio::fs;
+
+  use super::{PluginDependIssueKind, PluginGraph};
+  use crate::found::PluginEntry;
+
+  async fn plugin(dir: &Path, name: &str, depends: &[&str]) -> anyhow::Result<PluginEntry> {
+    let pb = dir.join(name);
+    fs::write(&pb, b"").await?;
+    if !depends.is_empty() {
+      fs::write(
+        pb.with_extension("json"),
+        serde_json::json!({ "depends": depends }).to_string()
+      ).await?;
+    }
+    PluginEntry::new(pb).await
+  }
+
+  fn names(v: &[&PluginEntry]) -> Vec<String> {
+    v.iter().map(|e| e.name().unwrap_or("").to_string()).collect()
+  }
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let entries = vec![
+      plugin(dir.path(), "Chrome_90.0_Cno.7z", &["VCRuntime", "dotnet"]).await?,
+      plugin(dir.path(), "DotNet_4.8_Cno.7zf", &[]).await?,
+      plugin(dir.path(), "Tool_1.0_Cno.7z", &["Chrome", "Fonts"]).await?,
+      plugin(dir.path(), "VCRuntime_2019_Cno.7z", &[]).await?,
+      plugin(dir.path(), "A_1.0_Cno.7z", &["B"]).await?,
+      plugin(dir.path(), "B_1.0_Cno.7z", &["A"]).await?,
+    ];
+
+    let graph = PluginGraph::new(&entries);
+    assert_eq!(names(&graph.depends_of(&entries[0])), vec!["VCRuntime"]);
+
+    let order = graph.load_order();
+    assert_eq!(
+      names(&order.order),
+      vec!["DotNet", "VCRuntime", "Chrome", "Tool", "A", "B"]
+    );
+    assert_eq!(order.cycles.len(), 1);
+    assert_eq!(names(&order.cycles[0]), vec!["A", "B"]);
+
+    let issues = order.issues.iter()
+      .map(|i| (i.entry.name().unwrap_or(""), i.depend.as_str(), i.kind))
+      .collect::<Vec<_>>();
+    assert_eq!(issues, vec![
+      ("Chrome", "dotnet", PluginDependIssueKind::Disabled),
+      ("Tool", "Fonts", PluginDependIssueKind::Missing),
+    ]);
+
+    Ok(())
+  }
+}
@@ -0,0 +1,81 @@
+use std::path::{Path, PathBuf};
+use edgeless_core::options::define::EXT_PLUGIN_MANIFEST;
+use serde::{Deserialize, Serialize};
+
+use anyhow::anyhow;
+use log::info;
+use tokio::fs;
+
+/*
+ * 插件清单，与插件包同名的旁路文件
+ * e.g. `Chrome_90.0_Cno.7z` -> `Chrome_90.0_Cno.json`
+ *
+ * {
+ *   "depends": ["VCRuntime", "DotNet"],
+ *   "sha256": "...",
+ *   "exclusive": false,
+ *   "watch": false
+ * }
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginManifest {
+  // 依赖的插件名，对应 `PluginMetadata.name`
+  pub depends: Vec<String>,
+  // 插件包的 SHA-256 校验值
+  pub sha256: Option<String>,
+  // 脚本需要独占运行，不能与其他插件的脚本同时执行
+  pub exclusive: bool,
+  // 脚本会在监视目录中新建文件（如桌面快捷方式），需要记入日志以便回滚
+  pub watch: bool,
+}
+
+impl PluginManifest {
+  pub fn sidecar_path(plugin: &Path) -> PathBuf {
+    plugin.with_extension(*EXT_PLUGIN_MANIFEST)
+  }
+
+  pub fn parse(s: &str) -> anyhow::Result<Self> {
+    serde_json::from_str(s).map_err(|e| anyhow!("invalid plugin manifest, {}", e))
+  }
+
+  pub async fn load(plugin: &Path) -> anyhow::Result<Option<Self>> {
+    let pb = Self::sidecar_path(plugin);
+    if !(pb.exists() && pb.is_file()) {
+      return Ok(None);
+    }
+
+    info!("found plugin manifest {:?}", pb);
+    let text = fs::read_to_string(&pb).await?;
+    let manifest = Self::parse(&text)
+      .map_err(|e| anyhow!("{:?}: {}", pb, e))?;
+    info!("parsed, manifest = {:?}", manifest);
+
+    Ok(Some(manifest))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use super::PluginManifest;
+
+  #[test]
+  fn it_works() -> anyhow::Result<()> {
+    assert_eq!(
+      PluginManifest::sidecar_path(Path::new("Resource/Chrome_90.0.1_Cno.7z")),
+      Path::new("Resource/Chrome_90.0.1_Cno.json")
+    );
+
+    let m = PluginManifest::parse(r#"{ "depends": ["VCRuntime"] }"#)?;
+    assert_eq!(m.depends, vec!["VCRuntime".to_string()]);
+    assert!(!m.exclusive);
+    assert!(PluginManifest::parse(r#"{ "exclusive": true }"#)?.exclusive);
+    assert!(PluginManifest::parse(r#"{ "watch": true }"#)?.watch);
+    assert_eq!(PluginManifest::parse("{}")?, PluginManifest::default());
+    assert!(PluginManifest::parse("depends").is_err());
+
+    Ok(())
+  }
+}
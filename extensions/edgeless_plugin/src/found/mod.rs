@@ -1,206 +1,293 @@
-#![allow(unused_imports)]
-pub mod localboost;
-use std::{ffi::OsString, fs::Metadata, path::{Path, PathBuf}};
-use async_recursion::async_recursion;
-use edgeless_core::found::ProfileEntry;
-pub use edgeless_core::options::define::{
-  PATH_PLUGIN_RESOURCES,
-  EXT_PLUGIN_DISABLE,
-  EXT_PLUGIN_LOCALBOOST,
-  EXT_PLUGIN_NORMAL,
-  EXT_PLUGIN_PATTERN,
-  PATH_EXTERNAL_LAUNCHER
-};
-
-use anyhow::anyhow;
-use log::{info, warn, error, debug};
-use tokio::fs;
-
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct PluginMetadata {
-  pub name: String,
-  pub version: String,
-  pub author: String,
-  pub category: Option<String>,
-  _source: String,
-}
-
-impl PluginMetadata {
-  pub fn parse(s: &str) -> Option<Self> {
-    info!("try parse {:?} to plugin meta", s);
-    let ul_sep = s.split("_").collect::<Vec<_>>();
-    info!("sep = {:?}", ul_sep);
-    info!("sep len = {}", ul_sep.len());
-    if ul_sep.len() < 3 || ul_sep.len() > 4 {
-      error!("parse to meta error!");
-      return Some(PluginMetadata {
-        name: String::new(),
-        version: String::new(),
-        author: String::new(),
-        category: None,
-        _source: s.into(),
-      });
-    }
-    
-    let meta = PluginMetadata {
-      name: ul_sep[0].to_string(),
-      version: ul_sep[1].to_string(),
-      author: ul_sep[2].to_string(),
-      category: ul_sep.get(3).map(|s| s.to_string()),
-      _source: s.into(),
-    };
-
-    info!("parsed, meta = {:#?}", meta);
-
-    Some(meta)
-  }
-}
-
-impl ToString for PluginMetadata {
-  fn to_string(&self) -> String {
-    if let Some(cate) = &self.category {
-      return format!("{}_{}_{}_{}", &self.name, &self.version, &self.author, cate);
-    } else {
-      return format!("{}_{}_{}", &self.name, &self.version, &self.author);
-    }
-  }
-}
-
-impl AsRef<str> for PluginMetadata {
-  fn as_ref(&self) -> &str {
-      &self._source
-  }
-}
-
-impl AsRef<String> for PluginMetadata {
-  fn as_ref(&self) -> &String {
-      &self._source
-  }
-}
-
-impl Into<String> for PluginMetadata {
-  fn into(self) -> String {
-      return self.to_string();
-  }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum PluginExtension {
-  Normal,
-  Localboost,
-  Disabled,
-}
-
-impl PluginExtension {
-  pub fn new(ext: &str) -> Option<Self> {
-    info!("parse plugin ext, ext = {:?}", ext);
-    if !EXT_PLUGIN_PATTERN.is_match(ext) {
-      warn!("invaild plugin ext");
-      return None;
-    }
-    let o = match ext {
-      "7z" => Self::Normal,
-      "7zl" => Self::Localboost,
-      "7zf" => Self::Disabled,
-      _ => {
-        info!("unknown plugin ext, disabled, {:?}", ext);
-        Self::Disabled
-      },
-    };
-
-    info!("parsed, {:?}", o);
-
-    Some(o)
-  }
-}
-
-#[derive(Debug, Clone)]
-pub struct PluginEntry {
-  pub path: PathBuf,
-  pub extension: Option<PluginExtension>,
-  pub meta: Option<PluginMetadata>,
-  pub filemeta: Metadata,
-}
-
-impl PluginEntry {
-  pub async fn new(pb: PathBuf) -> anyhow::Result<Self> {
-    if !(pb.exists() && pb.is_file()) {
-      return Err(anyhow!("not found this file"));
-    }
-
-    let ext = pb.extension().ok_or(
-      anyhow!("invalid extension")
-    )?.to_string_lossy();
-
-    let ext = PluginExtension::new(&ext);
-
-    let f = fs::File::open(&pb).await?;
-    let f = f.metadata().await?;
-
-    let s = pb.file_stem().map(
-      |v| v.to_str().map(PluginMetadata::parse).flatten()
-    ).flatten();
-
-    Ok(Self {
-      path: pb,
-      extension: ext,
-      meta: s,
-      filemeta: f,
-    })
-  }
-
-  pub async fn from_profile(entry: &ProfileEntry) -> anyhow::Result<Vec<Self>> {
-    let res_pb = entry.path.join(PATH_PLUGIN_RESOURCES.clone());
-    
-
-    if !(res_pb.exists() && res_pb.is_dir()) {
-      return Err(anyhow!("not found plugin resource in {:#?}", entry));
-    }   
-    
-    Self::scan_dir(res_pb).await
-  }
-
-  #[async_recursion]
-  pub async fn scan_dir(pb: PathBuf) -> anyhow::Result<Vec<Self>> {
-    let mut dir= fs::read_dir(pb).await?;
-    let mut plugins = vec![];
-    loop {
-      if let Some(d) = dir.next_entry().await? {
-        let meta = d.metadata().await?;
-        if meta.is_file() {
-          let r = Self::new(
-              d.path()
-          ).await?;
-          if let Some(_) = r.extension {
-            plugins.push(r);
-          }
-        } else if meta.is_dir() {
-          plugins.append(&mut Self::scan_dir(d.path()).await?);
-        }
-      } else {
-        break;
-      }
-    }
-
-    Ok(plugins)
-  }
-}
-
-#[cfg(test)]
-mod tests {
-    use std::{path::PathBuf, str::FromStr};
-
-    use edgeless_core::found::ProfileEntry;
-
-    use super::PluginEntry;
-
-  #[tokio::test]
-  async fn it_works() -> anyhow::Result<()> {
-    let profile = ProfileEntry::find_last().await?;
-    if let Some(profile) = profile {
-      let entries = PluginEntry::from_profile(&profile).await?;
-      println!("{:#?}", entries);
-    }
-    Ok(())
-  }
+#![allow(unused_imports)]
+pub mod localboost;
+pub mod manifest;
+pub mod graph;
+pub mod category;
+pub mod scan;
+pub mod cache;
+pub mod verify;
+pub mod trust;
+pub mod convert;
+use std::{ffi::OsString, fs::Metadata, path::{Path, PathBuf}};
+use std::ops::{Deref, DerefMut};
+use edgeless_core::found::ProfileEntry;
+pub use edgeless_core::options::define::{
+  PATH_PLUGIN_RESOURCES,
+  EXT_PLUGIN_DISABLE,
+  EXT_PLUGIN_LOCALBOOST,
+  EXT_PLUGIN_NORMAL,
+  EXT_PLUGIN_PATTERN,
+  PATH_EXTERNAL_LAUNCHER
+};
+
+use anyhow::anyhow;
+use log::{info, warn, error, debug};
+use tokio::fs;
+
+use manifest::PluginManifest;
+use scan::{PluginScanOptions, PluginScanReport};
+use trust::PluginTrustStatus;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PluginMetadata {
+  pub name: String,
+  pub version: String,
+  pub author: String,
+  pub category: Option<String>,
+  _source: String,
+}
+
+impl PluginMetadata {
+  pub fn parse(s: &str) -> Option<Self> {
+    info!("try parse {:?} to plugin meta", s);
+    let ul_sep = s.split("_").collect::<Vec<_>>();
+    info!("sep = {:?}", ul_sep);
+    info!("sep len = {}", ul_sep.len());
+    if ul_sep.len() < 3 || ul_sep.len() > 4 {
+      error!("parse to meta error!");
+      return Some(PluginMetadata {
+        name: String::new(),
+        version: String::new(),
+        author: String::new(),
+        category: None,
+        _source: s.into(),
+      });
+    }
+    
+    let meta = PluginMetadata {
+      name: ul_sep[0].to_string(),
+      version: ul_sep[1].to_string(),
+      author: ul_sep[2].to_string(),
+      category: ul_sep.get(3).map(|s| s.to_string()),
+      _source: s.into(),
+    };
+
+    info!("parsed, meta = {:#?}", meta);
+
+    Some(meta)
+  }
+}
+
+impl std::fmt::Display for PluginMetadata {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if let Some(cate) = &self.category {
+      write!(f, "{}_{}_{}_{}", &self.name, &self.version, &self.author, cate)
+    } else {
+      write!(f, "{}_{}_{}", &self.name, &self.version, &self.author)
+    }
+  }
+}
+
+impl AsRef<str> for PluginMetadata {
+  fn as_ref(&self) -> &str {
+      &self._source
+  }
+}
+
+impl AsRef<String> for PluginMetadata {
+  fn as_ref(&self) -> &String {
+      &self._source
+  }
+}
+
+impl From<PluginMetadata> for String {
+  fn from(val: PluginMetadata) -> Self {
+      val.to_string()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginExtension {
+  Normal,
+  Localboost,
+  Disabled,
+}
+
+impl PluginExtension {
+  pub fn new(ext: &str) -> Option<Self> {
+    info!("parse plugin ext, ext = {:?}", ext);
+    if !EXT_PLUGIN_PATTERN.is_match(ext) {
+      warn!("invaild plugin ext");
+      return None;
+    }
+    let o = match ext {
+      "7z" => Self::Normal,
+      "7zl" => Self::Localboost,
+      "7zf" => Self::Disabled,
+      _ => {
+        info!("unknown plugin ext, disabled, {:?}", ext);
+        Self::Disabled
+      },
+    };
+
+    info!("parsed, {:?}", o);
+
+    Some(o)
+  }
+
+  pub fn as_ext(&self) -> &'static str {
+    match self {
+      Self::Normal => *EXT_PLUGIN_NORMAL,
+      Self::Localboost => *EXT_PLUGIN_LOCALBOOST,
+      Self::Disabled => *EXT_PLUGIN_DISABLE,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginEntry {
+  pub path: PathBuf,
+  pub extension: Option<PluginExtension>,
+  pub meta: Option<PluginMetadata>,
+  pub manifest: Option<PluginManifest>,
+  // 清单文件无法解析时的错误，此时按没有清单处理
+  pub manifest_error: Option<String>,
+  // 位于 Resource 下的分类文件夹
+  pub category_folder: Option<String>,
+  pub trust: PluginTrustStatus,
+  pub filemeta: Metadata,
+}
+
+impl PluginEntry {
+  pub async fn new(pb: PathBuf) -> anyhow::Result<Self> {
+    if !(pb.exists() && pb.is_file()) {
+      return Err(anyhow!("not found this file"));
+    }
+
+    let f = fs::metadata(&pb).await?;
+    Self::from_metadata(pb, f).await
+  }
+
+  // 直接使用目录项的元数据，无需打开文件
+  pub async fn from_metadata(pb: PathBuf, f: Metadata) -> anyhow::Result<Self> {
+    match PluginManifest::load(&pb).await {
+      Ok(manifest) => Self::from_parts(pb, f, manifest),
+      Err(e) => {
+        warn!("ignore plugin manifest of {:?}, {}", pb, e);
+        let mut entry = Self::from_parts(pb, f, None)?;
+        entry.manifest_error = Some(e.to_string());
+        Ok(entry)
+      }
+    }
+  }
+
+  pub(crate) fn from_parts(pb: PathBuf, f: Metadata, manifest: Option<PluginManifest>) -> anyhow::Result<Self> {
+    let ext = pb.extension().ok_or(
+      anyhow!("invalid extension")
+    )?.to_string_lossy();
+
+    let ext = PluginExtension::new(&ext);
+
+    let s = pb.file_stem().and_then(|v| v.to_str().and_then(PluginMetadata::parse));
+
+    Ok(Self {
+      path: pb,
+      extension: ext,
+      meta: s,
+      manifest,
+      manifest_error: None,
+      category_folder: None,
+      trust: PluginTrustStatus::Unchecked,
+      filemeta: f,
+    })
+  }
+
+  pub fn name(&self) -> Option<&str> {
+    self.meta.as_ref()
+      .map(|m| m.name.as_str())
+      .filter(|n| !n.is_empty())
+  }
+
+  pub fn depends(&self) -> &[String] {
+    self.manifest.as_ref()
+      .map(|m| m.depends.as_slice())
+      .unwrap_or(&[])
+  }
+
+  pub fn is_exclusive(&self) -> bool {
+    self.manifest.as_ref()
+      .map(|m| m.exclusive)
+      .unwrap_or(false)
+  }
+
+  pub fn is_watched(&self) -> bool {
+    self.manifest.as_ref()
+      .map(|m| m.watch)
+      .unwrap_or(false)
+  }
+
+  pub async fn from_profile(entry: &ProfileEntry) -> anyhow::Result<PluginEntries> {
+    let res_pb = entry.path.join(PATH_PLUGIN_RESOURCES.clone());
+    
+
+    if !(res_pb.exists() && res_pb.is_dir()) {
+      return Err(anyhow!("not found plugin resource in {:#?}", entry));
+    }   
+    
+    Self::scan_dir(res_pb).await
+  }
+
+  pub async fn from_profile_with(entry: &ProfileEntry, options: &PluginScanOptions) -> anyhow::Result<PluginScanReport> {
+    let res_pb = entry.path.join(PATH_PLUGIN_RESOURCES.clone());
+
+    if !(res_pb.exists() && res_pb.is_dir()) {
+      return Err(anyhow!("not found plugin resource in {:#?}", entry));
+    }
+
+    Self::scan_dir_with(res_pb, options).await
+  }
+
+  pub async fn scan_dir(pb: PathBuf) -> anyhow::Result<PluginEntries> {
+    let report = Self::scan_dir_with(pb, &PluginScanOptions::default()).await?;
+    for e in &report.errors {
+      error!("failed to load plugin {:?}, {}", e.path, e.error);
+    }
+    Ok(report.entries)
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PluginEntries (pub Vec<PluginEntry>);
+impl From<Vec<PluginEntry>> for PluginEntries {
+  fn from(v: Vec<PluginEntry>) -> Self {
+      Self(v)
+  }
+}
+
+impl Deref for PluginEntries {
+  type Target = Vec<PluginEntry>;
+  fn deref(&self) -> &Self::Target {
+      &self.0
+  }
+}
+
+impl DerefMut for PluginEntries {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+      &mut self.0
+  }
+}
+
+impl From<PluginEntries> for Vec<PluginEntry> {
+  fn from(val: PluginEntries) -> Self {
+    val.0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, str::FromStr};
+
+    use edgeless_core::found::ProfileEntry;
+
+    use super::PluginEntry;
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    let profile = ProfileEntry::find_last().await?;
+    if let Some(profile) = profile {
+      let entries = PluginEntry::from_profile(&profile).await?;
+      println!("{:#?}", entries);
+    }
+    Ok(())
+  }
 }
\ No newline at end of file
@@ -0,0 +1,484 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use edgeless_core::options::define::{EXT_PLUGIN_MANIFEST, PATH_PLUGIN_IGNORE};
+use edgeless_utils::{wildcard_match, FileAttributes};
+use super::{PluginEntry, PluginEntries, PluginExtension};
+use super::cache::{mtime_of, PluginIndex, PluginIndexRecord};
+use super::manifest::PluginManifest;
+
+use anyhow::anyhow;
+use log::{info, warn};
+use tokio::fs;
+use tokio::sync::{mpsc, Semaphore};
+
+#[derive(Debug, Clone)]
+pub struct PluginScanOptions {
+  // 最多进入的子文件夹层数，None 为不限制
+  pub max_depth: Option<usize>,
+  pub ignore: Vec<String>,
+  pub skip_hidden: bool,
+  pub follow_links: bool,
+  // 同时读取的文件夹与文件数
+  pub concurrency: usize,
+}
+
+impl Default for PluginScanOptions {
+  fn default() -> Self {
+    Self {
+      max_depth: Some(8),
+      ignore: vec![],
+      skip_hidden: true,
+      follow_links: true,
+      concurrency: 8,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginScanError {
+  pub path: PathBuf,
+  pub error: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PluginScanReport {
+  pub entries: PluginEntries,
+  pub errors: Vec<PluginScanError>,
+  pub skipped: Vec<PathBuf>,
+  pub progress: PluginScanProgress,
+}
+
+impl PluginScanOptions {
+  // 读取根目录下的 `.edgelessignore`，每行一个通配符，`#` 开头为注释
+  pub async fn with_ignore_file(mut self, root: &Path) -> anyhow::Result<Self> {
+    let pb = root.join(PATH_PLUGIN_IGNORE.as_path());
+    if pb.exists() && pb.is_file() {
+      info!("found ignore file {:?}", pb);
+      let text = fs::read_to_string(&pb).await?;
+      self.ignore.extend(
+        text.lines()
+          .map(|l| l.trim())
+          .filter(|l| !l.is_empty() && !l.starts_with('#'))
+          .map(|l| l.trim_end_matches(['/', '\\']).to_string())
+      );
+    }
+    Ok(self)
+  }
+
+  pub fn is_ignored(&self, name: &str, relative: &str) -> bool {
+    self.ignore.iter().any(
+      |p| wildcard_match(p, name) || wildcard_match(&p.replace('\\', "/"), relative)
+    )
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PluginScanProgress {
+  pub dirs_visited: usize,
+  pub files_found: usize,
+  pub bytes: u64,
+  // 命中索引缓存的插件数
+  pub cached: usize,
+}
+
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum PluginScanEvent {
+  Found(PluginEntry),
+  Skipped(PathBuf),
+  Error(PluginScanError),
+  Progress(PluginScanProgress),
+}
+
+struct PluginScanner {
+  root: PathBuf,
+  options: PluginScanOptions,
+  visited: Mutex<HashSet<PathBuf>>,
+  index: Option<PluginIndex>,
+  // 本次扫描得到的索引记录
+  records: Mutex<BTreeMap<String, PluginIndexRecord>>,
+  limit: Semaphore,
+  dirs_visited: AtomicUsize,
+  files_found: AtomicUsize,
+  bytes: AtomicU64,
+  cached: AtomicUsize,
+}
+
+type EventSender = mpsc::Sender<PluginScanEvent>;
+
+impl PluginScanner {
+  fn progress(&self) -> PluginScanProgress {
+    PluginScanProgress {
+      dirs_visited: self.dirs_visited.load(Ordering::SeqCst),
+      files_found: self.files_found.load(Ordering::SeqCst),
+      bytes: self.bytes.load(Ordering::SeqCst),
+      cached: self.cached.load(Ordering::SeqCst),
+    }
+  }
+
+  async fn error(tx: &EventSender, path: &Path, error: anyhow::Error) {
+    warn!("scan {:?} failed, {}", path, error);
+    let _ = tx.send(PluginScanEvent::Error(PluginScanError {
+      path: path.to_path_buf(),
+      error: error.to_string(),
+    })).await;
+  }
+
+  async fn skip(tx: &EventSender, path: PathBuf) {
+    let _ = tx.send(PluginScanEvent::Skipped(path)).await;
+  }
+
+  fn relative(&self, path: &Path) -> String {
+    path.strip_prefix(&self.root)
+      .unwrap_or(path)
+      .to_string_lossy()
+      .replace('\\', "/")
+  }
+
+  // 符号链接与目录联接只进入一次，避免循环
+  async fn enter(&self, path: &Path) -> anyhow::Result<bool> {
+    let real = fs::canonicalize(path).await?;
+    Ok(self.visited.lock().unwrap().insert(real))
+  }
+
+  fn spawn_dir(self: &Arc<Self>, tx: &EventSender, pb: PathBuf, depth: usize, category: Option<String>) {
+    let (this, tx) = (self.clone(), tx.clone());
+    tokio::spawn(async move {
+      if let Err(e) = this.scan_dir(&tx, &pb, depth, category).await {
+        Self::error(&tx, &pb, e).await;
+      }
+      this.dirs_visited.fetch_add(1, Ordering::SeqCst);
+      let _ = tx.send(PluginScanEvent::Progress(this.progress())).await;
+    });
+  }
+
+  // `manifest_mtime` 为目录列表中清单旁路文件的修改时间
+  fn spawn_file(self: &Arc<Self>, tx: &EventSender, path: PathBuf, meta: Metadata, category: Option<String>, manifest_mtime: Option<u64>) {
+    let (this, tx) = (self.clone(), tx.clone());
+    tokio::spawn(async move {
+      let _permit = this.limit.acquire().await;
+      let size = meta.len();
+      let key = this.relative(&path);
+      let record = this.index.as_ref()
+        .and_then(|index| index.lookup(&key, &meta, manifest_mtime))
+        .cloned();
+      let r = match &record {
+        Some(record) => {
+          this.cached.fetch_add(1, Ordering::SeqCst);
+          PluginEntry::from_parts(path.clone(), meta, record.manifest.clone())
+        }
+        None => PluginEntry::from_metadata(path.clone(), meta).await,
+      };
+      match r {
+        Ok(mut r) => {
+          if let Some(index) = &this.index {
+            let record = match record {
+              Some(record) => Some(record),
+              // 清单有错误时不缓存，下次扫描重新读取并报告
+              None if r.manifest_error.is_none() => Some(index.record_of(&key, &r, manifest_mtime)),
+              None => None,
+            };
+            if let Some(record) = record {
+              this.records.lock().unwrap().insert(key, record);
+            }
+          }
+          r.category_folder = category;
+          // 清单错误不影响插件本身，作为警告报告
+          if let Some(e) = &r.manifest_error {
+            Self::error(&tx, &path, anyhow!("{}", e)).await;
+          }
+          this.files_found.fetch_add(1, Ordering::SeqCst);
+          this.bytes.fetch_add(size, Ordering::SeqCst);
+          let _ = tx.send(PluginScanEvent::Found(r)).await;
+          let _ = tx.send(PluginScanEvent::Progress(this.progress())).await;
+        }
+        Err(e) => Self::error(&tx, &path, e).await,
+      }
+    });
+  }
+
+  async fn scan_dir(self: &Arc<Self>, tx: &EventSender, pb: &Path, depth: usize, category: Option<String>) -> anyhow::Result<()> {
+    let _permit = self.limit.acquire().await?;
+    let mut dir = fs::read_dir(pb).await?;
+    // 列完目录后再处理插件，清单的修改时间直接取自目录列表
+    let mut files = vec![];
+    let mut manifests = HashMap::new();
+    loop {
+      let d = match dir.next_entry().await {
+        Ok(Some(d)) => d,
+        Ok(None) => break,
+        Err(e) => {
+          Self::error(tx, pb, e.into()).await;
+          break;
+        }
+      };
+      let path = d.path();
+      let name = d.file_name().to_string_lossy().to_string();
+
+      let mut meta = match d.metadata().await {
+        Ok(m) => m,
+        Err(e) => {
+          Self::error(tx, &path, e.into()).await;
+          continue;
+        }
+      };
+
+      if path.extension().map(|e| e == *EXT_PLUGIN_MANIFEST).unwrap_or(false) {
+        let m = match meta.file_type().is_symlink() {
+          true => fs::metadata(&path).await.ok(),
+          false => Some(meta.clone()),
+        };
+        if let Some(m) = m.filter(|m| m.is_file()) {
+          manifests.insert(path.clone(), mtime_of(&m));
+        }
+      }
+
+      if self.options.skip_hidden && (name.starts_with('.') || meta.is_hidden() || meta.is_system()) {
+        info!("skip hidden {:?}", path);
+        Self::skip(tx, path).await;
+        continue;
+      }
+
+      if self.options.is_ignored(&name, &self.relative(&path)) {
+        info!("skip ignored {:?}", path);
+        Self::skip(tx, path).await;
+        continue;
+      }
+
+      if meta.file_type().is_symlink() {
+        if !self.options.follow_links {
+          info!("skip link {:?}", path);
+          Self::skip(tx, path).await;
+          continue;
+        }
+        meta = match fs::metadata(&path).await {
+          Ok(m) => m,
+          Err(e) => {
+            Self::error(tx, &path, anyhow!("broken link, {}", e)).await;
+            continue;
+          }
+        };
+      }
+
+      if meta.is_file() {
+        let ext = path.extension()
+          .map(|e| e.to_string_lossy().to_string())
+          .and_then(|e| PluginExtension::new(&e));
+        if ext.is_some() {
+          files.push((path, meta));
+        }
+      } else if meta.is_dir() {
+        if self.options.max_depth.map(|m| depth >= m).unwrap_or(false) {
+          warn!("skip {:?}, reached max depth", path);
+          Self::skip(tx, path).await;
+          continue;
+        }
+
+        match self.enter(&path).await {
+          Ok(true) => {}
+          Ok(false) => {
+            warn!("skip {:?}, already visited", path);
+            Self::skip(tx, path).await;
+            continue;
+          }
+          Err(e) => {
+            Self::error(tx, &path, e).await;
+            continue;
+          }
+        }
+
+        let sub = category.clone().or_else(|| Some(name.clone()));
+        self.spawn_dir(tx, path, depth + 1, sub);
+      }
+    }
+
+    for (path, meta) in files {
+      let manifest_mtime = manifests.get(&PluginManifest::sidecar_path(&path)).copied();
+      self.spawn_file(tx, path, meta, category.clone(), manifest_mtime);
+    }
+
+    Ok(())
+  }
+}
+
+impl PluginEntry {
+  // 事件随发现实时发出，全部扫描结束后通道关闭
+  pub async fn scan_dir_stream(pb: PathBuf, options: &PluginScanOptions) -> anyhow::Result<mpsc::Receiver<PluginScanEvent>> {
+    let (_, rx) = Self::start_scan(pb, options, None).await?;
+    Ok(rx)
+  }
+
+  async fn start_scan(
+    pb: PathBuf,
+    options: &PluginScanOptions,
+    index: Option<PluginIndex>
+  ) -> anyhow::Result<(Arc<PluginScanner>, mpsc::Receiver<PluginScanEvent>)> {
+    info!("scan plugins in {:?}, options = {:?}", pb, options);
+    if !fs::metadata(&pb).await?.is_dir() {
+      return Err(anyhow!("{:?} is not a directory", pb));
+    }
+
+    let options = options.clone().with_ignore_file(&pb).await?;
+    let scanner = Arc::new(PluginScanner {
+      root: pb.clone(),
+      limit: Semaphore::new(options.concurrency.max(1)),
+      options,
+      index,
+      records: Mutex::new(BTreeMap::new()),
+      visited: Mutex::new(HashSet::new()),
+      dirs_visited: AtomicUsize::new(0),
+      files_found: AtomicUsize::new(0),
+      bytes: AtomicU64::new(0),
+      cached: AtomicUsize::new(0),
+    });
+    scanner.enter(&pb).await?;
+
+    let (tx, rx) = mpsc::channel(64);
+    scanner.spawn_dir(&tx, pb, 0, None);
+    Ok((scanner, rx))
+  }
+
+  pub async fn scan_dir_with(pb: PathBuf, options: &PluginScanOptions) -> anyhow::Result<PluginScanReport> {
+    Self::scan_dir_with_index(pb, options, None).await
+  }
+
+  // 扫描结束后 `index` 替换为本次的结果
+  pub(crate) async fn scan_dir_with_index(
+    pb: PathBuf,
+    options: &PluginScanOptions,
+    index: Option<&mut PluginIndex>
+  ) -> anyhow::Result<PluginScanReport> {
+    let (scanner, mut rx) = Self::start_scan(pb, options, index.as_ref().map(|i| (**i).clone())).await?;
+    let mut report = PluginScanReport::default();
+    while let Some(ev) = rx.recv().await {
+      match ev {
+        PluginScanEvent::Found(e) => report.entries.push(e),
+        PluginScanEvent::Skipped(p) => report.skipped.push(p),
+        PluginScanEvent::Error(e) => report.errors.push(e),
+        // 各任务的进度快照可能乱序到达
+        PluginScanEvent::Progress(p) => report.progress = PluginScanProgress {
+          dirs_visited: report.progress.dirs_visited.max(p.dirs_visited),
+          files_found: report.progress.files_found.max(p.files_found),
+          bytes: report.progress.bytes.max(p.bytes),
+          cached: report.progress.cached.max(p.cached),
+        },
+      }
+    }
+
+    if let Some(index) = index {
+      index.records = std::mem::take(&mut *scanner.records.lock().unwrap());
+    }
+
+    // 并发扫描的结果顺序不固定，按路径排序
+    report.entries.sort_by(|a, b| a.path.cmp(&b.path));
+    report.errors.sort_by(|a, b| a.path.cmp(&b.path));
+    report.skipped.sort();
+
+    info!(
+      "scanned, {} plugins, {} errors, {} skipped",
+      report.entries.len(),
+      report.errors.len(),
+      report.skipped.len()
+    );
+    Ok(report)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use tokio::fs;
+  use crate::testing::touch;
+
+  use super::{PluginScanEvent, PluginScanOptions};
+  use crate::found::PluginEntry;
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    touch(&root.join("README"), "").await?;
+    touch(&root.join("Chrome_90.0_Cno.7z"), "chrome").await?;
+    touch(&root.join(".Hidden_1.0_Cno.7z"), "").await?;
+    touch(&root.join("Broken_1.0_Cno.7z"), "").await?;
+    touch(&root.join("Broken_1.0_Cno.json"), "{ depends").await?;
+    touch(&root.join("Old").join("IE_8.0_Cno.7z"), "").await?;
+    touch(&root.join("Tools").join("Tool_BETA_Cno.7z"), "").await?;
+    touch(&root.join("Tools").join("Deep").join("Deeper").join("Far_1.0_Cno.7z"), "").await?;
+    touch(&root.join(".edgelessignore"), "# comment\nOld/\n*_beta_*\n").await?;
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(root, root.join("Tools").join("Loop"))?;
+
+    let options = PluginScanOptions {
+      max_depth: Some(2),
+      ..Default::default()
+    };
+    let report = PluginEntry::scan_dir_with(root.to_path_buf(), &options).await?;
+
+    let mut names = report.entries.iter()
+      .map(|e| e.name().unwrap_or("").to_string())
+      .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["Broken", "Chrome"]);
+
+    // 清单无法解析时插件照常加入，错误单独报告
+    assert_eq!(report.errors.len(), 1);
+    assert!(report.errors[0].path.ends_with("Broken_1.0_Cno.7z"));
+    let broken = report.entries.iter().find(|e| e.name() == Some("Broken")).unwrap();
+    assert!(broken.manifest.is_none());
+    assert!(broken.manifest_error.is_some());
+
+    // Root, Tools, Tools/Deep
+    assert_eq!(report.progress.dirs_visited, 3);
+    assert_eq!(report.progress.files_found, 2);
+    assert_eq!(report.progress.bytes, 6);
+
+    let skipped = |n: &str| report.skipped.iter().any(|p| p.ends_with(n));
+    assert!(skipped(".Hidden_1.0_Cno.7z"));
+    assert!(skipped("Old"));
+    assert!(skipped("Tool_BETA_Cno.7z"));
+    assert!(skipped("Deeper"));
+    #[cfg(unix)]
+    assert!(skipped("Loop"));
+
+    let report = PluginEntry::scan_dir_with(root.to_path_buf(), &PluginScanOptions::default()).await?;
+    assert!(report.entries.iter().any(|e| e.name() == Some("Far")));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn stream() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    for i in 0..32 {
+      touch(&dir.path().join(format!("Cate{}", i % 4)).join(format!("P{}_1.0_Cno.7z", i)), "").await?;
+    }
+
+    let options = PluginScanOptions {
+      concurrency: 2,
+      ..Default::default()
+    };
+    let mut rx = PluginEntry::scan_dir_stream(dir.path().to_path_buf(), &options).await?;
+    let (mut found, mut progress) = (0, 0);
+    while let Some(ev) = rx.recv().await {
+      match ev {
+        PluginScanEvent::Found(e) => {
+          assert!(e.category_folder.is_some());
+          found += 1;
+        }
+        PluginScanEvent::Progress(_) => progress += 1,
+        ev => panic!("unexpected {:?}", ev),
+      }
+    }
+    assert_eq!(found, 32);
+    assert_eq!(progress, 32 + 5);
+
+    assert!(PluginEntry::scan_dir_stream(dir.path().join("None"), &options).await.is_err());
+
+    Ok(())
+  }
+}
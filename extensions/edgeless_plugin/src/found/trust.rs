@@ -0,0 +1,241 @@
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+use ed25519_dalek::{Signature, VerifyingKey, Verifier};
+use edgeless_core::found::ProfileEntry;
+use edgeless_core::options::PluginTrustPolicy;
+use edgeless_core::options::define::{
+  EXT_PLUGIN_SIGNATURE,
+  EXT_PLUGIN_TRUSTED_KEY,
+  PATH_OPTION_PLUGIN_TRUSTED_KEYS,
+};
+use super::{PluginEntry, PluginEntries};
+
+use anyhow::anyhow;
+use log::{info, warn};
+use tokio::fs;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginTrustStatus {
+  Unchecked,
+  // 没有签名文件
+  Unsigned,
+  // 签名有效，值为签发者的密钥名
+  Trusted(String),
+  // 签名与任何受信任的密钥都不匹配
+  Untrusted,
+  Invalid(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PluginTrustStore {
+  pub keys: Vec<(String, VerifyingKey)>,
+}
+
+pub fn signature_path(plugin: &Path) -> PathBuf {
+  plugin.with_extension(*EXT_PLUGIN_SIGNATURE)
+}
+
+fn parse_hex<const N: usize>(s: &str) -> anyhow::Result<[u8; N]> {
+  let s = s.split_whitespace().next().unwrap_or("");
+  let v = hex::decode(s)?;
+  v.as_slice().try_into().map_err(|_| anyhow!("expect {} bytes, got {}", N, v.len()))
+}
+
+// 签名文件为十六进制文本或 64 字节的原始签名
+pub fn parse_signature(bytes: &[u8]) -> anyhow::Result<Signature> {
+  if bytes.len() == 64 {
+    if let Ok(b) = bytes.try_into() {
+      return Ok(Signature::from_bytes(&b));
+    }
+  }
+  let text = std::str::from_utf8(bytes)?;
+  Ok(Signature::from_bytes(&parse_hex::<64>(text)?))
+}
+
+impl PluginTrustStore {
+  pub fn add(&mut self, name: &str, key_hex: &str) -> anyhow::Result<()> {
+    let key = VerifyingKey::from_bytes(&parse_hex::<32>(key_hex)?)?;
+    self.keys.push((name.to_string(), key));
+    Ok(())
+  }
+
+  // 目录下每个 `.pub` 文件为一个十六进制公钥，无效的密钥会被忽略
+  pub async fn from_dir(dir: &Path) -> anyhow::Result<Self> {
+    let mut store = Self::default();
+    if !(dir.exists() && dir.is_dir()) {
+      info!("trusted keys dir {:?} not found", dir);
+      return Ok(store);
+    }
+
+    let mut iter = fs::read_dir(dir).await?;
+    while let Some(f) = iter.next_entry().await? {
+      let path = f.path();
+      if path.extension().and_then(|e| e.to_str()) != Some(*EXT_PLUGIN_TRUSTED_KEY) {
+        continue;
+      }
+      let name = path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+      let text = fs::read_to_string(&path).await?;
+      match store.add(&name, &text) {
+        Ok(_) => info!("loaded trusted key {:?}", name),
+        Err(e) => warn!("invalid trusted key {:?}, {}", path, e),
+      }
+    }
+    store.keys.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(store)
+  }
+
+  pub async fn from_profile(entry: &ProfileEntry) -> anyhow::Result<Self> {
+    Self::from_dir(&entry.path.join(PATH_OPTION_PLUGIN_TRUSTED_KEYS.as_path())).await
+  }
+
+  // 签名的内容为插件包 SHA-256 的 32 字节摘要
+  pub fn verify(&self, sha256_hex: &str, signature: &Signature) -> anyhow::Result<Option<&str>> {
+    let digest = parse_hex::<32>(sha256_hex)?;
+    Ok(
+      self.keys.iter()
+        .find(|(_, k)| k.verify(&digest, signature).is_ok())
+        .map(|(n, _)| n.as_str())
+    )
+  }
+}
+
+impl PluginEntry {
+  // 总是重新计算哈希，不使用按大小与修改时间缓存的值，否则替换后的插件包可能被当作可信
+  pub async fn check_trust(&mut self, store: &PluginTrustStore) -> &PluginTrustStatus {
+    self.trust = match self.trust_status(store).await {
+      Ok(s) => s,
+      Err(e) => PluginTrustStatus::Invalid(e.to_string()),
+    };
+    info!("plugin {:?} trust status: {:?}", self.path, self.trust);
+    &self.trust
+  }
+
+  async fn trust_status(&self, store: &PluginTrustStore) -> anyhow::Result<PluginTrustStatus> {
+    let pb = signature_path(&self.path);
+    if !(pb.exists() && pb.is_file()) {
+      return Ok(PluginTrustStatus::Unsigned);
+    }
+
+    let signature = parse_signature(&fs::read(&pb).await?)?;
+    let hash = self.sha256().await?;
+
+    Ok(match store.verify(&hash, &signature)? {
+      Some(name) => PluginTrustStatus::Trusted(name.to_string()),
+      None => PluginTrustStatus::Untrusted,
+    })
+  }
+
+  /*
+   * 加载前重新计算哈希并校验签名，不沿用扫描时的 `trust`
+   * 扫描与加载之间插件包可能被替换，`enforce` 时未受信任的插件返回错误
+   */
+  pub async fn verify_trust(&self, store: &PluginTrustStore, policy: PluginTrustPolicy) -> anyhow::Result<()> {
+    if policy == PluginTrustPolicy::Off {
+      return Ok(());
+    }
+    let status = match self.trust_status(store).await {
+      Ok(s) => s,
+      Err(e) => PluginTrustStatus::Invalid(e.to_string()),
+    };
+    match status {
+      PluginTrustStatus::Trusted(_) => Ok(()),
+      _ if policy == PluginTrustPolicy::Enforce => Err(anyhow!("plugin {:?} is not trusted, {:?}", self.path, status)),
+      _ => {
+        warn!("plugin {:?} is not trusted, {:?}", self.path, status);
+        Ok(())
+      }
+    }
+  }
+
+  pub fn is_allowed(&self, policy: PluginTrustPolicy) -> bool {
+    match policy {
+      PluginTrustPolicy::Off => true,
+      PluginTrustPolicy::Warn => {
+        if !matches!(self.trust, PluginTrustStatus::Trusted(_)) {
+          warn!("plugin {:?} is not trusted, {:?}", self.path, self.trust);
+        }
+        true
+      }
+      PluginTrustPolicy::Enforce => matches!(self.trust, PluginTrustStatus::Trusted(_)),
+    }
+  }
+}
+
+impl PluginEntries {
+  pub async fn check_trust(&mut self, store: &PluginTrustStore) {
+    for e in self.0.iter_mut() {
+      e.check_trust(store).await;
+    }
+  }
+
+  pub fn allowed(&self, policy: PluginTrustPolicy) -> Vec<&PluginEntry> {
+    self.0.iter()
+      .filter(|e| e.is_allowed(policy))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use ed25519_dalek::{Signer, SigningKey};
+  use edgeless_core::options::PluginTrustPolicy;
+  use tokio::fs;
+  use crate::testing::touch;
+
+  use super::{PluginTrustStatus, PluginTrustStore};
+  use crate::found::PluginEntry;
+
+  async fn sign(key: &SigningKey, plugin: &PluginEntry) -> anyhow::Result<String> {
+    let digest = hex::decode(plugin.sha256().await?)?;
+    Ok(hex::encode(key.sign(&digest).to_bytes()))
+  }
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let ours = SigningKey::from_bytes(&[7u8; 32]);
+    let theirs = SigningKey::from_bytes(&[9u8; 32]);
+
+    let keys = dir.path().join("Keys");
+    touch(&keys.join("pipeline.pub"), hex::encode(ours.verifying_key().to_bytes()).as_bytes()).await?;
+    touch(&keys.join("broken.pub"), b"xyz").await?;
+    touch(&keys.join("README.txt"), b"").await?;
+    let store = PluginTrustStore::from_dir(&keys).await?;
+    assert_eq!(store.keys.len(), 1);
+
+    let res = dir.path().join("Resource");
+    for n in ["Signed", "Foreign", "Unsigned", "Broken"] {
+      touch(&res.join(format!("{}_1.0_Cno.7z", n)), n.as_bytes()).await?;
+    }
+    let mut signed = PluginEntry::new(res.join("Signed_1.0_Cno.7z")).await?;
+    let mut foreign = PluginEntry::new(res.join("Foreign_1.0_Cno.7z")).await?;
+    let mut unsigned = PluginEntry::new(res.join("Unsigned_1.0_Cno.7z")).await?;
+    let mut broken = PluginEntry::new(res.join("Broken_1.0_Cno.7z")).await?;
+
+    touch(&res.join("Signed_1.0_Cno.sig"), sign(&ours, &signed).await?.as_bytes()).await?;
+    touch(&res.join("Foreign_1.0_Cno.sig"), sign(&theirs, &foreign).await?.as_bytes()).await?;
+    touch(&res.join("Broken_1.0_Cno.sig"), b"00").await?;
+
+    assert_eq!(signed.trust, PluginTrustStatus::Unchecked);
+    assert!(!signed.is_allowed(PluginTrustPolicy::Enforce));
+
+    assert_eq!(signed.check_trust(&store).await, &PluginTrustStatus::Trusted("pipeline".into()));
+    assert_eq!(foreign.check_trust(&store).await, &PluginTrustStatus::Untrusted);
+    assert_eq!(unsigned.check_trust(&store).await, &PluginTrustStatus::Unsigned);
+    assert!(matches!(broken.check_trust(&store).await, PluginTrustStatus::Invalid(_)));
+
+    assert!(signed.is_allowed(PluginTrustPolicy::Enforce));
+    assert!(!foreign.is_allowed(PluginTrustPolicy::Enforce));
+    assert!(foreign.is_allowed(PluginTrustPolicy::Warn));
+    assert!(broken.is_allowed(PluginTrustPolicy::Off));
+
+    // 插件包被修改后签名失效
+    touch(&res.join("Signed_1.0_Cno.7z"), b"tampered").await?;
+    assert_eq!(signed.check_trust(&store).await, &PluginTrustStatus::Untrusted);
+
+    Ok(())
+  }
+}
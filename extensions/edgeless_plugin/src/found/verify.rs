@@ -0,0 +1,284 @@
+use std::path::{Path, PathBuf};
+use bindings_7z::{SevenZip, SevenZipError};
+use edgeless_core::options::define::EXT_PLUGIN_CHECKSUM;
+use sha2::{Digest, Sha256};
+use super::cache::PluginIndex;
+use super::{PluginEntry, PluginEntries};
+
+use anyhow::anyhow;
+use log::{info, warn, error};
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginVerifyVerdict {
+  // 校验值一致且压缩包测试通过
+  Ok,
+  // 校验值一致，未执行压缩包测试
+  HashOnly,
+  // 没有可对比的校验值，压缩包测试通过或未执行
+  Unverified,
+  Mismatch { expected: String, actual: String },
+  Corrupted(String),
+  Error(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginVerifyReport {
+  pub path: PathBuf,
+  pub sha256: Option<String>,
+  pub expected: Option<String>,
+  // None 为未执行压缩包测试
+  pub archive_ok: Option<bool>,
+  pub verdict: PluginVerifyVerdict,
+}
+
+impl PluginVerifyReport {
+  pub fn is_ok(&self) -> bool {
+    matches!(
+      self.verdict,
+      PluginVerifyVerdict::Ok | PluginVerifyVerdict::HashOnly | PluginVerifyVerdict::Unverified
+    )
+  }
+}
+
+pub fn checksum_path(plugin: &Path) -> PathBuf {
+  plugin.with_extension(*EXT_PLUGIN_CHECKSUM)
+}
+
+// 兼容 `sha256sum` 的输出格式，取第一列
+pub fn parse_checksum(s: &str) -> Option<String> {
+  s.split_whitespace()
+    .next()
+    .map(|h| h.trim_start_matches('\u{feff}').to_lowercase())
+    .filter(|h| h.len() == 64 && h.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+pub async fn sha256_file(path: &Path) -> anyhow::Result<String> {
+  let mut f = fs::File::open(path).await?;
+  let mut hasher = Sha256::new();
+  let mut buf = vec![0u8; 64 * 1024];
+  loop {
+    let n = f.read(&mut buf).await?;
+    if n == 0 {
+      break;
+    }
+    hasher.update(&buf[..n]);
+  }
+  Ok(format!("{:x}", hasher.finalize()))
+}
+
+impl PluginEntry {
+  // 校验值优先取旁路文件，其次为清单中的 sha256
+  pub async fn expected_sha256(&self) -> anyhow::Result<Option<String>> {
+    let pb = checksum_path(&self.path);
+    if pb.exists() && pb.is_file() {
+      let text = fs::read_to_string(&pb).await?;
+      return parse_checksum(&text)
+        .map(Some)
+        .ok_or(anyhow!("invalid checksum file {:?}", pb));
+    }
+
+    Ok(
+      self.manifest.as_ref()
+        .and_then(|m| m.sha256.as_deref())
+        .map(|h| h.trim().to_lowercase())
+    )
+  }
+
+  pub async fn sha256(&self) -> anyhow::Result<String> {
+    info!("compute sha256 of {:?}", self.path);
+    sha256_file(&self.path).await
+  }
+
+  // 压缩包损坏时返回 `Ok(Err(原因))`，无法运行 7z 时返回 `Err`
+  pub async fn test_archive(&self, zip: &SevenZip) -> anyhow::Result<Result<(), String>> {
+    match zip.test(&self.path).run().await {
+      Ok(_) => Ok(Ok(())),
+      Err(SevenZipError::Spawn(e)) => Err(anyhow!("failed to test {:?}, {}", self.path, e)),
+      Err(e) => Ok(Err(e.to_string())),
+    }
+  }
+
+  pub async fn verify(&self, zip: Option<&SevenZip>) -> PluginVerifyReport {
+    self.verify_with_hash(zip, None).await
+  }
+
+  // 已知的哈希（如索引缓存中的）可跳过重新计算
+  pub async fn verify_with_hash(&self, zip: Option<&SevenZip>, known: Option<String>) -> PluginVerifyReport {
+    info!("verify plugin {:?}", self.path);
+    let mut report = PluginVerifyReport {
+      path: self.path.clone(),
+      sha256: None,
+      expected: None,
+      archive_ok: None,
+      verdict: PluginVerifyVerdict::Unverified,
+    };
+
+    match self.expected_sha256().await {
+      Ok(h) => report.expected = h,
+      Err(e) => {
+        report.verdict = PluginVerifyVerdict::Error(e.to_string());
+        return report;
+      }
+    }
+
+    let actual = match known {
+      Some(h) => h,
+      None => match self.sha256().await {
+        Ok(h) => h,
+        Err(e) => {
+          report.verdict = PluginVerifyVerdict::Error(e.to_string());
+          return report;
+        }
+      },
+    };
+    report.sha256 = Some(actual.clone());
+
+    if let Some(expected) = &report.expected {
+      if expected != &actual {
+        warn!("checksum mismatch for {:?}, expected {}, actual {}", self.path, expected, actual);
+        report.verdict = PluginVerifyVerdict::Mismatch {
+          expected: expected.clone(),
+          actual,
+        };
+        return report;
+      }
+    }
+
+    if let Some(zip) = zip {
+      match self.test_archive(zip).await {
+        Ok(Ok(())) => report.archive_ok = Some(true),
+        Ok(Err(msg)) => {
+          error!("plugin {:?} is corrupted, {}", self.path, msg);
+          report.archive_ok = Some(false);
+          report.verdict = PluginVerifyVerdict::Corrupted(msg);
+          return report;
+        }
+        Err(e) => {
+          report.verdict = PluginVerifyVerdict::Error(e.to_string());
+          return report;
+        }
+      }
+    }
+
+    if report.expected.is_some() {
+      report.verdict = match report.archive_ok {
+        Some(true) => PluginVerifyVerdict::Ok,
+        _ => PluginVerifyVerdict::HashOnly,
+      };
+    }
+    info!("verified, {:?}", report.verdict);
+    report
+  }
+}
+
+impl PluginEntries {
+  pub async fn verify_all(&self, zip: Option<&SevenZip>) -> Vec<PluginVerifyReport> {
+    let mut r = vec![];
+    for e in &self.0 {
+      r.push(e.verify(zip).await);
+    }
+    r
+  }
+
+  // 复用并回写索引缓存中的哈希
+  pub async fn verify_indexed(&self, zip: Option<&SevenZip>, root: &Path, index: &mut PluginIndex) -> Vec<PluginVerifyReport> {
+    let mut r = vec![];
+    for e in &self.0 {
+      let known = index.sha256(root, e).map(|h| h.to_string());
+      let report = e.verify_with_hash(zip, known).await;
+      if let Some(h) = &report.sha256 {
+        index.set_sha256(root, e, h.clone());
+      }
+      r.push(report);
+    }
+    r
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use tokio::fs;
+  use crate::testing::touch;
+  #[cfg(unix)]
+  use crate::testing::fake_seven_zip;
+
+  use super::{parse_checksum, PluginVerifyVerdict};
+  use crate::found::cache::PluginIndex;
+  use crate::found::scan::PluginScanOptions;
+  use crate::found::PluginEntry;
+
+  // sha256("chrome")
+  const CHROME: &str = "9390ef32addf32bfdea786bc1e20679592498068bcbfcd357ea10ab64ea35e47";
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    assert_eq!(parse_checksum(&format!("{}  Chrome.7z\n", CHROME.to_uppercase())), Some(CHROME.to_string()));
+    assert_eq!(parse_checksum("abc"), None);
+
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    touch(&root.join("Chrome_90.0_Cno.7z"), "chrome").await?;
+    let chrome = PluginEntry::new(root.join("Chrome_90.0_Cno.7z")).await?;
+    let actual = chrome.sha256().await?;
+    assert_eq!(actual, CHROME);
+
+    let report = chrome.verify(None).await;
+    assert_eq!(report.verdict, PluginVerifyVerdict::Unverified);
+    assert!(report.is_ok());
+
+    touch(&root.join("Chrome_90.0_Cno.sha256"), &format!("{}  Chrome_90.0_Cno.7z", actual)).await?;
+    let report = chrome.verify(None).await;
+    assert_eq!(report.verdict, PluginVerifyVerdict::HashOnly);
+    assert!(report.is_ok());
+
+    touch(&root.join("Chrome_90.0_Cno.sha256"), &"0".repeat(64)).await?;
+    let report = chrome.verify(None).await;
+    assert!(matches!(report.verdict, PluginVerifyVerdict::Mismatch { .. }));
+    assert!(!report.is_ok());
+
+    fs::remove_file(root.join("Chrome_90.0_Cno.sha256")).await?;
+    touch(&root.join("Chrome_90.0_Cno.json"), &format!(r#"{{ "sha256": "{}" }}"#, actual)).await?;
+    let chrome = PluginEntry::new(root.join("Chrome_90.0_Cno.7z")).await?;
+    assert_eq!(chrome.verify(None).await.verdict, PluginVerifyVerdict::HashOnly);
+
+    let mut index = PluginIndex::default();
+    let entries = PluginEntry::scan_dir_indexed(root.to_path_buf(), &PluginScanOptions::default(), &mut index).await?.entries;
+    let reports = entries.verify_indexed(None, root, &mut index).await;
+    assert_eq!(reports.len(), 1);
+    assert_eq!(index.sha256(root, &entries[0]), Some(actual.as_str()));
+
+    Ok(())
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn archive_test() -> anyhow::Result<()> {
+    use bindings_7z::SevenZip;
+
+    let dir = tempfile::tempdir()?;
+    let exe = fake_seven_zip(dir.path()).await?;
+    let zip = SevenZip::new(exe)?;
+
+    touch(&dir.path().join("Good_1.0_Cno.7z"), "good").await?;
+    touch(&dir.path().join("Bad_1.0_Cno.7z"), "broken").await?;
+
+    let good = PluginEntry::new(dir.path().join("Good_1.0_Cno.7z")).await?;
+    let report = good.verify(Some(&zip)).await;
+    assert_eq!(report.archive_ok, Some(true));
+    assert_eq!(report.verdict, PluginVerifyVerdict::Unverified);
+
+    // 校验值一致且测试通过才是 Ok
+    touch(&dir.path().join("Good_1.0_Cno.sha256"), super::sha256_file(&good.path).await?).await?;
+    assert_eq!(good.verify(Some(&zip)).await.verdict, PluginVerifyVerdict::Ok);
+
+    let bad = PluginEntry::new(dir.path().join("Bad_1.0_Cno.7z")).await?;
+    let report = bad.verify(Some(&zip)).await;
+    assert_eq!(report.archive_ok, Some(false));
+    assert_eq!(report.verdict, PluginVerifyVerdict::Corrupted("data error".into()));
+
+    Ok(())
+  }
+}
@@ -2,6 +2,9 @@ pub mod found;
 pub mod loader;
 pub mod pnp;
 
+#[cfg(test)]
+mod testing;
+
 #[cfg(test)]
 mod tests {
     #[test]
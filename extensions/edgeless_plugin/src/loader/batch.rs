@@ -0,0 +1,382 @@
+use std::path::Path;
+use bindings_pecmd::command::PecmdCommand;
+
+use anyhow::anyhow;
+use encoding_rs::Encoding;
+use tokio::fs;
+
+/*
+ * 生成 `.cmd` 脚本
+ * 文本参数一律按字面量处理：引号内把 `%` 写成 `%%`，引号外再用 `^` 转义 `&|<>()^`
+ * 需要引用变量时用 `PluginBatchArg::var`，引用脚本所在目录用 `PluginBatchArg::script_dir`
+ */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginBatchPart {
+  Text(String),
+  // `%name%`
+  Var(String),
+  // `%~dp0`，以 `\` 结尾
+  ScriptDir,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PluginBatchArg {
+  pub parts: Vec<PluginBatchPart>,
+}
+
+impl PluginBatchArg {
+  pub fn text(text: &str) -> Self {
+    Self::default().push_text(text)
+  }
+
+  pub fn var(name: &str) -> Self {
+    Self::default().push_var(name)
+  }
+
+  pub fn script_dir() -> Self {
+    Self {
+      parts: vec![PluginBatchPart::ScriptDir],
+    }
+  }
+
+  pub fn push_text(mut self, text: &str) -> Self {
+    self.parts.push(PluginBatchPart::Text(text.to_string()));
+    self
+  }
+
+  pub fn push_var(mut self, name: &str) -> Self {
+    self.parts.push(PluginBatchPart::Var(name.to_string()));
+    self
+  }
+
+  // 只含安全字符的纯文本不加引号
+  fn is_plain(&self) -> bool {
+    self.parts.iter().all(|p| match p {
+      PluginBatchPart::Text(t) => !t.is_empty() && t.chars().all(|c| c.is_ascii_alphanumeric() || "_-.:\\/+@#~".contains(c)),
+      _ => false,
+    }) && !self.parts.is_empty()
+  }
+
+  fn check(&self) -> anyhow::Result<()> {
+    for p in &self.parts {
+      let s = match p {
+        PluginBatchPart::Text(t) => t.as_str(),
+        PluginBatchPart::Var(v) if v.is_empty() || v.contains(['%', '=']) => return Err(anyhow!("invalid batch variable {:?}", v)),
+        PluginBatchPart::Var(v) => v.as_str(),
+        PluginBatchPart::ScriptDir => "",
+      };
+      if s.contains(['"', '\r', '\n']) {
+        return Err(anyhow!("invalid batch argument {:?}", s));
+      }
+    }
+    Ok(())
+  }
+
+  // 不加引号的形式，用于 `set "name=value"` 之类已在引号中的位置
+  fn inner(&self) -> String {
+    self.parts.iter()
+      .map(|p| match p {
+        PluginBatchPart::Text(t) => t.replace('%', "%%"),
+        PluginBatchPart::Var(v) => format!("%{}%", v),
+        PluginBatchPart::ScriptDir => "%~dp0".to_string(),
+      })
+      .collect()
+  }
+
+  pub fn render(&self) -> anyhow::Result<String> {
+    self.check()?;
+    match self.is_plain() {
+      true => Ok(self.inner()),
+      false => Ok(format!("\"{}\"", self.inner())),
+    }
+  }
+}
+
+impl From<&str> for PluginBatchArg {
+  fn from(s: &str) -> Self {
+    Self::text(s)
+  }
+}
+
+impl From<String> for PluginBatchArg {
+  fn from(s: String) -> Self {
+    Self::text(&s)
+  }
+}
+
+impl From<&Path> for PluginBatchArg {
+  fn from(p: &Path) -> Self {
+    Self::text(&p.to_string_lossy())
+  }
+}
+
+// 引号外的特殊字符加 `^`，引号内原样；`%` 一律写成 `%%`
+pub fn escape(line: &str) -> String {
+  let mut out = String::with_capacity(line.len());
+  let mut quoted = false;
+  for c in line.chars() {
+    match c {
+      '"' => quoted = !quoted,
+      '%' => out.push('%'),
+      '^' | '&' | '|' | '<' | '>' | '(' | ')' if !quoted => out.push('^'),
+      _ => {}
+    }
+    out.push(c);
+  }
+  out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PluginBatchCodePage {
+  // `chcp 65001`，按 UTF-8 写出
+  #[default]
+  Utf8,
+  // `chcp <n>`，按该代码页编码写出，无法表示的字符报错
+  Oem(u16),
+  // 不切换代码页，只能写 ASCII
+  System,
+}
+
+impl PluginBatchCodePage {
+  // 代码页对应的编码，System 未知按 ASCII 处理
+  fn encoding(&self) -> anyhow::Result<Option<&'static Encoding>> {
+    let encoding = match self {
+      PluginBatchCodePage::Utf8 | PluginBatchCodePage::Oem(65001) => encoding_rs::UTF_8,
+      PluginBatchCodePage::System => return Ok(None),
+      PluginBatchCodePage::Oem(866) => encoding_rs::IBM866,
+      PluginBatchCodePage::Oem(874) => encoding_rs::WINDOWS_874,
+      PluginBatchCodePage::Oem(932) => encoding_rs::SHIFT_JIS,
+      PluginBatchCodePage::Oem(936) => encoding_rs::GBK,
+      PluginBatchCodePage::Oem(949) => encoding_rs::EUC_KR,
+      PluginBatchCodePage::Oem(950) => encoding_rs::BIG5,
+      PluginBatchCodePage::Oem(54936) => encoding_rs::GB18030,
+      PluginBatchCodePage::Oem(cp @ 1250..=1258) => Encoding::for_label(format!("windows-{}", cp).as_bytes()).unwrap(),
+      PluginBatchCodePage::Oem(cp) => return Err(anyhow!("unsupported batch code page {}", cp)),
+    };
+    Ok(Some(encoding))
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PluginBatchScript {
+  code_page: PluginBatchCodePage,
+  lines: Vec<String>,
+  // 第一个错误在 `render` 时返回
+  error: Option<String>,
+}
+
+impl PluginBatchScript {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn code_page(mut self, code_page: PluginBatchCodePage) -> Self {
+    self.code_page = code_page;
+    self
+  }
+
+  fn line(mut self, line: anyhow::Result<String>) -> Self {
+    match line {
+      Ok(l) => self.lines.push(l),
+      Err(e) => {
+        self.error.get_or_insert(e.to_string());
+      }
+    }
+    self
+  }
+
+  fn args(args: &[PluginBatchArg]) -> anyhow::Result<String> {
+    Ok(args.iter()
+      .map(|a| a.render())
+      .collect::<anyhow::Result<Vec<_>>>()?
+      .join(" "))
+  }
+
+  // 原样写入，调用方负责转义
+  pub fn raw(self, line: &str) -> Self {
+    let line = match line.contains(['\r', '\n']) {
+      true => Err(anyhow!("invalid batch line {:?}", line)),
+      false => Ok(line.to_string()),
+    };
+    self.line(line)
+  }
+
+  pub fn comment(self, text: &str) -> Self {
+    text.lines().fold(self, |s, l| s.line(Ok(format!("rem {}", escape(l)))))
+  }
+
+  pub fn echo(self, text: &str) -> Self {
+    let line = match text.contains(['\r', '\n']) {
+      true => Err(anyhow!("invalid echo text {:?}", text)),
+      false => Ok(format!("echo.{}", escape(text))),
+    };
+    self.line(line)
+  }
+
+  pub fn set<V: Into<PluginBatchArg>>(self, name: &str, value: V) -> Self {
+    let value = value.into();
+    let line = PluginBatchArg::var(name).check()
+      .and_then(|_| value.check())
+      .map(|_| format!("set \"{}={}\"", name, value.inner()));
+    self.line(line)
+  }
+
+  pub fn run<P: Into<PluginBatchArg>>(self, program: P, args: &[PluginBatchArg]) -> Self {
+    let program = program.into();
+    let line = program.render().and_then(|p| {
+      let args = Self::args(args)?;
+      Ok(match args.is_empty() {
+        true => p,
+        false => format!("{} {}", p, args),
+      })
+    });
+    self.line(line)
+  }
+
+  pub fn mkdir<P: Into<PluginBatchArg>>(self, path: P) -> Self {
+    let line = path.into().render().map(|p| format!("if not exist {} mkdir {}", p, p));
+    self.line(line)
+  }
+
+  pub fn copy<F: Into<PluginBatchArg>, T: Into<PluginBatchArg>>(self, from: F, to: T) -> Self {
+    let line = Self::args(&[from.into(), to.into()]).map(|a| format!("copy /y {} >nul", a));
+    self.line(line)
+  }
+
+  pub fn delete<P: Into<PluginBatchArg>>(self, path: P) -> Self {
+    let line = path.into().render().map(|p| format!("if exist {} del /f /q {}", p, p));
+    self.line(line)
+  }
+
+  pub fn rmdir<P: Into<PluginBatchArg>>(self, path: P) -> Self {
+    let line = path.into().render().map(|p| format!("if exist {} rmdir /s /q {}", p, p));
+    self.line(line)
+  }
+
+  /*
+   * 用 PECMD 执行一条命令，如创建快捷方式
+   * 不用 `call`，它会把 `%` 再展开一次并加倍 `^`
+   */
+  pub fn pecmd<P: Into<PluginBatchArg>>(self, pecmd: P, command: &PecmdCommand) -> Self {
+    let line = pecmd.into().render().and_then(|exe| {
+      let mut line = format!("{} {}", exe, command.name());
+      for arg in command.argv()?.iter().skip(1) {
+        line.push(' ');
+        match command {
+          PecmdCommand::Load(_) => line.push_str(&PluginBatchArg::text(arg).render()?),
+          _ => line.push_str(&escape(arg)),
+        }
+      }
+      Ok(line)
+    });
+    self.line(line)
+  }
+
+  pub fn exit(self, code: i32) -> Self {
+    self.line(Ok(format!("exit /b {}", code)))
+  }
+
+  // 以 CRLF 换行，内容相同时输出相同；写入文件时的字节见 `encode`
+  pub fn render(&self) -> anyhow::Result<String> {
+    if let Some(e) = &self.error {
+      return Err(anyhow!("{}", e));
+    }
+    let mut out = String::from("@echo off\r\n");
+    match self.code_page {
+      PluginBatchCodePage::Utf8 => out.push_str("chcp 65001 >nul\r\n"),
+      PluginBatchCodePage::Oem(cp) => out.push_str(&format!("chcp {} >nul\r\n", cp)),
+      PluginBatchCodePage::System => {}
+    }
+    for l in &self.lines {
+      out.push_str(l);
+      out.push_str("\r\n");
+    }
+    Ok(out)
+  }
+
+  // 按代码页编码后的脚本内容
+  pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+    let text = self.render()?;
+    let encoding = match self.code_page.encoding()? {
+      Some(e) => e,
+      None if text.is_ascii() => return Ok(text.into_bytes()),
+      None => return Err(anyhow!("non-ASCII text in a batch script needs an explicit code page")),
+    };
+    let (bytes, _, unmappable) = encoding.encode(&text);
+    if unmappable {
+      let c = text.chars()
+        .find(|c| encoding.encode(c.encode_utf8(&mut [0; 4])).2)
+        .unwrap_or_default();
+      return Err(anyhow!("{:?} can not be represented in batch code page {:?}", c, self.code_page));
+    }
+    Ok(bytes.into_owned())
+  }
+
+  pub async fn write(&self, path: &Path) -> anyhow::Result<()> {
+    fs::write(path, self.encode()?).await?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::{Path, PathBuf};
+  use bindings_pecmd::command::{PecmdCommand, PecmdLink};
+
+  use super::{escape, PluginBatchArg, PluginBatchCodePage, PluginBatchScript};
+
+  #[test]
+  fn it_works() -> anyhow::Result<()> {
+    assert_eq!(escape("a & b (100%) \"c & d\""), "a ^& b ^(100%%^) \"c & d\"");
+    assert_eq!(PluginBatchArg::text("chrome.exe").render()?, "chrome.exe");
+    assert_eq!(PluginBatchArg::text("a&b").render()?, "\"a&b\"");
+    assert_eq!(PluginBatchArg::var("ProgramFiles").push_text("\\Edgeless").render()?, "\"%ProgramFiles%\\Edgeless\"");
+    assert!(PluginBatchArg::text("a\"b").render().is_err());
+
+    let dir = PluginBatchArg::var("ProgramFiles").push_text("\\Edgeless\\Chrome");
+    let link = PecmdLink::new("%Desktop%\\Chrome", "%ProgramFiles%\\Edgeless\\Chrome\\chrome.exe").args("--a,--b");
+    let script = PluginBatchScript::new()
+      .comment("Chrome (setup)")
+      .set("APP", "100% & done")
+      .mkdir(dir.clone())
+      .copy(PluginBatchArg::script_dir().push_text("a.ini"), dir.clone())
+      .run(Path::new("C:\\Program Files\\x.exe"), &["/S".into(), PluginBatchArg::var("APP")])
+      .run("reg", &["import".into(), "a b.reg".into()])
+      .pecmd(Path::new("X:\\bin\\pecmd.exe"), &PecmdCommand::Link(link))
+      .pecmd(Path::new("X:\\bin\\pecmd.exe"), &PecmdCommand::Load(PathBuf::from("X:\\Program Files\\a.wcs")))
+      .delete(PluginBatchArg::script_dir().push_text("a.ini"))
+      .echo("done (ok)")
+      .exit(0);
+    let expected = [
+      "@echo off",
+      "chcp 65001 >nul",
+      "rem Chrome ^(setup^)",
+      "set \"APP=100%% & done\"",
+      "if not exist \"%ProgramFiles%\\Edgeless\\Chrome\" mkdir \"%ProgramFiles%\\Edgeless\\Chrome\"",
+      "copy /y \"%~dp0a.ini\" \"%ProgramFiles%\\Edgeless\\Chrome\" >nul",
+      "\"C:\\Program Files\\x.exe\" /S \"%APP%\"",
+      "reg import \"a b.reg\"",
+      "X:\\bin\\pecmd.exe LINK %%Desktop%%\\Chrome,%%ProgramFiles%%\\Edgeless\\Chrome\\chrome.exe,\"--a,--b\"",
+      "X:\\bin\\pecmd.exe LOAD \"X:\\Program Files\\a.wcs\"",
+      "if exist \"%~dp0a.ini\" del /f /q \"%~dp0a.ini\"",
+      "echo.done ^(ok^)",
+      "exit /b 0",
+      "",
+    ].join("\r\n");
+    assert_eq!(script.render()?, expected);
+
+    let oem = PluginBatchScript::new().code_page(PluginBatchCodePage::Oem(936));
+    assert_eq!(oem.clone().echo("插件").render()?, "@echo off\r\nchcp 936 >nul\r\necho.插件\r\n");
+    assert_eq!(oem.clone().echo("插件").encode()?, b"@echo off\r\nchcp 936 >nul\r\necho.\xb2\xe5\xbc\xfe\r\n");
+    assert!(oem.echo("🧩").encode().is_err());
+    assert!(PluginBatchScript::new().code_page(PluginBatchCodePage::Oem(437)).encode().is_err());
+    let system = PluginBatchScript::new().code_page(PluginBatchCodePage::System);
+    assert!(!system.clone().echo("hi").render()?.contains("chcp"));
+    assert!(system.echo("插件").encode().is_err());
+    assert!(PluginBatchScript::new().set("A", "x\ny").exit(0).render().is_err());
+    assert!(PluginBatchScript::new().set("A=B", "x").render().is_err());
+
+    Ok(())
+  }
+}
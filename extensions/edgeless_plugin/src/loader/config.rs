@@ -0,0 +1,482 @@
+use std::env::var;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use edgeless_core::found::ProfileEntry;
+use edgeless_core::options::PluginTrustPolicy;
+use edgeless_core::options::define::{PATH_BIN_7Z, PATH_OPTION_PLUGIN_LOADER};
+use edgeless_utils::rand_uuid;
+use bindings_7z::{Archive, ArchiveBackend};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::found::trust::PluginTrustStore;
+use crate::found::PluginEntry;
+use super::link::{PluginCopyFileSystem, PluginFileSystem, PluginNativeFileSystem};
+use super::mangle::mangle_release_name;
+use super::script::PluginScriptRunner;
+
+use anyhow::anyhow;
+use log::info;
+use tokio::fs;
+
+// 未指定 `release` 时使用加载目录下的这个目录
+pub const RELEASE_DIR_NAME: &str = "__release__";
+
+/*
+ * link: 目录链接与硬链接，失败时回退到复制
+ * copy: 总是复制
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginLinkStrategy {
+  #[default]
+  Link,
+  Copy,
+}
+
+impl PluginLinkStrategy {
+  pub fn filesystem(&self) -> Arc<dyn PluginFileSystem> {
+    match self {
+      Self::Link => Arc::new(PluginNativeFileSystem),
+      Self::Copy => Arc::new(PluginCopyFileSystem),
+    }
+  }
+}
+
+/*
+ * sevenzip: 调用 `seven_zip` 指定的 7z，支持所有格式
+ * native: 内置的 7z 与 zip 解压，不需要 7z.exe，不支持加密的插件包
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginArchiveBackend {
+  #[default]
+  SevenZip,
+  Native,
+}
+
+impl PluginArchiveBackend {
+  pub fn archive(&self, seven_zip: &Path) -> anyhow::Result<Arc<dyn Archive>> {
+    match self {
+      Self::SevenZip => ArchiveBackend::SevenZip(seven_zip.to_path_buf()).open(),
+      Self::Native => ArchiveBackend::Native.open(),
+    }
+  }
+}
+
+/*
+ * 加载配置，构造时不访问文件系统，目录在加载时才创建
+ * 解压先放到 `temp` 下的 `.tmp` 目录，完整后再改名到 `release`，二者应在同一个分区
+ */
+#[derive(Debug, Clone)]
+pub struct PluginLoadConfig {
+  pub(super) release: PathBuf,
+  pub(super) dest: PathBuf,
+  pub(super) temp: PathBuf,
+  pub(super) mangle_id: Uuid,
+  pub(super) seven_zip: PathBuf,
+  pub(super) archive_backend: PluginArchiveBackend,
+  // 脚本可能在这些目录中创建文件（如快捷方式），清单声明 `watch` 的插件加载时记入日志以便回滚
+  pub(super) watch_dirs: Vec<PathBuf>,
+  pub(super) runner: Option<Arc<dyn PluginScriptRunner>>,
+  pub(super) link_strategy: PluginLinkStrategy,
+  pub(super) concurrency: Option<usize>,
+  // 单个脚本的最长运行时间
+  pub(super) script_timeout: Option<Duration>,
+  // 通过环境变量告知脚本
+  pub(super) profile: Option<PathBuf>,
+  // 解压或链接前按此重新校验签名
+  pub(super) trust_policy: PluginTrustPolicy,
+  pub(super) trust_store: Arc<PluginTrustStore>,
+}
+
+/*
+ * 配置文件 `Config/PluginLoader.json`，所有字段可选
+ * 相对路径以 Profile 目录为基准
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginLoadConfigFile {
+  pub dest: Option<PathBuf>,
+  pub release: Option<PathBuf>,
+  pub temp: Option<PathBuf>,
+  pub seven_zip: Option<PathBuf>,
+  pub archive_backend: Option<PluginArchiveBackend>,
+  pub watch_dirs: Option<Vec<PathBuf>>,
+  pub link_strategy: Option<PluginLinkStrategy>,
+  pub concurrency: Option<usize>,
+  // 秒
+  pub script_timeout: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PluginLoadConfigBuilder {
+  dest: Option<PathBuf>,
+  release: Option<PathBuf>,
+  temp: Option<PathBuf>,
+  mangle_id: Option<Uuid>,
+  seven_zip: Option<PathBuf>,
+  archive_backend: PluginArchiveBackend,
+  watch_dirs: Vec<PathBuf>,
+  runner: Option<Arc<dyn PluginScriptRunner>>,
+  link_strategy: PluginLinkStrategy,
+  concurrency: Option<usize>,
+  script_timeout: Option<Duration>,
+  profile: Option<PathBuf>,
+  trust_policy: Option<PluginTrustPolicy>,
+  trust_store: Option<PluginTrustStore>,
+}
+
+impl PluginLoadConfigBuilder {
+  pub fn dest(mut self, dest: PathBuf) -> Self {
+    self.dest = Some(dest);
+    self
+  }
+
+  pub fn release(mut self, release: PathBuf) -> Self {
+    self.release = Some(release);
+    self
+  }
+
+  pub fn temp(mut self, temp: PathBuf) -> Self {
+    self.temp = Some(temp);
+    self
+  }
+
+  pub fn mangle_id(mut self, id: Uuid) -> Self {
+    self.mangle_id = Some(id);
+    self
+  }
+
+  pub fn seven_zip(mut self, exe: PathBuf) -> Self {
+    self.seven_zip = Some(exe);
+    self
+  }
+
+  pub fn archive_backend(mut self, backend: PluginArchiveBackend) -> Self {
+    self.archive_backend = backend;
+    self
+  }
+
+  pub fn watch_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+    self.watch_dirs = dirs;
+    self
+  }
+
+  pub fn runner(mut self, runner: Arc<dyn PluginScriptRunner>) -> Self {
+    self.runner = Some(runner);
+    self
+  }
+
+  pub fn link_strategy(mut self, strategy: PluginLinkStrategy) -> Self {
+    self.link_strategy = strategy;
+    self
+  }
+
+  pub fn concurrency(mut self, n: usize) -> Self {
+    self.concurrency = Some(n);
+    self
+  }
+
+  pub fn script_timeout(mut self, timeout: Duration) -> Self {
+    self.script_timeout = Some(timeout);
+    self
+  }
+
+  pub fn profile(mut self, profile: PathBuf) -> Self {
+    self.profile = Some(profile);
+    self
+  }
+
+  pub fn trust_policy(mut self, policy: PluginTrustPolicy) -> Self {
+    self.trust_policy = Some(policy);
+    self
+  }
+
+  pub fn trust_store(mut self, store: PluginTrustStore) -> Self {
+    self.trust_store = Some(store);
+    self
+  }
+
+  // 合并配置文件中给出的项，`base` 为相对路径的基准
+  pub fn with_file(mut self, file: PluginLoadConfigFile, base: &Path) -> Self {
+    let resolve = |p: PathBuf| if p.is_relative() { base.join(p) } else { p };
+    if let Some(p) = file.dest {
+      self.dest = Some(resolve(p));
+    }
+    if let Some(p) = file.release {
+      self.release = Some(resolve(p));
+    }
+    if let Some(p) = file.temp {
+      self.temp = Some(resolve(p));
+    }
+    if let Some(p) = file.seven_zip {
+      self.seven_zip = Some(resolve(p));
+    }
+    if let Some(b) = file.archive_backend {
+      self.archive_backend = b;
+    }
+    if let Some(dirs) = file.watch_dirs {
+      self.watch_dirs = dirs.into_iter().map(resolve).collect();
+    }
+    if let Some(s) = file.link_strategy {
+      self.link_strategy = s;
+    }
+    if let Some(n) = file.concurrency {
+      self.concurrency = Some(n);
+    }
+    if let Some(secs) = file.script_timeout {
+      self.script_timeout = Some(Duration::from_secs(secs));
+    }
+    self
+  }
+
+  // 读取 Profile 中的配置文件与受信任的密钥，配置文件不存在时保持不变
+  pub async fn with_profile(self, profile: &ProfileEntry) -> anyhow::Result<Self> {
+    let path = profile.path.join(PATH_OPTION_PLUGIN_LOADER.as_path());
+    let mut this = self.profile(profile.path.clone());
+    if this.trust_store.is_none() {
+      this.trust_store = Some(PluginTrustStore::from_profile(profile).await?);
+    }
+    if !path.exists() {
+      return Ok(this);
+    }
+
+    info!("found plugin loader config {:?}", path);
+    let text = fs::read_to_string(&path).await?;
+    let file = serde_json::from_str::<PluginLoadConfigFile>(&text)
+      .map_err(|e| anyhow!("invalid plugin loader config {:?}, {}", path, e))?;
+    Ok(this.with_file(file, &profile.path))
+  }
+
+  pub async fn build(self) -> anyhow::Result<PluginLoadConfig> {
+    let dest = self.dest.ok_or(anyhow!("no plugin load destination"))?;
+    if dest.as_os_str().is_empty() {
+      return Err(anyhow!("empty plugin load destination"));
+    }
+    let release = self.release.unwrap_or_else(|| dest.join(RELEASE_DIR_NAME));
+    let temp = self.temp.unwrap_or_else(|| release.clone());
+
+    // 加载目录不能在解压目录中，否则放置时会移动到自己里面
+    if dest.starts_with(&release) {
+      return Err(anyhow!("release {:?} contains destination {:?}", release, dest));
+    }
+    if dest.starts_with(&temp) {
+      return Err(anyhow!("temp {:?} contains destination {:?}", temp, dest));
+    }
+    if self.concurrency == Some(0) {
+      return Err(anyhow!("concurrency must be at least 1"));
+    }
+    if self.script_timeout == Some(Duration::ZERO) {
+      return Err(anyhow!("script timeout must not be zero"));
+    }
+
+    let mangle_id = match self.mangle_id {
+      Some(id) => id,
+      None => rand_uuid().await?,
+    };
+
+    Ok(PluginLoadConfig {
+      release,
+      dest,
+      temp,
+      mangle_id,
+      seven_zip: self.seven_zip.unwrap_or_else(|| PATH_BIN_7Z.clone()),
+      archive_backend: self.archive_backend,
+      watch_dirs: self.watch_dirs,
+      runner: self.runner,
+      link_strategy: self.link_strategy,
+      concurrency: self.concurrency,
+      script_timeout: self.script_timeout,
+      profile: self.profile,
+      trust_policy: self.trust_policy.unwrap_or(PluginTrustPolicy::Off),
+      trust_store: Arc::new(self.trust_store.unwrap_or_default()),
+    })
+  }
+}
+
+impl PluginLoadConfig {
+  pub fn builder() -> PluginLoadConfigBuilder {
+    PluginLoadConfigBuilder::default()
+  }
+
+  // 默认加载到 `%PROGRAMFILES%\Edgeless`
+  pub fn default_builder() -> anyhow::Result<PluginLoadConfigBuilder> {
+    let dest = PathBuf::from(var("PROGRAMFILES")?).join("Edgeless");
+
+    let mut watch_dirs = vec![];
+    if let Ok(p) = var("USERPROFILE") {
+      watch_dirs.push(PathBuf::from(p).join("Desktop"));
+    }
+    if let Ok(p) = var("APPDATA") {
+      watch_dirs.push(PathBuf::from(p).join("Microsoft").join("Windows").join("Start Menu").join("Programs"));
+    }
+
+    Ok(Self::builder().dest(dest).watch_dirs(watch_dirs))
+  }
+
+  pub async fn default() -> anyhow::Result<Self> {
+    Self::default_builder()?.build().await
+  }
+
+  pub async fn from_profile(profile: &ProfileEntry) -> anyhow::Result<Self> {
+    Self::default_builder()
+      .unwrap_or_default()
+      .with_profile(profile)
+      .await?
+      .build()
+      .await
+  }
+
+  pub async fn new(dest: PathBuf) -> anyhow::Result<Self> {
+    Self::builder().dest(dest).build().await
+  }
+
+  pub fn with_seven_zip(mut self, exe: PathBuf) -> Self {
+    self.seven_zip = exe;
+    self
+  }
+
+  pub fn with_archive_backend(mut self, backend: PluginArchiveBackend) -> Self {
+    self.archive_backend = backend;
+    self
+  }
+
+  pub fn with_watch_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+    self.watch_dirs = dirs;
+    self
+  }
+
+  pub fn with_trust(mut self, policy: PluginTrustPolicy, store: PluginTrustStore) -> Self {
+    self.trust_policy = policy;
+    self.trust_store = Arc::new(store);
+    self
+  }
+
+  pub fn release(&self) -> &Path {
+    &self.release
+  }
+
+  pub fn dest(&self) -> &Path {
+    &self.dest
+  }
+
+  pub fn temp(&self) -> &Path {
+    &self.temp
+  }
+
+  pub fn seven_zip(&self) -> &Path {
+    &self.seven_zip
+  }
+
+  pub fn archive_backend(&self) -> PluginArchiveBackend {
+    self.archive_backend
+  }
+
+  // 按配置的后端打开插件包
+  pub fn archive(&self) -> anyhow::Result<Arc<dyn Archive>> {
+    self.archive_backend.archive(&self.seven_zip)
+  }
+
+  pub fn watch_dirs(&self) -> &[PathBuf] {
+    &self.watch_dirs
+  }
+
+  pub fn runner(&self) -> Option<&Arc<dyn PluginScriptRunner>> {
+    self.runner.as_ref()
+  }
+
+  pub fn link_strategy(&self) -> PluginLinkStrategy {
+    self.link_strategy
+  }
+
+  pub fn concurrency(&self) -> Option<usize> {
+    self.concurrency
+  }
+
+  pub fn script_timeout(&self) -> Option<Duration> {
+    self.script_timeout
+  }
+
+  pub fn profile(&self) -> Option<&Path> {
+    self.profile.as_deref()
+  }
+
+  pub fn trust_policy(&self) -> PluginTrustPolicy {
+    self.trust_policy
+  }
+
+  pub fn trust_store(&self) -> &PluginTrustStore {
+    &self.trust_store
+  }
+
+  // 解压目录名，避免不同插件或多次加载互相覆盖
+  pub fn mangle(&self, entry: &PluginEntry) -> String {
+    mangle_release_name(entry, &self.mangle_id)
+  }
+
+  pub fn mangle_id(&self) -> Uuid {
+    self.mangle_id
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use edgeless_core::found::{ProfileEntry, ProfileType};
+  use tokio::fs;
+  use uuid::Uuid;
+
+  use super::{PluginArchiveBackend, PluginLinkStrategy, PluginLoadConfig};
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dest = dir.path().join("Edgeless");
+    let id = Uuid::from_u128(1);
+    let config = PluginLoadConfig::builder()
+      .dest(dest.clone())
+      .mangle_id(id)
+      .build()
+      .await?;
+    assert_eq!(config.release(), dest.join("__release__"));
+    assert_eq!(config.temp(), config.release());
+    assert_eq!(config.link_strategy(), PluginLinkStrategy::Link);
+    assert_eq!(config.archive_backend(), PluginArchiveBackend::SevenZip);
+    assert!(!dest.exists());
+
+    assert!(PluginLoadConfig::builder().build().await.is_err());
+    assert!(PluginLoadConfig::builder().dest(dest.clone()).release(dir.path().to_path_buf()).build().await.is_err());
+    assert!(PluginLoadConfig::builder().dest(dest.clone()).concurrency(0).build().await.is_err());
+
+    let profile = dir.path().join("Stick").join("Edgeless");
+    fs::create_dir_all(profile.join("Config")).await?;
+    fs::write(profile.join("Config/PluginLoader.json"), r#"{
+      "dest": "Target",
+      "seven_zip": "bin/7z.exe",
+      "archive_backend": "native",
+      "link_strategy": "copy",
+      "concurrency": 2,
+      "script_timeout": 60
+    }"#).await?;
+    let profile = ProfileEntry::from_path(profile, ProfileType::Default);
+    let config = PluginLoadConfig::builder()
+      .dest(dest.clone())
+      .with_profile(&profile)
+      .await?
+      .build()
+      .await?;
+    assert_eq!(config.dest(), profile.path.join("Target"));
+    assert_eq!(config.release(), profile.path.join("Target/__release__"));
+    assert_eq!(config.seven_zip(), profile.path.join("bin/7z.exe"));
+    assert_eq!(config.archive_backend(), PluginArchiveBackend::Native);
+    assert_eq!(config.link_strategy(), PluginLinkStrategy::Copy);
+    assert_eq!(config.concurrency(), Some(2));
+    assert_eq!(config.script_timeout(), Some(std::time::Duration::from_secs(60)));
+    assert_eq!(config.profile(), Some(profile.path.as_path()));
+
+    fs::write(profile.path.join("Config/PluginLoader.json"), r#"{ "unknown": 1 }"#).await?;
+    assert!(PluginLoadConfig::builder().with_profile(&profile).await.is_err());
+
+    Ok(())
+  }
+}
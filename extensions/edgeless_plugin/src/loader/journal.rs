@@ -0,0 +1,271 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use super::link::{PluginLinkEntry, PluginLinkKind};
+use super::{PluginLoadSession, PluginLoadState};
+
+use anyhow::anyhow;
+use async_recursion::async_recursion;
+use log::{info, warn};
+use tokio::fs;
+
+/*
+ * 插件加载过程中创建的文件、目录与链接
+ * 可保存为 JSON，在另一个进程中回滚
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginLoadJournal {
+  pub plugin: PathBuf,
+  pub files: Vec<PathBuf>,
+  // 按创建顺序，父目录在前
+  pub dirs: Vec<PathBuf>,
+  pub links: Vec<PluginLinkEntry>,
+  // 未完成的解压目录
+  pub release: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginRollbackError {
+  pub path: PathBuf,
+  pub error: String,
+}
+
+async fn remove_link(path: &Path) -> std::io::Result<()> {
+  #[cfg(windows)]
+  {
+    // 目录联接与目录符号链接需要用 remove_dir 删除
+    if fs::remove_dir(path).await.is_ok() {
+      return Ok(());
+    }
+  }
+  fs::remove_file(path).await
+}
+
+fn not_found(e: &std::io::Error) -> bool {
+  e.kind() == std::io::ErrorKind::NotFound
+}
+
+// 目录只在为空时删除，其他插件或用户放入的文件不受影响
+async fn remove_empty_dir(path: &Path) -> std::io::Result<()> {
+  let mut iter = fs::read_dir(path).await?;
+  if iter.next_entry().await?.is_some() {
+    info!("{:?} is not empty, keep it", path);
+    return Ok(());
+  }
+  fs::remove_dir(path).await
+}
+
+impl PluginLoadJournal {
+  pub fn to_json(&self) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(self)?)
+  }
+
+  pub fn from_json(s: &str) -> anyhow::Result<Self> {
+    serde_json::from_str(s).map_err(|e| anyhow!("invalid load journal, {}", e))
+  }
+
+  /*
+   * 按创建的逆序删除，已不存在的路径视为成功
+   * 目录只在为空时删除，其他插件放入的文件不受影响
+   */
+  pub async fn rollback(&self) -> Vec<PluginRollbackError> {
+    info!("rollback plugin {:?}", self.plugin);
+    let mut errors = vec![];
+    let mut fail = |path: &Path, e: std::io::Error| {
+      if !not_found(&e) {
+        warn!("failed to remove {:?}, {}", path, e);
+        errors.push(PluginRollbackError {
+          path: path.to_path_buf(),
+          error: e.to_string(),
+        });
+      }
+    };
+
+    for l in self.links.iter().rev() {
+      let r = match l.kind {
+        PluginLinkKind::DirLink => remove_link(&l.path).await,
+        PluginLinkKind::Hardlink => fs::remove_file(&l.path).await,
+        PluginLinkKind::Copy if l.path.is_dir() => remove_empty_dir(&l.path).await,
+        PluginLinkKind::Copy => fs::remove_file(&l.path).await,
+      };
+      if let Err(e) = r {
+        fail(&l.path, e);
+      }
+    }
+
+    for f in self.files.iter().rev() {
+      if let Err(e) = fs::remove_file(f).await {
+        fail(f, e);
+      }
+    }
+
+    for d in self.dirs.iter().rev() {
+      if let Err(e) = remove_empty_dir(d).await {
+        fail(d, e);
+      }
+    }
+
+    if let Some(r) = &self.release {
+      if let Err(e) = fs::remove_dir_all(r).await {
+        fail(r, e);
+      }
+    }
+
+    errors
+  }
+}
+
+// 记录目录下已有的路径，不进入符号链接
+#[async_recursion]
+pub(crate) async fn snapshot(dir: &Path, paths: &mut BTreeSet<PathBuf>) -> anyhow::Result<()> {
+  let mut iter = match fs::read_dir(dir).await {
+    Ok(iter) => iter,
+    Err(e) if not_found(&e) => return Ok(()),
+    Err(e) => return Err(e.into()),
+  };
+  while let Some(f) = iter.next_entry().await? {
+    let path = f.path();
+    let t = f.file_type().await?;
+    paths.insert(path.clone());
+    if t.is_dir() && !t.is_symlink() {
+      snapshot(&path, paths).await?;
+    }
+  }
+  Ok(())
+}
+
+impl<'a> PluginLoadSession<'a> {
+  pub fn journal(&self) -> PluginLoadJournal {
+    let release = self.config.as_ref()
+      .filter(|c| self.release.starts_with(c.release()) && self.release != c.release())
+      .map(|_| self.release.clone());
+    PluginLoadJournal {
+      plugin: self.entry.path.clone(),
+      files: self.depend_files.clone(),
+      dirs: self.depend_dirs.clone(),
+      links: self.links.clone(),
+      release,
+    }
+  }
+
+  pub(crate) async fn watch_snapshot(&self) -> BTreeSet<PathBuf> {
+    let mut paths = BTreeSet::new();
+    if !self.entry.is_watched() {
+      return paths;
+    }
+    for d in self.config.iter().flat_map(|c| c.watch_dirs.iter()) {
+      if let Err(e) = snapshot(d, &mut paths).await {
+        warn!("failed to snapshot {:?}, {}", d, e);
+      }
+    }
+    paths
+  }
+
+  // 脚本在监视目录中新建的文件与目录（如快捷方式）记入日志，只对清单声明 `watch` 的插件有效
+  pub(crate) async fn record_created(&mut self, before: &BTreeSet<PathBuf>) {
+    if !self.entry.is_watched() {
+      return;
+    }
+    let after = self.watch_snapshot().await;
+    for p in after.difference(before) {
+      info!("script created {:?}", p);
+      if p.is_dir() {
+        self.depend_dirs.push(p.clone());
+      } else {
+        self.depend_files.push(p.clone());
+      }
+    }
+  }
+
+  pub async fn rollback(&mut self) -> Vec<PluginRollbackError> {
+    let errors = self.journal().rollback().await;
+    self.depend_files.clear();
+    self.depend_dirs.clear();
+    self.links.clear();
+    errors
+  }
+
+  // 卸载已加载的插件，之后可以再次加载
+  pub async fn unload(&mut self) -> anyhow::Result<()> {
+    if self.state == PluginLoadState::Pending {
+      return Err(anyhow!("plugin {:?} is not loaded", self.entry.path));
+    }
+
+    let errors = self.rollback().await;
+    if let Some(e) = errors.first() {
+      return Err(anyhow!("failed to unload {:?}, {} errors, first: {:?} {}", self.entry.path, errors.len(), e.path, e.error));
+    }
+
+    self.scripts.clear();
+    self.outputs.clear();
+    self.release = PathBuf::new();
+    self.error = None;
+    self.state = PluginLoadState::Pending;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use tokio::fs;
+  use crate::testing::touch;
+
+  use super::PluginLoadJournal;
+  use crate::loader::link::{PluginLinkEntry, PluginLinkKind};
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    touch(&root.join("Repo/Office/office.exe"), "").await?;
+    touch(&root.join("Dest/App/app.exe"), "").await?;
+    touch(&root.join("Dest/Shared/ours.dll"), "").await?;
+    touch(&root.join("Dest/Shared/theirs.dll"), "").await?;
+    touch(&root.join("Dest/Copied/a.txt"), "").await?;
+    touch(&root.join("Dest/Copied/Sub/b.txt"), "").await?;
+    touch(&root.join("Dest/Kept/a.txt"), "").await?;
+    touch(&root.join("Dest/Kept/user.txt"), "").await?;
+    touch(&root.join("Release/Chrome_x/half.exe"), "").await?;
+
+    let journal = PluginLoadJournal {
+      plugin: root.join("Chrome_1.0_Cno.7z"),
+      files: vec![
+        root.join("Dest/App/app.exe"),
+        root.join("Dest/Shared/ours.dll"),
+        root.join("Dest/missing.txt"),
+      ],
+      dirs: vec![root.join("Dest/App"), root.join("Dest/Shared")],
+      links: vec![
+        "Copied",
+        "Copied/a.txt",
+        "Copied/Sub",
+        "Copied/Sub/b.txt",
+        "Kept",
+        "Kept/a.txt",
+      ].into_iter().map(|p| PluginLinkEntry {
+        path: root.join("Dest").join(p),
+        target: root.join("Repo").join(p),
+        kind: PluginLinkKind::Copy,
+      }).collect(),
+      release: Some(root.join("Release/Chrome_x")),
+    };
+    let journal = PluginLoadJournal::from_json(&journal.to_json()?)?;
+
+    assert!(journal.rollback().await.is_empty());
+    assert!(!root.join("Dest/App").exists());
+    assert!(!root.join("Dest/Shared/ours.dll").exists());
+    assert!(root.join("Dest/Shared/theirs.dll").exists());
+    assert!(!root.join("Dest/Copied").exists());
+    // 复制的目录中有不属于插件的文件，只删除复制出的文件
+    assert!(!root.join("Dest/Kept/a.txt").exists());
+    assert!(root.join("Dest/Kept/user.txt").exists());
+    assert!(!root.join("Release/Chrome_x").exists());
+    assert!(root.join("Repo/Office/office.exe").exists());
+
+    // 重复回滚不报错
+    assert!(journal.rollback().await.is_empty());
+
+    Ok(())
+  }
+}
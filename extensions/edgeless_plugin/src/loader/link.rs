@@ -0,0 +1,237 @@
+use std::fmt::Debug;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use serde::{Deserialize, Serialize};
+
+use anyhow::anyhow;
+use async_recursion::async_recursion;
+use log::{info, warn};
+use tokio::fs;
+
+pub type PluginFsFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PluginLinkKind {
+  // 目录联接或目录符号链接
+  DirLink,
+  Hardlink,
+  Copy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginLinkEntry {
+  pub path: PathBuf,
+  pub target: PathBuf,
+  pub kind: PluginLinkKind,
+}
+
+/*
+ * LocalBoost 链接所需的文件系统操作
+ * 任一操作失败时由调用方回退到复制
+ */
+pub trait PluginFileSystem: Debug + Send + Sync {
+  fn link_dir<'a>(&'a self, src: &'a Path, dst: &'a Path) -> PluginFsFuture<'a>;
+  fn link_file<'a>(&'a self, src: &'a Path, dst: &'a Path) -> PluginFsFuture<'a>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PluginNativeFileSystem;
+
+impl PluginNativeFileSystem {
+  #[cfg(unix)]
+  async fn symlink_dir(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    Ok(fs::symlink(src, dst).await?)
+  }
+
+  // 符号链接需要开发者模式或管理员权限，失败时改用目录联接
+  #[cfg(windows)]
+  async fn symlink_dir(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    if fs::symlink_dir(src, dst).await.is_ok() {
+      return Ok(());
+    }
+
+    let out = tokio::process::Command::new("cmd")
+      .arg("/c")
+      .arg("mklink")
+      .arg("/J")
+      .arg(dst)
+      .arg(src)
+      .output()
+      .await?;
+    if !out.status.success() {
+      return Err(anyhow!("mklink /J failed, {}", String::from_utf8_lossy(&out.stdout).trim()));
+    }
+    Ok(())
+  }
+}
+
+impl PluginFileSystem for PluginNativeFileSystem {
+  fn link_dir<'a>(&'a self, src: &'a Path, dst: &'a Path) -> PluginFsFuture<'a> {
+    Box::pin(Self::symlink_dir(src, dst))
+  }
+
+  fn link_file<'a>(&'a self, src: &'a Path, dst: &'a Path) -> PluginFsFuture<'a> {
+    Box::pin(async move { Ok(fs::hard_link(src, dst).await?) })
+  }
+}
+
+// 不创建任何链接，全部回退到复制；用于不支持链接的文件系统（如 FAT32 上的加载目录）
+#[derive(Debug, Clone, Default)]
+pub struct PluginCopyFileSystem;
+
+impl PluginFileSystem for PluginCopyFileSystem {
+  fn link_dir<'a>(&'a self, _: &'a Path, _: &'a Path) -> PluginFsFuture<'a> {
+    Box::pin(async { Err(anyhow!("link is disabled")) })
+  }
+
+  fn link_file<'a>(&'a self, _: &'a Path, _: &'a Path) -> PluginFsFuture<'a> {
+    Box::pin(async { Err(anyhow!("link is disabled")) })
+  }
+}
+
+// 逐项复制并记录，回滚时只删除复制出的文件，目录为空时才删除
+#[async_recursion]
+async fn copy_tree(src: &Path, dst: &Path, links: &mut Vec<PluginLinkEntry>) -> anyhow::Result<()> {
+  fs::create_dir(dst).await?;
+  links.push(PluginLinkEntry {
+    path: dst.to_path_buf(),
+    target: src.to_path_buf(),
+    kind: PluginLinkKind::Copy,
+  });
+  let mut iter = fs::read_dir(src).await?;
+  while let Some(f) = iter.next_entry().await? {
+    let to = dst.join(f.file_name());
+    if f.file_type().await?.is_dir() {
+      copy_tree(&f.path(), &to, links).await?;
+    } else {
+      fs::copy(f.path(), &to).await?;
+      links.push(PluginLinkEntry {
+        path: to,
+        target: f.path(),
+        kind: PluginLinkKind::Copy,
+      });
+    }
+  }
+  Ok(())
+}
+
+/*
+ * 把 `src` 目录下的内容链接到 `dst`
+ * `dst` 中不存在的目录整体链接，已存在的目录逐项合并，已存在的文件跳过；
+ * `skip` 返回 true 的根目录项不处理（如脚本）
+ */
+#[async_recursion]
+pub async fn link_tree(
+  filesystem: &dyn PluginFileSystem,
+  src: &Path,
+  dst: &Path,
+  skip: &(dyn Fn(&Path) -> bool + Sync),
+  links: &mut Vec<PluginLinkEntry>,
+) -> anyhow::Result<()> {
+  fs::create_dir_all(dst).await?;
+  let mut iter = fs::read_dir(src).await?;
+  let mut items = vec![];
+  while let Some(f) = iter.next_entry().await? {
+    items.push((f.path(), f.file_type().await?.is_dir()));
+  }
+  items.sort();
+
+  for (from, is_dir) in items {
+    if skip(&from) {
+      continue;
+    }
+    let name = from.file_name().ok_or(anyhow!("invalid path {:?}", from))?;
+    let to = dst.join(name);
+
+    // 已链接的目录属于其他插件，不能合并进去
+    let is_link = fs::symlink_metadata(&to).await
+      .map(|m| m.file_type().is_symlink())
+      .unwrap_or(false);
+    if is_dir && !is_link && to.is_dir() {
+      link_tree(filesystem, &from, &to, &|_| false, links).await?;
+      continue;
+    }
+    if to.exists() {
+      warn!("{:?} already exists, skip", to);
+      continue;
+    }
+
+    let linked = if is_dir {
+      filesystem.link_dir(&from, &to).await
+    } else {
+      filesystem.link_file(&from, &to).await
+    };
+    let kind = match linked {
+      Ok(_) if is_dir => PluginLinkKind::DirLink,
+      Ok(_) => PluginLinkKind::Hardlink,
+      Err(e) if is_dir => {
+        info!("cannot link {:?}, fallback to copy, {}", from, e);
+        copy_tree(&from, &to, links).await?;
+        continue;
+      }
+      Err(e) => {
+        info!("cannot link {:?}, fallback to copy, {}", from, e);
+        fs::copy(&from, &to).await?;
+        PluginLinkKind::Copy
+      }
+    };
+
+    links.push(PluginLinkEntry {
+      path: to,
+      target: from,
+      kind,
+    });
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use tokio::fs;
+  use crate::testing::touch;
+
+  use super::{link_tree, PluginCopyFileSystem, PluginLinkKind, PluginNativeFileSystem};
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let src = dir.path().join("Repo");
+    touch(&src.join("Chrome/chrome.exe"), "chrome").await?;
+    touch(&src.join("Shared/a.dll"), "a").await?;
+    touch(&src.join("Chrome.wcs"), "").await?;
+    touch(&src.join("readme.txt"), "readme").await?;
+
+    let dst = dir.path().join("Edgeless");
+    touch(&dst.join("Shared/b.dll"), "b").await?;
+    touch(&dst.join("readme.txt"), "ours").await?;
+
+    let mut links = vec![];
+    let skip = |p: &Path| p.extension().map(|e| e == "wcs").unwrap_or(false);
+    link_tree(&PluginNativeFileSystem, &src, &dst, &skip, &mut links).await?;
+
+    let kinds = links.iter()
+      .map(|l| (l.path.strip_prefix(&dst).unwrap().to_path_buf(), l.kind))
+      .collect::<Vec<_>>();
+    assert_eq!(kinds, vec![
+      (Path::new("Chrome").to_path_buf(), PluginLinkKind::DirLink),
+      (Path::new("Shared/a.dll").to_path_buf(), PluginLinkKind::Hardlink),
+    ]);
+    assert_eq!(fs::read_to_string(dst.join("Chrome/chrome.exe")).await?, "chrome");
+    assert_eq!(fs::read_to_string(dst.join("readme.txt")).await?, "ours");
+    assert!(!dst.join("Chrome.wcs").exists());
+
+    let copied = dir.path().join("Copied");
+    let mut links = vec![];
+    link_tree(&PluginCopyFileSystem, &src, &copied, &skip, &mut links).await?;
+    assert!(links.iter().all(|l| l.kind == PluginLinkKind::Copy));
+    let copied_paths = links.iter().map(|l| l.path.clone()).collect::<Vec<_>>();
+    assert!(copied_paths.contains(&copied.join("Chrome")));
+    assert!(copied_paths.contains(&copied.join("Chrome/chrome.exe")));
+    assert_eq!(fs::read_to_string(copied.join("Chrome/chrome.exe")).await?, "chrome");
+    assert!(!fs::symlink_metadata(copied.join("Chrome")).await?.file_type().is_symlink());
+
+    Ok(())
+  }
+}
@@ -0,0 +1,516 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use bindings_pecmd::ast::{FileOp, Stmt, StmtKind, Word};
+use bindings_pecmd::parser::parse;
+use regex::Regex;
+use serde::Serialize;
+use super::{PluginLoadSession, PluginScriptType};
+
+use async_recursion::async_recursion;
+use lazy_static::lazy_static;
+use log::info;
+use tokio::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginLintSeverity {
+  Warning,
+  Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PluginLintRule {
+  // 脚本无法解析
+  ParseError,
+  // 写死的盘符，如 `X:\`
+  HardcodedDrive,
+  // 加载目录以外的绝对路径，如 `%SystemRoot%`
+  AbsolutePath,
+  // 引用了压缩包中不存在的文件
+  MissingFile,
+  // 格式化、分区、删除系统注册表等
+  DangerousCommand,
+  // 含空格的路径没有加引号
+  UnquotedSpace,
+}
+
+impl PluginLintRule {
+  pub fn id(&self) -> &'static str {
+    match self {
+      Self::ParseError => "parse-error",
+      Self::HardcodedDrive => "hardcoded-drive",
+      Self::AbsolutePath => "absolute-path",
+      Self::MissingFile => "missing-file",
+      Self::DangerousCommand => "dangerous-command",
+      Self::UnquotedSpace => "unquoted-space",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginLintIssue {
+  pub rule: PluginLintRule,
+  pub severity: PluginLintSeverity,
+  pub script: PathBuf,
+  // 从 1 开始
+  pub line: usize,
+  pub column: usize,
+  pub message: String,
+}
+
+impl Display for PluginLintIssue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let severity = match self.severity {
+      PluginLintSeverity::Warning => "warning",
+      PluginLintSeverity::Error => "error",
+    };
+    write!(
+      f,
+      "{}:{}:{}: {}[{}] {}",
+      self.script.display(),
+      self.line,
+      self.column,
+      severity,
+      self.rule.id(),
+      self.message
+    )
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PluginLintReport {
+  pub issues: Vec<PluginLintIssue>,
+}
+
+impl PluginLintReport {
+  pub fn to_json(&self) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(self)?)
+  }
+
+  pub fn has_errors(&self) -> bool {
+    self.issues.iter().any(|i| i.severity == PluginLintSeverity::Error)
+  }
+
+  pub fn count(&self, severity: PluginLintSeverity) -> usize {
+    self.issues.iter().filter(|i| i.severity == severity).count()
+  }
+
+  // 检查解压后的插件目录中根目录下的脚本
+  pub async fn lint_dir(dir: &Path) -> anyhow::Result<Self> {
+    let files = archive_files(dir).await?;
+    let mut report = Self::default();
+    let mut iter = fs::read_dir(dir).await?;
+    let mut scripts = vec![];
+    while let Some(f) = iter.next_entry().await? {
+      let path = f.path();
+      if f.file_type().await?.is_file() {
+        if let Some(t) = PluginScriptType::from_path(&path) {
+          scripts.push((path, t));
+        }
+      }
+    }
+    scripts.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (path, t) in scripts {
+      let content = fs::read(&path).await?;
+      let name = PathBuf::from(path.file_name().unwrap_or_default());
+      report.issues.append(&mut lint_script(&name, t, &String::from_utf8_lossy(&content), &files));
+    }
+    info!("lint {:?}, {} errors, {} warnings", dir, report.count(PluginLintSeverity::Error), report.count(PluginLintSeverity::Warning));
+    Ok(report)
+  }
+}
+
+impl Display for PluginLintReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for i in &self.issues {
+      writeln!(f, "{}", i)?;
+    }
+    Ok(())
+  }
+}
+
+// 压缩包中的文件与目录，小写并以 `/` 分隔，用于不区分大小写的查找
+#[async_recursion]
+async fn collect_files(root: &Path, dir: &Path, files: &mut BTreeSet<String>) -> anyhow::Result<()> {
+  let mut iter = fs::read_dir(dir).await?;
+  while let Some(f) = iter.next_entry().await? {
+    let path = f.path();
+    if let Ok(rel) = path.strip_prefix(root) {
+      files.insert(normalize(&rel.to_string_lossy()));
+    }
+    if f.file_type().await?.is_dir() {
+      collect_files(root, &path, files).await?;
+    }
+  }
+  Ok(())
+}
+
+pub async fn archive_files(dir: &Path) -> anyhow::Result<BTreeSet<String>> {
+  let mut files = BTreeSet::new();
+  collect_files(dir, dir, &mut files).await?;
+  Ok(files)
+}
+
+fn normalize(p: &str) -> String {
+  p.replace('\\', "/").trim_matches('/').to_lowercase()
+}
+
+lazy_static! {
+  static ref RE_DRIVE: Regex = Regex::new(r"(?:^|[^A-Za-z0-9_%~])([A-Za-z]:[\\/])").unwrap();
+  static ref RE_ROOT_VAR: Regex = Regex::new(
+    r#"(?i)%(SystemRoot|WinDir|SystemDrive|ProgramData|ProgramFiles\(x86\)|ProgramW6432|CommonProgramFiles|ProgramFiles)%(\\[^\\\s,"]*)?"#
+  ).unwrap();
+  static ref RE_FORMAT: Regex = Regex::new(r#"(?i)(?:^|[\s"&|(=!@*\\])format(?:\.com|\.exe)?\s+"?[a-z]:"#).unwrap();
+  static ref RE_DISKPART: Regex = Regex::new(r#"(?i)(?:^|[\s"&|(=!@*\\])(diskpart|bcdedit)(?:\.exe)?(?:\s|"|$)"#).unwrap();
+  static ref RE_REG_DELETE: Regex = Regex::new(
+    r#"(?i)\breg(?:\.exe)?"?\s+delete\s+"?(?:HKLM|HKEY_LOCAL_MACHINE)\\(SYSTEM|SOFTWARE|SAM|SECURITY)\\?(?:"|\s|$)"#
+  ).unwrap();
+  // 展开后含空格的变量
+  static ref RE_SPACED_VAR: Regex = Regex::new(r"(?i)%(ProgramFiles|ProgramFiles\(x86\)|CommonProgramFiles|Programs|StartMenu)%").unwrap();
+  static ref RE_SPACED_PATH: Regex = Regex::new(r"(?i)[a-z]:\\[^\\\s]*\s[^\\]*\\").unwrap();
+}
+
+struct Linter<'a> {
+  script: &'a Path,
+  script_type: PluginScriptType,
+  files: &'a BTreeSet<String>,
+  issues: Vec<PluginLintIssue>,
+}
+
+// 行中不在引号内的区间
+fn unquoted(line: &str) -> Vec<(usize, &str)> {
+  let mut out = vec![];
+  let mut from = 0;
+  for (n, part) in line.split('"').enumerate() {
+    if n % 2 == 0 {
+      out.push((from, part));
+    }
+    from += part.len() + 1;
+  }
+  out
+}
+
+// 不区分 ASCII 大小写，从 `from` 开始查找，返回原字符串中的字节位置
+fn find_ignore_case(text: &str, pat: &str, from: usize) -> Option<usize> {
+  (from..text.len()).find(|&i| text.get(i..i + pat.len()).is_some_and(|s| s.eq_ignore_ascii_case(pat)))
+}
+
+fn starts_with_ignore_case(text: &str, pat: &str) -> bool {
+  text.get(..pat.len()).is_some_and(|s| s.eq_ignore_ascii_case(pat))
+}
+
+// PECMD 语句中需要检查的参数，IFEX 分支与 _SUB 中的语句由 `Script::walk` 单独访问
+fn stmt_words(stmt: &Stmt) -> Vec<&Word> {
+  match &stmt.kind {
+    StmtKind::Exec { command, .. } => vec![command],
+    StmtKind::Link { path, target, rest } => vec![path, target].into_iter().chain(rest).collect(),
+    StmtKind::File(FileOp::Copy { from, to } | FileOp::Move { from, to }) => vec![from, to],
+    StmtKind::File(FileOp::Delete { path }) => vec![path],
+    StmtKind::Envi { value, .. } => value.iter().collect(),
+    StmtKind::Ifex { cond, .. } => vec![&cond.text],
+    StmtKind::Call { name, args } => std::iter::once(name).chain(args).collect(),
+    StmtKind::Command { args, .. } => vec![args],
+    StmtKind::Comment(_) | StmtKind::Sub { .. } => vec![],
+  }
+}
+
+fn in_quotes(line: &str, at: usize) -> bool {
+  line[..at].matches('"').count() % 2 == 1
+}
+
+// 从 `at` 开始的路径，引号内到引号结束，否则到空白或分隔符
+fn path_at(line: &str, at: usize) -> &str {
+  let rest = &line[at..];
+  let end = if in_quotes(line, at) {
+    rest.find('"')
+  } else {
+    rest.find(|c: char| c.is_whitespace() || "\",|&<>".contains(c))
+  };
+  rest[..end.unwrap_or(rest.len())].trim_end_matches('\\')
+}
+
+impl<'a> Linter<'a> {
+  fn push(&mut self, rule: PluginLintRule, severity: PluginLintSeverity, line: usize, text: &str, at: usize, message: String) {
+    self.issues.push(PluginLintIssue {
+      rule,
+      severity,
+      script: self.script.to_path_buf(),
+      line,
+      column: text[..at].chars().count() + 1,
+      message,
+    });
+  }
+
+  // 批处理逐行检查
+  fn batch_line(&mut self, n: usize, text: &str) {
+    let command = text.trim_start().trim_start_matches('@');
+    if command.eq_ignore_ascii_case("rem") || starts_with_ignore_case(command, "rem ") || command.starts_with("::") {
+      return;
+    }
+    self.check(n, text, 0..text.len());
+    // 赋值与输出中的空格不影响执行
+    if starts_with_ignore_case(command, "set ") || starts_with_ignore_case(command, "echo") {
+      return;
+    }
+    self.unquoted_space(n, text, 0..text.len());
+  }
+
+  // PECMD 按语句中各参数的位置检查，`lines` 为各行的起始字节与内容
+  fn stmt(&mut self, stmt: &Stmt, lines: &[(usize, &str)]) {
+    for word in stmt_words(stmt) {
+      let (start, text) = lines[word.span.line - 1];
+      let scope = word.span.start - start..(word.span.end - start).min(text.len());
+      self.check(word.span.line, text, scope.clone());
+
+      // 参数以逗号分隔，只有 EXEC 的命令行会按空格切分
+      if let StmtKind::Exec { .. } = stmt.kind {
+        let command = &text[scope.clone()];
+        if !command.starts_with('"') {
+          let program = command.split(' ').next().unwrap_or_default();
+          self.unquoted_space(word.span.line, text, scope.start..scope.start + program.len());
+        }
+      }
+    }
+  }
+
+  // `scope` 为 `text` 中要检查的字节区间
+  fn check(&mut self, n: usize, text: &str, scope: Range<usize>) {
+    use PluginLintRule::*;
+    use PluginLintSeverity::*;
+
+    let (base, part) = (scope.start, &text[scope.clone()]);
+    for c in RE_DRIVE.captures_iter(part) {
+      let m = c.get(1).unwrap();
+      self.push(HardcodedDrive, Warning, n, text, base + m.start(), format!("hard-coded drive `{}`", m.as_str()));
+    }
+
+    for c in RE_ROOT_VAR.captures_iter(part) {
+      let m = c.get(0).unwrap();
+      let var = c.get(1).unwrap().as_str();
+      let sub = c.get(2).map(|s| s.as_str()).unwrap_or_default();
+      if var.eq_ignore_ascii_case("ProgramFiles") && sub.eq_ignore_ascii_case("\\Edgeless") {
+        continue;
+      }
+      self.push(AbsolutePath, Warning, n, text, base + m.start(), format!("path `{}` is outside %ProgramFiles%\\Edgeless", m.as_str()));
+    }
+
+    for (re, what) in [(&*RE_FORMAT, "format"), (&*RE_DISKPART, "diskpart"), (&*RE_REG_DELETE, "reg delete")] {
+      if let Some(m) = re.find(part) {
+        let name = match what {
+          "diskpart" => re.captures(part).unwrap().get(1).unwrap().as_str().to_lowercase(),
+          _ => what.to_string(),
+        };
+        let at = m.start() + m.as_str().len() - m.as_str().trim_start_matches(|c: char| !c.is_alphabetic()).len();
+        self.push(DangerousCommand, Error, n, text, base + at, format!("dangerous command `{}`", name));
+      }
+    }
+
+    self.missing(n, text, scope);
+  }
+
+  // `scope` 为 `text` 中要检查的字节区间
+  fn unquoted_space(&mut self, n: usize, text: &str, scope: Range<usize>) {
+    let base = scope.start;
+    for (from, part) in unquoted(&text[scope]) {
+      if let Some(m) = RE_SPACED_VAR.find(part).or_else(|| RE_SPACED_PATH.find(part)) {
+        let at = base + from + m.start();
+        self.push(
+          PluginLintRule::UnquotedSpace,
+          PluginLintSeverity::Warning,
+          n,
+          text,
+          at,
+          format!("`{}` contains spaces and should be quoted", m.as_str()),
+        );
+        return;
+      }
+    }
+  }
+
+  // 相对插件目录的引用应在压缩包中存在，路径不超出 `scope`
+  fn missing(&mut self, n: usize, text: &str, scope: Range<usize>) {
+    let part = &text[..scope.end];
+    let mut prefixes = vec![("%programfiles%\\edgeless\\", PluginLintSeverity::Warning)];
+    match self.script_type {
+      PluginScriptType::Batch => prefixes.push(("%~dp0", PluginLintSeverity::Error)),
+      PluginScriptType::Pecmd => prefixes.push(("%curdir%\\", PluginLintSeverity::Error)),
+    }
+
+    for (prefix, severity) in prefixes {
+      let mut from = scope.start;
+      while let Some(i) = find_ignore_case(part, prefix, from) {
+        let at = i + prefix.len();
+        from = at;
+        let path = path_at(part, at);
+        if path.is_empty() || path.contains('%') || path.contains('*') {
+          continue;
+        }
+        if !self.files.contains(&normalize(path)) {
+          self.push(
+            PluginLintRule::MissingFile,
+            severity,
+            n,
+            text,
+            at,
+            format!("`{}` is not in the plugin", path),
+          );
+        }
+      }
+    }
+  }
+}
+
+/*
+ * 检查一个脚本，`files` 为压缩包中的文件（见 `archive_files`）
+ * 批处理逐行检查；PECMD 脚本解析后按语句参数检查，无法解析时只报告语法错误
+ */
+pub fn lint_script(script: &Path, script_type: PluginScriptType, content: &str, files: &BTreeSet<String>) -> Vec<PluginLintIssue> {
+  let mut linter = Linter {
+    script,
+    script_type,
+    files,
+    issues: vec![],
+  };
+
+  match script_type {
+    PluginScriptType::Batch => {
+      for (i, line) in content.lines().enumerate() {
+        linter.batch_line(i + 1, line);
+      }
+    }
+    PluginScriptType::Pecmd => match parse(content) {
+      Ok(ast) => {
+        let lines = content.split('\n')
+          .scan(0, |start, raw| {
+            let line = (*start, raw.strip_suffix('\r').unwrap_or(raw));
+            *start += raw.len() + 1;
+            Some(line)
+          })
+          .collect::<Vec<_>>();
+        ast.walk(|s| linter.stmt(s, &lines));
+      }
+      Err(e) => linter.issues.push(PluginLintIssue {
+        rule: PluginLintRule::ParseError,
+        severity: PluginLintSeverity::Error,
+        script: script.to_path_buf(),
+        line: e.span.line,
+        column: e.span.column,
+        message: e.message,
+      }),
+    },
+  }
+  linter.issues
+}
+
+impl<'a> PluginLoadSession<'a> {
+  /*
+   * 检查已解压但尚未放置的插件
+   * LocalBoost 插件目录中是原脚本，改名后的脚本已写到加载目录
+   */
+  pub async fn lint(&self) -> anyhow::Result<PluginLintReport> {
+    let normal = self.release.starts_with(self.config()?.release());
+    let mut files = archive_files(&self.release).await?;
+    files.extend(self.scripts.iter().map(|s| normalize(&s.mangled_name())));
+    let mut report = PluginLintReport::default();
+    for script in &self.scripts {
+      // 普通插件放置前脚本仍在解压目录中
+      let path = match normal {
+        true => self.release.join(script.mangled_name()),
+        false => script.path_mangled.clone(),
+      };
+      let content = fs::read(&path).await?;
+      report.issues.append(&mut lint_script(
+        Path::new(&script.original_name()),
+        script.script_type,
+        &String::from_utf8_lossy(&content),
+        &files,
+      ));
+    }
+    Ok(report)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use tokio::fs;
+  use crate::testing::touch;
+
+  use super::{PluginLintReport, PluginLintRule, PluginLintSeverity};
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    touch(&root.join("Chrome/chrome.exe"), "").await?;
+    touch(&root.join("a b/x.exe"), "").await?;
+    touch(&root.join("setup.cmd"), &[
+      "@echo off",
+      "rem format C: in a comment is fine",
+      "copy X:\\Tools\\a.exe %~dp0Chrome",
+      "start \"\" \"%~dp0Chrome\\chrome.exe\"",
+      "start %~dp0Chrome\\missing.exe",
+      "%ProgramFiles%\\Edgeless\\Chrome\\chrome.exe --x",
+      "reg delete HKLM\\SYSTEM /f",
+      "reg delete HKLM\\SOFTWARE\\Chrome /f",
+      "diskpart /s a.txt",
+      "copy a.txt %SystemRoot%\\System32",
+      "set P=%ProgramFiles%\\Edgeless",
+      "copy İ.txt %~dp0Chrome\\chrome.exe",
+    ].join("\r\n")).await?;
+    touch(&root.join("Chrome.wcs"), &[
+      "// Chrome",
+      "EXEC =%ProgramFiles%\\Edgeless\\Chrome\\chrome.exe",
+      "LINK %Desktop%\\Chrome,%CurDir%\\Chrome\\chrome.exe",
+      "EXEC \"%CurDir%\\a b\\x.exe\"",
+      "插件 X:\\a",
+      "IFEX %CurDir%\\Chrome,EXEC !%CurDir%\\Chrome\\missing.exe",
+    ].join("\r\n")).await?;
+    touch(&root.join("Broken.wcs"), "EXEC X:\\a.exe\r\nIFEX a,{").await?;
+
+    let report = PluginLintReport::lint_dir(root).await?;
+    let found = report.issues.iter()
+      .map(|i| (i.script.to_string_lossy().to_string(), i.line, i.rule))
+      .collect::<Vec<_>>();
+    let has = |script: &str, line: usize, rule: PluginLintRule| found.contains(&(script.to_string(), line, rule));
+
+    assert!(has("Broken.wcs", 2, PluginLintRule::ParseError));
+    assert!(!has("Broken.wcs", 1, PluginLintRule::HardcodedDrive));
+    assert!(has("Chrome.wcs", 5, PluginLintRule::HardcodedDrive));
+    assert!(has("Chrome.wcs", 6, PluginLintRule::MissingFile));
+    assert!(!has("Chrome.wcs", 4, PluginLintRule::UnquotedSpace));
+    assert!(has("Chrome.wcs", 2, PluginLintRule::UnquotedSpace));
+    assert!(!has("Chrome.wcs", 3, PluginLintRule::MissingFile));
+    assert!(has("setup.cmd", 3, PluginLintRule::HardcodedDrive));
+    assert!(!has("setup.cmd", 2, PluginLintRule::DangerousCommand));
+    assert!(!has("setup.cmd", 4, PluginLintRule::MissingFile));
+    assert!(has("setup.cmd", 5, PluginLintRule::MissingFile));
+    assert!(has("setup.cmd", 6, PluginLintRule::UnquotedSpace));
+    assert!(!has("setup.cmd", 6, PluginLintRule::AbsolutePath));
+    assert!(has("setup.cmd", 7, PluginLintRule::DangerousCommand));
+    assert!(!has("setup.cmd", 8, PluginLintRule::DangerousCommand));
+    assert!(has("setup.cmd", 9, PluginLintRule::DangerousCommand));
+    assert!(has("setup.cmd", 10, PluginLintRule::AbsolutePath));
+    assert!(!has("setup.cmd", 11, PluginLintRule::UnquotedSpace));
+    assert!(!has("setup.cmd", 12, PluginLintRule::MissingFile));
+    assert!(report.has_errors());
+    assert_eq!(found.len(), 10, "{}", report);
+
+    let drives = report.issues.iter()
+      .filter(|i| i.rule == PluginLintRule::HardcodedDrive)
+      .map(|i| (i.script.to_string_lossy().to_string(), i.column, i.severity))
+      .collect::<Vec<_>>();
+    assert!(drives.contains(&("setup.cmd".to_string(), 6, PluginLintSeverity::Warning)));
+    // 列按字符计
+    assert!(drives.contains(&("Chrome.wcs".to_string(), 4, PluginLintSeverity::Warning)));
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()?)?;
+    assert_eq!(json["issues"][0]["rule"], "parse-error");
+    assert!(report.to_string().contains("setup.cmd:7:1: error[dangerous-command]"));
+
+    Ok(())
+  }
+}
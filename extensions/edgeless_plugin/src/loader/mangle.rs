@@ -0,0 +1,223 @@
+use std::path::Path;
+use uuid::Uuid;
+use crate::found::PluginEntry;
+use super::{PluginLoadSession, PluginScriptEntry, PluginScriptType};
+
+use anyhow::anyhow;
+use log::info;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/*
+ * 根目录脚本的改名规则：`{原文件名主干}~{插件}~{会话}.{扩展名}`
+ * 如 `setup.cmd` -> `setup~Chrome_90.0_Cno~<uuid>.cmd`
+ * 插件取文件名主干，会话取 `mangle_id`，不同插件或多次加载的同名脚本不会互相覆盖
+ */
+const SEPARATOR: char = '~';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginMangledName {
+  pub original: String,
+  pub plugin: String,
+  pub session: Uuid,
+}
+
+// 去掉分隔符以及 cmd 中有特殊含义的字符
+fn sanitize(s: &str) -> String {
+  s.chars()
+    .map(|c| match c {
+      SEPARATOR | ' ' | '&' | '^' | '%' | '!' | '(' | ')' | '=' | ';' | ',' => '-',
+      c => c,
+    })
+    .collect()
+}
+
+pub fn plugin_key(entry: &PluginEntry) -> String {
+  let stem = entry.path.file_stem()
+    .map(|s| s.to_string_lossy().to_string())
+    .unwrap_or_default();
+  sanitize(&stem)
+}
+
+// 普通插件的解压目录名 `{插件文件名主干}_{会话}`，避免不同插件或多次加载互相覆盖
+pub fn mangle_release_name(entry: &PluginEntry, session: &Uuid) -> String {
+  let stem = entry.path.file_stem()
+    .map(|s| s.to_string_lossy().to_string())
+    .unwrap_or_default();
+  format!("{}_{}", stem, session.to_simple())
+}
+
+pub fn mangle_script_name(name: &str, plugin: &str, session: &Uuid) -> String {
+  let (stem, ext) = match name.rfind('.') {
+    Some(i) if i > 0 => (&name[..i], &name[i..]),
+    _ => (name, ""),
+  };
+  format!("{}{}{}{}{}{}", stem, SEPARATOR, sanitize(plugin), SEPARATOR, session.to_simple(), ext)
+}
+
+pub fn demangle_script_name(name: &str) -> Option<PluginMangledName> {
+  let (body, ext) = match name.rfind('.') {
+    Some(i) if i > 0 => (&name[..i], &name[i..]),
+    _ => (name, ""),
+  };
+  let mut parts = body.rsplitn(3, SEPARATOR);
+  let session = Uuid::parse_str(parts.next()?).ok()?;
+  let plugin = parts.next()?.to_string();
+  let stem = parts.next()?;
+  Some(PluginMangledName {
+    original: format!("{}{}", stem, ext),
+    plugin,
+    session,
+  })
+}
+
+// cmd 与 PECMD 中可作为文件名一部分的字节，非 ASCII 一律视为文件名
+fn is_name_byte(b: u8) -> bool {
+  b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'~' | b'$' | b'#' | b'@') || b >= 0x80
+}
+
+fn is_boundary_before(content: &[u8], at: usize) -> bool {
+  if at == 0 || !is_name_byte(content[at - 1]) {
+    return true;
+  }
+  // `%~dp0setup.cmd`
+  at >= 5 && content[at - 5..at].eq_ignore_ascii_case(b"%~dp0")
+}
+
+fn is_boundary_after(content: &[u8], at: usize) -> bool {
+  at >= content.len() || !is_name_byte(content[at])
+}
+
+/*
+ * 把脚本中对同一插件其他根目录脚本的引用改为新文件名
+ * 按字节处理以兼容 GBK 编码的批处理，ASCII 部分不区分大小写
+ */
+pub fn rewrite_script_refs(content: &[u8], renames: &[(String, String)]) -> Vec<u8> {
+  let mut out = Vec::with_capacity(content.len());
+  let mut i = 0;
+  'outer: while i < content.len() {
+    if is_boundary_before(content, i) {
+      for (from, to) in renames {
+        let from = from.as_bytes();
+        let end = i + from.len();
+        if !from.is_empty()
+          && end <= content.len()
+          && content[i..end].eq_ignore_ascii_case(from)
+          && is_boundary_after(content, end)
+        {
+          out.extend_from_slice(to.as_bytes());
+          i = end;
+          continue 'outer;
+        }
+      }
+    }
+    out.push(content[i]);
+    i += 1;
+  }
+  out
+}
+
+impl PluginScriptEntry {
+  // 改名前的文件名，用于日志与报告
+  pub fn original_name(&self) -> String {
+    self.path_original.file_name()
+      .map(|s| s.to_string_lossy().to_string())
+      .unwrap_or_default()
+  }
+
+  pub fn mangled_name(&self) -> String {
+    self.path_mangled.file_name()
+      .map(|s| s.to_string_lossy().to_string())
+      .unwrap_or_default()
+  }
+}
+
+// 改名映射，`(原文件名, 新文件名)`
+pub fn script_renames(scripts: &[PluginScriptEntry]) -> Vec<(String, String)> {
+  scripts.iter()
+    .map(|s| (s.original_name(), s.mangled_name()))
+    .collect()
+}
+
+impl<'a> PluginLoadSession<'a> {
+  /*
+   * 找出 `dir` 根目录下的脚本，改名并改写相互引用后写到 `to`
+   * `to` 与 `dir` 相同时删除原文件；返回的 `path_mangled` 位于加载目录
+   */
+  pub(crate) async fn mangle_scripts(&self, dir: &Path, to: &Path) -> anyhow::Result<Vec<PluginScriptEntry>> {
+    let config = self.config()?;
+    let plugin = plugin_key(self.entry);
+
+    let mut scripts = vec![];
+    let mut iter = fs::read_dir(dir).await?;
+    while let Some(f) = iter.next_entry().await? {
+      let path = f.path();
+      if !f.file_type().await?.is_file() {
+        continue;
+      }
+      if let Some(script_type) = PluginScriptType::from_path(&path) {
+        let name = mangle_script_name(&f.file_name().to_string_lossy(), &plugin, &config.mangle_id);
+        scripts.push(PluginScriptEntry {
+          script_type,
+          path_mangled: config.dest.join(name),
+          path_original: path,
+        });
+      }
+    }
+    scripts.sort_by(|a, b| a.path_original.cmp(&b.path_original));
+
+    let renames = script_renames(&scripts);
+    for script in &scripts {
+      let content = fs::read(&script.path_original).await?;
+      let target = to.join(script.mangled_name());
+      // 直接写入加载目录时不覆盖同名插件已放置的脚本
+      let mut f = match fs::OpenOptions::new().write(true).create_new(true).open(&target).await {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+          return Err(anyhow!("script {:?} already exists, refuse to place {:?}", target, self.entry.path));
+        }
+        Err(e) => return Err(e.into()),
+      };
+      f.write_all(&rewrite_script_refs(&content, &renames)).await?;
+      f.flush().await?;
+      if to == dir {
+        fs::remove_file(&script.path_original).await?;
+      }
+    }
+    info!("mangled scripts {:?}", renames);
+    Ok(scripts)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use uuid::Uuid;
+
+  use super::{demangle_script_name, mangle_script_name, rewrite_script_refs};
+
+  #[test]
+  fn it_works() {
+    let session = Uuid::from_u128(0x1234);
+    let name = mangle_script_name("setup.cmd", "Chrome_90.0_Cno", &session);
+    assert_eq!(name, "setup~Chrome_90.0_Cno~00000000000000000000000000001234.cmd");
+    let other = mangle_script_name("setup.cmd", "Firefox_1.0_Cno", &session);
+    assert_ne!(name, other);
+
+    let back = demangle_script_name(&name).unwrap();
+    assert_eq!(back.original, "setup.cmd");
+    assert_eq!(back.plugin, "Chrome_90.0_Cno");
+    assert_eq!(back.session, session);
+    let odd = mangle_script_name("a~b.wcs", "My Plugin~x", &session);
+    assert_eq!(demangle_script_name(&odd).unwrap().original, "a~b.wcs");
+    assert_eq!(demangle_script_name(&odd).unwrap().plugin, "My-Plugin-x");
+    assert!(demangle_script_name("setup.cmd").is_none());
+
+    let renames = vec![
+      ("setup.cmd".to_string(), "setup~A~1.cmd".to_string()),
+      ("b.wcs".to_string(), "b~A~1.wcs".to_string()),
+    ];
+    let script = b"call \"%~dp0SETUP.cmd\"\r\ncall setup.cmd&LOAD b.wcs\r\nresetup.cmd setup.cmd.bak %CurDir%\\b.wcs\xb2\xe5";
+    let expected = b"call \"%~dp0setup~A~1.cmd\"\r\ncall setup~A~1.cmd&LOAD b~A~1.wcs\r\nresetup.cmd setup.cmd.bak %CurDir%\\b.wcs\xb2\xe5";
+    assert_eq!(rewrite_script_refs(script, &renames), expected.to_vec());
+  }
+}
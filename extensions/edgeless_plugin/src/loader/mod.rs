@@ -1,191 +1,800 @@
-#![allow(unused_imports)]
-
-use anyhow::anyhow;
-use tokio::fs::{self, DirEntry};
-use crate::found::localboost::{BoostPluginEntry, BoostRepoEntries, BoostRepoEntry, BoostRepoPluginMap};
-use crate::found::{
-  PluginExtension,
-  PluginEntry,
-  PluginMetadata
-};
-use bindings_7z::SevenZip;
-use bindings_pecmd::Pecmd;
-use log::{error, warn, log};
-use lazy_static::lazy_static;
-use std::collections::HashMap;
-use std::os::windows::prelude::MetadataExt;
-use std::str::FromStr;
-use std::vec;
-use std::{env::var, path::PathBuf};
-use uuid::Uuid;
-
-use edgeless_utils::rand_uuid;
-
-#[derive(Debug, Clone, Copy)]
-pub enum PluginScriptType {
-  Batch,
-  Pecmd,
-}
-
-#[derive(Debug, Clone)]
-pub struct PluginScriptEntry {
-  pub script_type: PluginScriptType,
-  pub path_original: PathBuf,
-  pub path_mangled: PathBuf,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum PluginLoadState {
-  Pending,
-  Released,
-  Resolved,
-  Rejected,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum PluginLoadTarget {
-  Normal,
-  Localboost,
-  Disabled,
-}
-
-impl From<PluginExtension> for PluginLoadTarget {
-  fn from(ext: PluginExtension) -> Self {
-    match ext {
-      PluginExtension::Normal => PluginLoadTarget::Normal,
-      PluginExtension::Localboost => PluginLoadTarget::Localboost,
-      _ => PluginLoadTarget::Disabled,
-    }
-  }
-}
-
-#[derive(Debug, Clone)]
-pub struct PluginLoadConfig {
-  release: PathBuf,
-  dest: PathBuf,
-  mangle_id: Uuid,
-}
-
-impl PluginLoadConfig {
-  pub async fn default() -> anyhow::Result<Self> {
-    let dest = PathBuf::from_str(
-      &var("PROGRAMFILES")?
-    )?.join("Edgeless");
-
-    let release = dest.join("__release__");
-    fs::create_dir_all(
-      &release
-    ).await?;
-
-    Ok(Self {
-      release,
-      dest,
-      mangle_id: rand_uuid().await?,
-    })
-  }
-}
-
-
-#[derive(Debug, Clone)]
-pub struct PluginLoadSession<'a> {
-  pub entry: &'a PluginEntry,
-
-  pub target: PluginLoadTarget,
-  pub state: PluginLoadState,
-
-  pub config: Option<PluginLoadConfig>,
-
-  pub boostrepo: &'a BoostRepoPluginMap<'a>,
-
-  pub dest: PathBuf,
-  pub release: PathBuf,
-  pub scripts: Vec<PluginScriptEntry>,
-  pub depend_files: Vec<PathBuf>,
-  pub depend_dirs: Vec<PathBuf>,
-}
-
-impl<'a> PluginLoadSession<'a> {
-  pub fn new(entry: &'a PluginEntry, repo: &'a BoostRepoPluginMap) -> PluginLoadSession<'a> {
-    let target = entry.extension.clone()
-      .unwrap_or(PluginExtension::Disabled)
-      .into();
-    Self {
-      target,
-      entry,
-      config: None,
-      state: PluginLoadState::Pending,
-      dest: PathBuf::new(),
-      release: PathBuf::new(),
-      scripts: vec![],
-      depend_files: vec![],
-      depend_dirs: vec![],
-      boostrepo: repo,
-    }
-  }
-
-}
-
-impl<'a> PluginLoadSession<'a> {
-  pub fn with_config(&mut self, config: Option<PluginLoadConfig>) -> &mut Self {
-    self.config = config;
-    self
-  }
-
-  pub async fn release_as_localboost(&self, entry: &mut BoostRepoEntry, force: bool) -> anyhow::Result<bool> {
-    if let Some(m) = self.boostrepo.get(&self.entry.meta) {
-      if m.contains(entry) || !force {
-        return Ok(false);
-      }
-    }
-
-    let zip = SevenZip::new()?;
-    zip.
-    
-    todo!()
-  }
-
-  pub async fn link_as_localboost(&mut self) -> anyhow::Result<()> {
-    todo!()
-  }
-
-  pub async fn load_as_normal(&mut self) -> anyhow::Result<()> {
-    todo!()
-  }
-
-  pub async fn release_as_normal(&mut self) -> anyhow::Result<()> {
-    todo!()
-  }
-
-  pub async fn load(&mut self) -> anyhow::Result<()> {
-    todo!()
-  }
-}
-
-
-#[cfg(test)]
-mod tests {
-
-  use edgeless_core::found::ProfileEntry;
-  use crate::found::{PluginEntry, localboost::BoostRepoEntry};
-
-  use super::PluginLoadSession;
-
-  #[tokio::test]
-  async fn it_works() -> anyhow::Result<()> {
-    if let Some(profile) = ProfileEntry::find_last().await? {
-      let plugins = PluginEntry::from_profile(&profile).await?;
-      let lb = BoostRepoEntry::find().await?;
-      let lb = lb.get_plugins();
-      let session = plugins.iter()
-        .map(
-          |v| PluginLoadSession::new(
-            v, 
-            &lb
-          )
-        ).collect::<Vec<_>>();
-      println!("{:#?}", session);
-    }
-
-    Ok(())
-  }
-}
\ No newline at end of file
+#![allow(unused_imports)]
+pub mod script;
+pub mod link;
+pub mod plan;
+pub mod journal;
+pub mod schedule;
+pub mod config;
+pub mod mangle;
+pub mod lint;
+pub mod batch;
+
+pub use config::PluginLoadConfig;
+
+use anyhow::anyhow;
+use tokio::fs::{self, DirEntry};
+use crate::found::localboost::{BoostPluginEntry, BoostRepoEntries, BoostRepoEntry, BoostRepoPluginMap};
+use crate::found::{
+  PluginExtension,
+  PluginEntry,
+  PluginMetadata
+};
+use bindings_7z::SevenZipEvent;
+use bindings_pecmd::Pecmd;
+use tokio::sync::mpsc;
+use log::{info, error, warn, log};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::vec;
+use std::sync::Arc;
+use std::time::Instant;
+use std::path::{Path, PathBuf};
+
+use edgeless_core::options::define::PATH_PLUGIN_LB_RESOURCES;
+use async_recursion::async_recursion;
+
+use script::{
+  PluginDefaultScriptRunner,
+  PluginScriptContext,
+  PluginScriptOutput,
+  PluginScriptRunner,
+  ENV_PLUGIN,
+  ENV_PLUGIN_DIR,
+  ENV_PROFILE,
+  ENV_RELEASE_DIR,
+};
+use link::{link_tree, PluginFileSystem, PluginLinkEntry};
+
+#[derive(Debug, Clone, Copy)]
+pub enum PluginScriptType {
+  Batch,
+  Pecmd,
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginScriptEntry {
+  pub script_type: PluginScriptType,
+  pub path_original: PathBuf,
+  pub path_mangled: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginLoadState {
+  Pending,
+  Released,
+  Resolved,
+  Rejected,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PluginLoadTarget {
+  Normal,
+  Localboost,
+  Disabled,
+}
+
+impl From<PluginExtension> for PluginLoadTarget {
+  fn from(ext: PluginExtension) -> Self {
+    match ext {
+      PluginExtension::Normal => PluginLoadTarget::Normal,
+      PluginExtension::Localboost => PluginLoadTarget::Localboost,
+      _ => PluginLoadTarget::Disabled,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginLoadSession<'a> {
+  pub entry: &'a PluginEntry,
+
+  pub target: PluginLoadTarget,
+  pub state: PluginLoadState,
+
+  pub config: Option<PluginLoadConfig>,
+  pub runner: Option<Arc<dyn PluginScriptRunner>>,
+  pub filesystem: Option<Arc<dyn PluginFileSystem>>,
+
+  pub boostrepo: &'a BoostRepoPluginMap<'a>,
+
+  pub dest: PathBuf,
+  pub release: PathBuf,
+  pub scripts: Vec<PluginScriptEntry>,
+  pub depend_files: Vec<PathBuf>,
+  pub depend_dirs: Vec<PathBuf>,
+  pub links: Vec<PluginLinkEntry>,
+  // 与 `scripts` 按顺序对应，失败后的脚本不再执行
+  pub outputs: Vec<PluginScriptOutput>,
+  pub error: Option<String>,
+}
+
+impl<'a> PluginLoadSession<'a> {
+  pub fn new(entry: &'a PluginEntry, repo: &'a BoostRepoPluginMap) -> PluginLoadSession<'a> {
+    let target = entry.extension
+      .unwrap_or(PluginExtension::Disabled)
+      .into();
+    Self {
+      target,
+      entry,
+      config: None,
+      runner: None,
+      filesystem: None,
+      state: PluginLoadState::Pending,
+      dest: PathBuf::new(),
+      release: PathBuf::new(),
+      scripts: vec![],
+      depend_files: vec![],
+      depend_dirs: vec![],
+      links: vec![],
+      outputs: vec![],
+      boostrepo: repo,
+      error: None,
+    }
+  }
+
+}
+
+impl<'a> PluginLoadSession<'a> {
+  pub fn with_config(&mut self, config: Option<PluginLoadConfig>) -> &mut Self {
+    self.config = config;
+    self
+  }
+
+  pub fn with_runner(&mut self, runner: Arc<dyn PluginScriptRunner>) -> &mut Self {
+    self.runner = Some(runner);
+    self
+  }
+
+  fn config(&self) -> anyhow::Result<&PluginLoadConfig> {
+    self.config.as_ref().ok_or(anyhow!("no load config for {:?}", self.entry.path))
+  }
+
+  pub fn with_filesystem(&mut self, filesystem: Arc<dyn PluginFileSystem>) -> &mut Self {
+    self.filesystem = Some(filesystem);
+    self
+  }
+
+  async fn extract_to(&self, out: &Path, events: Option<mpsc::UnboundedSender<SevenZipEvent>>) -> anyhow::Result<()> {
+    let archive = self.config()?.archive()?;
+    fs::create_dir_all(out).await?;
+    archive.extract_with_events(&self.entry.path, out, events)
+      .await
+      .map_err(|e| anyhow!("failed to extract {:?}, {}", self.entry.path, e))?;
+    Ok(())
+  }
+
+  // 已释放到任一 BoostRepo 中的插件目录
+  pub fn find_localboost(&self) -> Option<&'a BoostPluginEntry> {
+    let meta = &self.entry.meta;
+    self.boostrepo.get(meta)?
+      .iter()
+      .flat_map(|r| r.plugins.iter())
+      .find(|p| &p.meta == meta)
+  }
+
+  /*
+   * 释放到指定的 BoostRepo，已释放过的插件（按元数据去重）在非 `force` 时跳过
+   * 先解压到临时目录再改名，中途失败不会留下不完整的插件目录
+   */
+  pub async fn release_as_localboost(&self, entry: &mut BoostRepoEntry, force: bool) -> anyhow::Result<bool> {
+    let released = self.find_localboost().is_some()
+      || entry.plugins.iter().any(|p| p.meta == self.entry.meta);
+    if released && !force {
+      info!("plugin {:?} is already in boostrepo, skip", self.entry.path);
+      return Ok(false);
+    }
+
+    let stem = self.entry.path.file_stem()
+      .ok_or(anyhow!("invalid path {:?}", self.entry.path))?;
+    let lb_path = entry.path.join(PATH_PLUGIN_LB_RESOURCES.as_path());
+    let target = lb_path.join(stem);
+    let temp = lb_path.join(format!("{}.tmp", stem.to_string_lossy()));
+
+    info!("release plugin {:?} to boostrepo {:?}", self.entry.path, target);
+    if temp.exists() {
+      fs::remove_dir_all(&temp).await?;
+    }
+    if let Err(e) = self.extract_to(&temp, None).await {
+      let _ = fs::remove_dir_all(&temp).await;
+      return Err(e);
+    }
+    if target.exists() {
+      fs::remove_dir_all(&target).await?;
+    }
+    fs::rename(&temp, &target).await?;
+
+    entry.plugins.retain(|p| p.meta != self.entry.meta);
+    entry.plugins.push(BoostPluginEntry {
+      path: target,
+      meta: self.entry.meta.clone(),
+      from: entry.path.to_path_buf(),
+    });
+    Ok(true)
+  }
+
+  // 把 BoostRepo 中的插件链接到加载目录，脚本复制过去以便执行
+  pub async fn link_as_localboost(&mut self) -> anyhow::Result<()> {
+    if self.state != PluginLoadState::Pending {
+      return Err(anyhow!("plugin {:?} is already {:?}", self.entry.path, self.state));
+    }
+
+    let plugin = self.find_localboost()
+      .ok_or(anyhow!("plugin {:?} is not in any boostrepo", self.entry.path))?;
+    let config = self.config()?;
+    self.entry.verify_trust(config.trust_store(), config.trust_policy()).await?;
+    let dest = config.dest.clone();
+    let filesystem = match &self.filesystem {
+      Some(f) => f.clone(),
+      None => self.config()?.link_strategy.filesystem(),
+    };
+
+    info!("link plugin {:?} from {:?}", self.entry.path, plugin.path);
+    let is_script = |p: &Path| p.is_file() && PluginScriptType::from_path(p).is_some();
+    link_tree(filesystem.as_ref(), &plugin.path, &dest, &is_script, &mut self.links).await?;
+
+    let scripts = self.mangle_scripts(&plugin.path, &dest).await?;
+    self.depend_files.extend(scripts.iter().map(|s| s.path_mangled.clone()));
+
+    self.release = plugin.path.clone();
+    self.dest = dest;
+    self.scripts = scripts;
+    self.state = PluginLoadState::Released;
+    Ok(())
+  }
+
+  // 解压到 `release` 下的独立目录，根目录下的脚本改名以免与其他插件冲突
+  pub async fn release_as_normal(&mut self) -> anyhow::Result<()> {
+    self.release_as_normal_with_events(None).await
+  }
+
+  // 解压进度发送到 `events`
+  pub async fn release_as_normal_with_events(&mut self, events: Option<mpsc::UnboundedSender<SevenZipEvent>>) -> anyhow::Result<()> {
+    if self.state != PluginLoadState::Pending {
+      return Err(anyhow!("plugin {:?} is already {:?}", self.entry.path, self.state));
+    }
+
+    let config = self.config()?;
+    self.entry.verify_trust(config.trust_store(), config.trust_policy()).await?;
+    let release = config.release.join(config.mangle(self.entry));
+    let temp = config.temp.join(format!("{}.tmp", config.mangle(self.entry)));
+    let dest = config.dest.clone();
+    let root = config.release.clone();
+
+    // 先解压到临时目录，`release` 下只有完整的插件
+    info!("release plugin {:?} to {:?}", self.entry.path, release);
+    self.release = release.clone();
+    if let Err(e) = self.extract_to(&temp, events).await {
+      let _ = fs::remove_dir_all(&temp).await;
+      return Err(e);
+    }
+    fs::create_dir_all(&root).await?;
+    fs::rename(&temp, &release).await?;
+
+    let scripts = self.mangle_scripts(&release, &release).await?;
+
+    self.release = release;
+    self.dest = dest;
+    self.scripts = scripts;
+    self.state = PluginLoadState::Released;
+    Ok(())
+  }
+
+  // 移动解压内容到加载目录，然后依次执行脚本
+  pub async fn load_as_normal(&mut self) -> anyhow::Result<()> {
+    self.place_as_normal().await?;
+    self.run_scripts().await
+  }
+
+  pub async fn place_as_normal(&mut self) -> anyhow::Result<()> {
+    if self.state != PluginLoadState::Released {
+      return Err(anyhow!("plugin {:?} is not released, {:?}", self.entry.path, self.state));
+    }
+
+    // LocalBoost 链接的插件目录在 BoostRepo 中，不能移走
+    if !self.release.starts_with(self.config()?.release()) {
+      return Err(anyhow!("plugin {:?} is not released as normal", self.entry.path));
+    }
+
+    // 同名插件（如不同分类文件夹中的同一插件）改名后的脚本相同，拒绝放置以免执行别人的脚本
+    if let Some(s) = self.scripts.iter().find(|s| s.path_mangled.exists()) {
+      return Err(anyhow!("script {:?} already exists, refuse to place {:?}", s.path_mangled, self.entry.path));
+    }
+
+    fs::create_dir_all(&self.dest).await?;
+    let (release, dest) = (self.release.clone(), self.dest.clone());
+    move_tree(&release, &dest, &mut self.depend_files, &mut self.depend_dirs).await?;
+    if let Err(e) = fs::remove_dir_all(&release).await {
+      warn!("failed to clean {:?}, {}", release, e);
+    }
+    Ok(())
+  }
+
+  pub async fn run_scripts(&mut self) -> anyhow::Result<()> {
+    let runner = match (&self.runner, self.config.as_ref().and_then(|c| c.runner())) {
+      (Some(r), _) | (None, Some(r)) => r.clone(),
+      (None, None) => Arc::new(PluginDefaultScriptRunner::default()),
+    };
+    let context = self.script_context();
+    let timeout = self.config.as_ref().and_then(|c| c.script_timeout());
+    let before = self.watch_snapshot().await;
+    let mut r = Ok(());
+    self.outputs.clear();
+    for script in &self.scripts {
+      let start = Instant::now();
+      let run = runner.run(script, &context);
+      let output = match timeout {
+        // 超时后丢弃 future，已启动的进程随之结束
+        Some(t) => tokio::time::timeout(t, run).await.unwrap_or_else(|_| Ok(PluginScriptOutput {
+          elapsed: start.elapsed(),
+          timed_out: true,
+          ..Default::default()
+        })),
+        None => run.await,
+      };
+      r = match output {
+        Ok(output) => {
+          info!("script {} finished, {}", script.original_name(), output);
+          let failed = output.state() == PluginLoadState::Rejected;
+          let e = anyhow!("script {} failed, {}", script.original_name(), output);
+          self.outputs.push(output);
+          if failed { Err(e) } else { Ok(()) }
+        }
+        Err(e) => Err(e),
+      };
+      if r.is_err() {
+        break;
+      }
+    }
+    self.record_created(&before).await;
+    r?;
+
+    self.state = PluginLoadState::Resolved;
+    Ok(())
+  }
+
+  // 脚本在加载目录中执行，并通过环境变量得知插件的位置
+  pub fn script_context(&self) -> PluginScriptContext {
+    let mut env = vec![
+      (ENV_PLUGIN.to_string(), self.entry.path.clone()),
+      (ENV_PLUGIN_DIR.to_string(), self.dest.clone()),
+      (ENV_RELEASE_DIR.to_string(), self.release.clone()),
+    ];
+    if let Some(p) = self.config.as_ref().and_then(|c| c.profile()) {
+      env.push((ENV_PROFILE.to_string(), p.to_path_buf()));
+    }
+    PluginScriptContext {
+      cwd: self.dest.clone(),
+      env,
+      files: self.depend_files.iter()
+        .chain(self.links.iter().map(|l| &l.path))
+        .cloned()
+        .collect(),
+    }
+  }
+
+  pub async fn load(&mut self) -> anyhow::Result<()> {
+    let r = match self.target {
+      PluginLoadTarget::Disabled => {
+        info!("plugin {:?} is disabled, skip", self.entry.path);
+        return Ok(());
+      }
+      PluginLoadTarget::Normal => self.load_normal().await,
+      PluginLoadTarget::Localboost => self.load_localboost().await,
+    };
+
+    if let Err(e) = &r {
+      self.reject(e).await;
+    }
+    r
+  }
+
+  pub(crate) async fn reject(&mut self, e: &anyhow::Error) {
+    error!("failed to load plugin {:?}, {}", self.entry.path, e);
+    self.state = PluginLoadState::Rejected;
+    self.error = Some(e.to_string());
+    self.rollback().await;
+  }
+
+  // 不在任何 BoostRepo 中的插件按普通插件加载
+  async fn load_localboost(&mut self) -> anyhow::Result<()> {
+    if self.state == PluginLoadState::Pending {
+      if self.find_localboost().is_none() {
+        warn!("plugin {:?} is not in any boostrepo, load as normal", self.entry.path);
+        return self.load_normal().await;
+      }
+      self.link_as_localboost().await?;
+    }
+    if self.state != PluginLoadState::Released {
+      return Err(anyhow!("plugin {:?} is not released, {:?}", self.entry.path, self.state));
+    }
+    self.run_scripts().await
+  }
+
+  async fn load_normal(&mut self) -> anyhow::Result<()> {
+    if self.state == PluginLoadState::Pending {
+      self.release_as_normal().await?;
+    }
+    self.load_as_normal().await
+  }
+}
+
+/*
+ * 合并移动目录，已存在的文件保留不覆盖（同 7z 的 `-aos`）
+ * 移动的文件与新建的目录记录到 `files` 与 `dirs`
+ */
+#[async_recursion]
+async fn move_tree(src: &Path, dst: &Path, files: &mut Vec<PathBuf>, dirs: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+  let mut iter = fs::read_dir(src).await?;
+  while let Some(f) = iter.next_entry().await? {
+    let from = f.path();
+    let to = dst.join(f.file_name());
+    if f.file_type().await?.is_dir() {
+      // 并发加载时其他插件可能已创建同名目录
+      match fs::create_dir(&to).await {
+        Ok(_) => dirs.push(to.clone()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e.into()),
+      }
+      move_tree(&from, &to, files, dirs).await?;
+    } else if to.exists() {
+      warn!("{:?} already exists, skip", to);
+    } else {
+      fs::rename(&from, &to).await?;
+      files.push(to);
+    }
+  }
+  Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+
+  use std::path::Path;
+  use std::sync::{Arc, Mutex};
+  use edgeless_core::found::{ProfileEntry, ProfileType};
+  use tokio::fs;
+  use crate::found::{PluginEntry, localboost::{BoostRepoEntries, BoostRepoEntry}};
+  use crate::testing::touch;
+  #[cfg(unix)]
+  use crate::testing::fake_seven_zip;
+
+  use super::script::{
+    PluginRecordingScriptRunner,
+    PluginScriptContext,
+    PluginScriptFuture,
+    PluginScriptOutput,
+    PluginScriptRunner,
+    ENV_PLUGIN_DIR,
+  };
+  use super::link::PluginLinkKind;
+  use super::{PluginLoadConfig, PluginLoadSession, PluginLoadState, PluginScriptEntry};
+
+  #[derive(Debug, Default)]
+  struct FakeRunner {
+    ran: Mutex<Vec<String>>,
+    // 模拟脚本创建的快捷方式
+    shortcuts: Option<std::path::PathBuf>,
+  }
+
+  impl PluginScriptRunner for FakeRunner {
+    fn run<'a>(&'a self, script: &'a PluginScriptEntry, context: &'a PluginScriptContext) -> PluginScriptFuture<'a> {
+      Box::pin(async move {
+        assert!(script.path_mangled.starts_with(&context.cwd));
+        let name = script.original_name();
+        self.ran.lock().unwrap().push(name.clone());
+        if let Some(dir) = &self.shortcuts {
+          std::fs::create_dir_all(dir.join("Tools"))?;
+          std::fs::write(dir.join("Tools").join(format!("{}.lnk", name)), "")?;
+        }
+        let code = if name.starts_with("fail") { 1 } else { 0 };
+        Ok(PluginScriptOutput {
+          code: Some(code),
+          stderr: format!("{} says no", name),
+          ..Default::default()
+        })
+      })
+    }
+  }
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    if let Some(profile) = ProfileEntry::find_last().await? {
+      let plugins = PluginEntry::from_profile(&profile).await?;
+      let lb = BoostRepoEntry::find().await?;
+      let lb = lb.get_plugins();
+      let session = plugins.iter()
+        .map(
+          |v| PluginLoadSession::new(
+            v, 
+            &lb
+          )
+        ).collect::<Vec<_>>();
+      println!("{:#?}", session);
+    }
+
+    Ok(())
+  }
+
+  // 内置后端直接解压真实的 zip，不需要 7z
+  #[tokio::test]
+  async fn load_native() -> anyhow::Result<()> {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+    use super::config::PluginArchiveBackend;
+
+    let dir = tempfile::tempdir()?;
+    let res = dir.path().join("Resource");
+    fs::create_dir_all(&res).await?;
+    let path = res.join("Chrome_90.0_Cno.7z");
+    let mut zip = ZipWriter::new(std::fs::File::create(&path)?);
+    zip.start_file("Chrome/bin/chrome.exe", FileOptions::default())?;
+    zip.write_all(b"chrome\n")?;
+    zip.start_file("a.cmd", FileOptions::default())?;
+    zip.write_all(b"\n")?;
+    zip.finish()?;
+    touch(&res.join("Broken_1.0_Cno.7z"), "not an archive").await?;
+
+    let dest = dir.path().join("Edgeless");
+    let config = PluginLoadConfig::builder()
+      .dest(dest.clone())
+      .seven_zip(dir.path().join("missing-7z"))
+      .archive_backend(PluginArchiveBackend::Native)
+      .build()
+      .await?;
+    let runner = Arc::new(FakeRunner::default());
+    let lb = Default::default();
+
+    let chrome = PluginEntry::new(path).await?;
+    let mut session = PluginLoadSession::new(&chrome, &lb);
+    session.with_config(Some(config.clone())).with_runner(runner.clone());
+    session.load().await?;
+    assert_eq!(session.state, PluginLoadState::Resolved);
+    assert_eq!(*runner.ran.lock().unwrap(), vec!["a.cmd".to_string()]);
+    assert_eq!(fs::read_to_string(dest.join("Chrome/bin/chrome.exe")).await?, "chrome\n");
+
+    let broken = PluginEntry::new(res.join("Broken_1.0_Cno.7z")).await?;
+    let mut session = PluginLoadSession::new(&broken, &lb);
+    session.with_config(Some(config)).with_runner(runner.clone());
+    assert!(session.load().await.is_err());
+    assert!(session.error.as_deref().unwrap().contains("cannot open as archive"));
+
+    Ok(())
+  }
+
+  // 两个同名插件改名后的脚本相同，后放置的被拒绝，已放置的脚本保持原样
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn place_conflict() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let exe = fake_seven_zip(dir.path()).await?;
+    let res = dir.path().join("Resource");
+    touch(&res.join("Tools").join("Dup_1.0_Cno.7z"), "echo tools > a.cmd
+").await?;
+    touch(&res.join("Other").join("Dup_1.0_Cno.7z"), "echo other > a.cmd
+").await?;
+
+    let dest = dir.path().join("Edgeless");
+    let config = PluginLoadConfig::new(dest.clone()).await?.with_seven_zip(exe);
+    let runner = Arc::new(FakeRunner::default());
+    let lb = Default::default();
+
+    let tools = PluginEntry::new(res.join("Tools").join("Dup_1.0_Cno.7z")).await?;
+    let mut session = PluginLoadSession::new(&tools, &lb);
+    session.with_config(Some(config.clone())).with_runner(runner.clone());
+    session.load().await?;
+    let script = session.scripts[0].path_mangled.clone();
+
+    let other = PluginEntry::new(res.join("Other").join("Dup_1.0_Cno.7z")).await?;
+    let mut session = PluginLoadSession::new(&other, &lb);
+    session.with_config(Some(config.clone())).with_runner(runner.clone());
+    assert!(session.load().await.is_err());
+    assert!(session.error.as_deref().unwrap().contains("refuse to place"));
+    assert_eq!(fs::read_to_string(&script).await?, "tools\n");
+    assert_eq!(runner.ran.lock().unwrap().len(), 1);
+
+    Ok(())
+  }
+
+  // 用 shell 脚本模拟 7z，"压缩包"本身是在输出目录中执行的脚本
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn load_normal() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let exe = fake_seven_zip(dir.path()).await?;
+
+    let res = dir.path().join("Resource");
+    touch(&res.join("Chrome_90.0_Cno.7z"), "mkdir -p Chrome/bin\necho chrome > Chrome/bin/chrome.exe\necho > b.wcs\necho > a.cmd\n").await?;
+    touch(&res.join("Other_1.0_Cno.7z"), "echo 'call b.wcs' > a.cmd\necho > b.wcs\n").await?;
+    touch(&res.join("Broken_1.0_Cno.7z"), "exit 2\n").await?;
+    touch(&res.join("Failing_1.0_Cno.7z"), "echo > fail.cmd\n").await?;
+    touch(&res.join("Disabled_1.0_Cno.7zf"), "").await?;
+
+    let dest = dir.path().join("Edgeless");
+    touch(&dest.join("Chrome").join("keep.txt"), "").await?;
+    let config = PluginLoadConfig::new(dest.clone()).await?.with_seven_zip(exe);
+    let runner = Arc::new(FakeRunner::default());
+    let lb = Default::default();
+
+    let chrome = PluginEntry::new(res.join("Chrome_90.0_Cno.7z")).await?;
+    let mut session = PluginLoadSession::new(&chrome, &lb);
+    session.with_config(Some(config.clone())).with_runner(runner.clone());
+    session.load().await?;
+    assert_eq!(session.state, PluginLoadState::Resolved);
+    assert_eq!(*runner.ran.lock().unwrap(), vec!["a.cmd".to_string(), "b.wcs".to_string()]);
+    assert_eq!(fs::read_to_string(dest.join("Chrome/bin/chrome.exe")).await?, "chrome\n");
+    assert!(session.depend_files.contains(&dest.join("Chrome/bin/chrome.exe")));
+    assert_eq!(session.depend_dirs, vec![dest.join("Chrome/bin")]);
+    assert!(!session.release.exists());
+    assert!(!dest.join("a.cmd").exists());
+    let chrome_a = session.scripts[0].path_mangled.clone();
+
+    // 同名脚本各自改名，引用同插件的脚本时改写为新文件名
+    let other = PluginEntry::new(res.join("Other_1.0_Cno.7z")).await?;
+    let mut session = PluginLoadSession::new(&other, &lb);
+    session.with_config(Some(config.clone())).with_runner(runner.clone());
+    session.load().await?;
+    let other_a = session.scripts[0].path_mangled.clone();
+    let other_b = session.scripts[1].mangled_name();
+    assert_ne!(chrome_a, other_a);
+    assert_eq!(fs::read_to_string(&chrome_a).await?, "\n");
+    assert_eq!(fs::read_to_string(&other_a).await?, format!("call {}\n", other_b));
+    assert!(session.depend_files.contains(&other_a));
+
+    let broken = PluginEntry::new(res.join("Broken_1.0_Cno.7z")).await?;
+    let mut session = PluginLoadSession::new(&broken, &lb);
+    session.with_config(Some(config.clone())).with_runner(runner.clone());
+    assert!(session.load().await.is_err());
+    assert_eq!(session.state, PluginLoadState::Rejected);
+    assert!(session.error.is_some());
+
+    let failing = PluginEntry::new(res.join("Failing_1.0_Cno.7z")).await?;
+    let mut session = PluginLoadSession::new(&failing, &lb);
+    session.with_config(Some(config.clone())).with_runner(runner.clone());
+    assert!(session.load().await.is_err());
+    assert_eq!(session.state, PluginLoadState::Rejected);
+    assert_eq!(session.error.as_deref(), Some("script fail.cmd failed, exit code 1, fail.cmd says no"));
+    assert_eq!(session.outputs.len(), 1);
+
+    // 超时的脚本视为失败，环境变量指向加载目录
+    let slow = Arc::new(PluginRecordingScriptRunner::new().with_delay(std::time::Duration::from_secs(5)));
+    let timed = PluginLoadConfig::builder()
+      .dest(dest.clone())
+      .seven_zip(config.seven_zip().to_path_buf())
+      .script_timeout(std::time::Duration::from_millis(20))
+      .build()
+      .await?;
+    let mut session = PluginLoadSession::new(&other, &lb);
+    session.with_config(Some(timed)).with_runner(slow.clone());
+    assert!(session.load().await.is_err());
+    assert!(session.outputs[0].timed_out);
+    assert!(session.error.as_deref().unwrap().contains("timed out"));
+    let env = &slow.records()[0].context.env;
+    assert!(env.contains(&(ENV_PLUGIN_DIR.to_string(), dest.clone())));
+
+    let disabled = PluginEntry::new(res.join("Disabled_1.0_Cno.7zf")).await?;
+    let mut session = PluginLoadSession::new(&disabled, &lb);
+    session.with_config(Some(config));
+    session.load().await?;
+    assert_eq!(session.state, PluginLoadState::Pending);
+
+    Ok(())
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn load_localboost() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let exe = fake_seven_zip(dir.path()).await?;
+
+    let res = dir.path().join("Edgeless").join("Resource");
+    touch(&res.join("Office_1.0_Cno.7zl"), "mkdir -p Office\necho office > Office/office.exe\necho > office.wcs\n").await?;
+    touch(&res.join("Lonely_1.0_Cno.7zl"), "mkdir -p Lonely\n").await?;
+    let office = PluginEntry::new(res.join("Office_1.0_Cno.7zl")).await?;
+    let lonely = PluginEntry::new(res.join("Lonely_1.0_Cno.7zl")).await?;
+
+    let repo_path = dir.path().join("Stick").join("Edgeless");
+    fs::create_dir_all(repo_path.join("BoostRepo")).await?;
+    let mut repo = BoostRepoEntry::new(ProfileEntry::from_path(repo_path.clone(), ProfileType::BoostRepo)).await?;
+
+    let dest = dir.path().join("Target");
+    let config = PluginLoadConfig::new(dest.clone()).await?.with_seven_zip(exe);
+    let runner = Arc::new(FakeRunner::default());
+
+    let empty = Default::default();
+    let mut session = PluginLoadSession::new(&office, &empty);
+    session.with_config(Some(config.clone()));
+    assert!(session.release_as_localboost(&mut repo, false).await?);
+    assert!(!session.release_as_localboost(&mut repo, false).await?);
+    assert!(session.release_as_localboost(&mut repo, true).await?);
+    assert_eq!(repo.plugins.len(), 1);
+    assert!(repo_path.join("BoostRepo/Office_1.0_Cno/Office/office.exe").exists());
+
+    let repos = BoostRepoEntries(vec![repo]);
+    let map = repos.get_plugins();
+    let mut session = PluginLoadSession::new(&office, &map);
+    session.with_config(Some(config.clone())).with_runner(runner.clone());
+    assert!(!session.release_as_localboost(&mut repos[0].clone(), false).await?);
+    session.load().await?;
+    assert_eq!(session.state, PluginLoadState::Resolved);
+    assert_eq!(*runner.ran.lock().unwrap(), vec!["office.wcs".to_string()]);
+    assert!(session.lint().await?.issues.is_empty());
+    assert_eq!(session.links.len(), 1);
+    assert_eq!(session.links[0].kind, PluginLinkKind::DirLink);
+    assert!(fs::symlink_metadata(dest.join("Office")).await?.file_type().is_symlink());
+    assert_eq!(fs::read_to_string(dest.join("Office/office.exe")).await?, "office\n");
+
+    // 不在 BoostRepo 中则按普通插件加载
+    let mut session = PluginLoadSession::new(&lonely, &map);
+    session.with_config(Some(config)).with_runner(runner.clone());
+    session.load().await?;
+    assert_eq!(session.state, PluginLoadState::Resolved);
+    assert!(session.links.is_empty());
+    assert!(dest.join("Lonely").is_dir());
+
+    Ok(())
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn rollback_and_unload() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let exe = fake_seven_zip(dir.path()).await?;
+
+    let res = dir.path().join("Resource");
+    touch(&res.join("Chrome_90.0_Cno.7z"), "mkdir -p Chrome/bin Shared\necho > Chrome/bin/chrome.exe\necho > Shared/chrome.dll\necho > chrome.cmd\n").await?;
+    touch(&res.join("Failing_1.0_Cno.7z"), "mkdir -p Failing\necho > Failing/app.exe\necho > fail.cmd\n").await?;
+    touch(&res.join("Half_1.0_Cno.7z"), "echo > half.exe\nexit 2\n").await?;
+    for n in ["Chrome_90.0_Cno", "Failing_1.0_Cno"] {
+      touch(&res.join(format!("{}.json", n)), r#"{ "watch": true }"#).await?;
+    }
+
+    let dest = dir.path().join("Edgeless");
+    let desktop = dir.path().join("Desktop");
+    touch(&dest.join("Shared").join("other.dll"), "").await?;
+    touch(&desktop.join("mine.lnk"), "").await?;
+    let config = PluginLoadConfig::new(dest.clone()).await?
+      .with_seven_zip(exe)
+      .with_watch_dirs(vec![desktop.clone()]);
+    let runner = Arc::new(FakeRunner {
+      shortcuts: Some(desktop.clone()),
+      ..Default::default()
+    });
+    let lb = Default::default();
+
+    let chrome = PluginEntry::new(res.join("Chrome_90.0_Cno.7z")).await?;
+    let mut session = PluginLoadSession::new(&chrome, &lb);
+    session.with_config(Some(config.clone())).with_runner(runner.clone());
+    for _ in 0..2 {
+      session.load().await?;
+      assert_eq!(session.state, PluginLoadState::Resolved);
+      assert!(desktop.join("Tools/chrome.cmd.lnk").exists());
+      assert!(session.journal().files.contains(&desktop.join("Tools/chrome.cmd.lnk")));
+
+      session.unload().await?;
+      assert_eq!(session.state, PluginLoadState::Pending);
+      assert!(!dest.join("Chrome").exists());
+      assert!(!dest.join("chrome.cmd").exists());
+      assert!(!dest.join("Shared/chrome.dll").exists());
+      assert!(dest.join("Shared/other.dll").exists());
+      assert!(!desktop.join("Tools").exists());
+      assert!(desktop.join("mine.lnk").exists());
+    }
+
+    let failing = PluginEntry::new(res.join("Failing_1.0_Cno.7z")).await?;
+    let mut session = PluginLoadSession::new(&failing, &lb);
+    session.with_config(Some(config.clone())).with_runner(runner.clone());
+    assert!(session.load().await.is_err());
+    assert_eq!(session.state, PluginLoadState::Rejected);
+    assert!(!dest.join("Failing").exists());
+    assert!(!desktop.join("Tools").exists());
+
+    let half = PluginEntry::new(res.join("Half_1.0_Cno.7z")).await?;
+    let mut session = PluginLoadSession::new(&half, &lb);
+    session.with_config(Some(config.clone()));
+    assert!(session.load().await.is_err());
+    let mut left = fs::read_dir(config.release()).await?;
+    assert!(left.next_entry().await?.is_none());
+
+    Ok(())
+  }
+}
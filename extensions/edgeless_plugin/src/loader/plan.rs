@@ -0,0 +1,650 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use edgeless_core::found::ProfileEntry;
+use edgeless_core::options::PluginTrustPolicy;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::found::graph::PluginGraph;
+use crate::found::localboost::{BoostRepoEntries, BoostRepoPluginMap};
+use crate::found::scan::PluginScanOptions;
+use crate::found::trust::PluginTrustStore;
+use crate::found::{PluginEntry, PluginExtension};
+use super::config::RELEASE_DIR_NAME;
+use super::mangle::mangle_release_name;
+use super::script::PluginScriptRunner;
+use super::{PluginLoadConfig, PluginLoadSession, PluginLoadState, PluginLoadTarget, PluginScriptType};
+
+use anyhow::anyhow;
+use log::{info, warn};
+use tokio::fs;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", content = "detail", rename_all = "snake_case")]
+pub enum PluginSkipReason {
+  Disabled,
+  // 信任策略为 enforce 时未通过签名校验
+  Untrusted(String),
+  // 同名插件中只加载版本最高的一个
+  Superseded(PathBuf),
+  // 扫描或读取插件时出错
+  Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginPlannedScript {
+  pub path: PathBuf,
+  pub runner: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PluginLoadStep {
+  // `to` 为 `release` 下的解压目录，放置到加载目录在解压之后进行
+  Extract { plugin: PathBuf, to: PathBuf },
+  Link { plugin: PathBuf, from: PathBuf, to: PathBuf },
+  // 普通插件的脚本在解压后才能确定，此时 `scripts` 为 None
+  RunScripts { plugin: PathBuf, scripts: Option<Vec<PluginPlannedScript>> },
+  Skip { plugin: PathBuf, reason: PluginSkipReason },
+}
+
+impl PluginLoadStep {
+  pub fn plugin(&self) -> &Path {
+    match self {
+      Self::Extract { plugin, .. }
+      | Self::Link { plugin, .. }
+      | Self::RunScripts { plugin, .. }
+      | Self::Skip { plugin, .. } => plugin,
+    }
+  }
+}
+
+impl fmt::Display for PluginLoadStep {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Extract { plugin, to } => write!(f, "extract {} to {}", plugin.display(), to.display()),
+      Self::Link { plugin, from, to } => {
+        write!(f, "link {} from {} to {}", plugin.display(), from.display(), to.display())
+      }
+      Self::RunScripts { plugin, scripts: None } => {
+        write!(f, "run scripts of {} found after extraction", plugin.display())
+      }
+      Self::RunScripts { plugin, scripts: Some(scripts) } => {
+        write!(f, "run scripts of {}:", plugin.display())?;
+        for s in scripts {
+          write!(f, " {} with {};", s.path.display(), s.runner)?;
+        }
+        Ok(())
+      }
+      Self::Skip { plugin, reason } => write!(f, "skip {} because {:?}", plugin.display(), reason),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "change", content = "step", rename_all = "snake_case")]
+pub enum PluginLoadPlanChange {
+  Added(PluginLoadStep),
+  Removed(PluginLoadStep),
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginLoadPlanOptions {
+  pub scan: PluginScanOptions,
+  pub trust_policy: PluginTrustPolicy,
+  // 可用的 BoostRepo，为空时 LocalBoost 插件按普通插件加载
+  pub boostrepo: BoostRepoEntries,
+  pub dest: PathBuf,
+  // 解压目录与会话 id，执行时使用同一配置，计划中的解压目录才与实际一致
+  pub release: PathBuf,
+  pub mangle_id: Uuid,
+}
+
+impl PluginLoadPlanOptions {
+  pub fn new(dest: PathBuf) -> Self {
+    Self {
+      scan: PluginScanOptions::default(),
+      trust_policy: PluginTrustPolicy::Off,
+      boostrepo: BoostRepoEntries::default(),
+      release: dest.join(RELEASE_DIR_NAME),
+      mangle_id: Uuid::nil(),
+      dest,
+    }
+  }
+
+  pub fn from_config(config: &PluginLoadConfig) -> Self {
+    Self {
+      release: config.release().to_path_buf(),
+      mangle_id: config.mangle_id(),
+      trust_policy: config.trust_policy(),
+      ..Self::new(config.dest().to_path_buf())
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginLoadPlan {
+  // 生成计划时的 Profile 目录，比较计划时路径相对于它
+  #[serde(default)]
+  pub profile: PathBuf,
+  pub steps: Vec<PluginLoadStep>,
+  // 依赖缺失、依赖成环等不影响加载的问题
+  pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginLoadReport {
+  pub plugin: PathBuf,
+  pub state: PluginLoadState,
+  pub skipped: Option<PluginSkipReason>,
+  pub error: Option<String>,
+}
+
+// 按数字段比较版本号，非数字段按字符串比较
+fn compare_version(a: &str, b: &str) -> Ordering {
+  let split = |s: &str| s.split(|c: char| !c.is_ascii_alphanumeric())
+    .map(|p| p.to_lowercase())
+    .collect::<Vec<_>>();
+  let (a, b) = (split(a), split(b));
+  for (x, y) in a.iter().zip(b.iter()) {
+    let o = match (x.parse::<u64>(), y.parse::<u64>()) {
+      (Ok(x), Ok(y)) => x.cmp(&y),
+      _ => x.cmp(y),
+    };
+    if o != Ordering::Equal {
+      return o;
+    }
+  }
+  a.len().cmp(&b.len())
+}
+
+fn runner_name(t: PluginScriptType) -> &'static str {
+  match t {
+    PluginScriptType::Batch => "cmd",
+    PluginScriptType::Pecmd => "pecmd",
+  }
+}
+
+async fn list_scripts(dir: &Path) -> anyhow::Result<Vec<PluginPlannedScript>> {
+  let mut scripts = vec![];
+  let mut iter = fs::read_dir(dir).await?;
+  while let Some(f) = iter.next_entry().await? {
+    let path = f.path();
+    if !f.file_type().await?.is_file() {
+      continue;
+    }
+    if let Some(t) = PluginScriptType::from_path(&path) {
+      scripts.push(PluginPlannedScript {
+        path,
+        runner: runner_name(t).to_string(),
+      });
+    }
+  }
+  scripts.sort_by(|a, b| a.path.cmp(&b.path));
+  Ok(scripts)
+}
+
+impl PluginLoadPlan {
+  /*
+   * 只读取，不修改任何文件
+   * 顺序：扫描 -> 信任校验 -> 同名插件取最高版本 -> 依赖排序 -> 生成步骤
+   */
+  pub async fn build(profile: &ProfileEntry, options: &PluginLoadPlanOptions) -> anyhow::Result<Self> {
+    let report = PluginEntry::from_profile_with(profile, &options.scan).await?;
+    let mut entries = report.entries;
+    let mut plan = Self {
+      profile: profile.path.clone(),
+      ..Self::default()
+    };
+
+    for e in report.errors {
+      plan.steps.push(PluginLoadStep::Skip {
+        plugin: e.path,
+        reason: PluginSkipReason::Error(e.error),
+      });
+    }
+
+    if options.trust_policy != PluginTrustPolicy::Off {
+      let store = PluginTrustStore::from_profile(profile).await?;
+      entries.check_trust(&store).await;
+    }
+
+    let mut skipped: HashMap<PathBuf, PluginSkipReason> = HashMap::new();
+    let mut newest: HashMap<String, &PluginEntry> = HashMap::new();
+    for e in entries.iter() {
+      if e.extension.unwrap_or(PluginExtension::Disabled) == PluginExtension::Disabled {
+        skipped.insert(e.path.clone(), PluginSkipReason::Disabled);
+        continue;
+      }
+      if !e.is_allowed(options.trust_policy) {
+        skipped.insert(e.path.clone(), PluginSkipReason::Untrusted(format!("{:?}", e.trust)));
+        continue;
+      }
+
+      let name = match e.name() {
+        Some(n) => n.to_lowercase(),
+        None => continue,
+      };
+      let version = |e: &PluginEntry| e.meta.as_ref().map(|m| m.version.clone()).unwrap_or_default();
+      match newest.get(&name) {
+        Some(old) if compare_version(&version(old), &version(e)) != Ordering::Less => {
+          skipped.insert(e.path.clone(), PluginSkipReason::Superseded(old.path.clone()));
+        }
+        Some(old) => {
+          skipped.insert(old.path.clone(), PluginSkipReason::Superseded(e.path.clone()));
+          newest.insert(name, e);
+        }
+        None => {
+          newest.insert(name, e);
+        }
+      }
+    }
+
+    let order = PluginGraph::new(entries.iter()).load_order();
+    for i in &order.issues {
+      plan.warnings.push(format!("{} depends on {}, {:?}", i.entry.path.display(), i.depend, i.kind));
+    }
+    for c in &order.cycles {
+      let names = c.iter().map(|e| e.path.display().to_string()).collect::<Vec<_>>();
+      plan.warnings.push(format!("dependency cycle: {}", names.join(", ")));
+    }
+
+    let lb = options.boostrepo.get_plugins();
+    for e in order.order {
+      if let Some(reason) = skipped.remove(&e.path) {
+        plan.steps.push(PluginLoadStep::Skip {
+          plugin: e.path.clone(),
+          reason,
+        });
+        continue;
+      }
+
+      let boost = lb.get(&e.meta)
+        .and_then(|repos| repos.iter().flat_map(|r| r.plugins.iter()).find(|p| p.meta == e.meta))
+        .filter(|_| e.extension == Some(PluginExtension::Localboost));
+      match boost {
+        Some(p) => {
+          plan.steps.push(PluginLoadStep::Link {
+            plugin: e.path.clone(),
+            from: p.path.clone(),
+            to: options.dest.clone(),
+          });
+          plan.steps.push(PluginLoadStep::RunScripts {
+            plugin: e.path.clone(),
+            scripts: Some(list_scripts(&p.path).await?),
+          });
+        }
+        None => {
+          plan.steps.push(PluginLoadStep::Extract {
+            plugin: e.path.clone(),
+            to: options.release.join(mangle_release_name(e, &options.mangle_id)),
+          });
+          plan.steps.push(PluginLoadStep::RunScripts {
+            plugin: e.path.clone(),
+            scripts: None,
+          });
+        }
+      }
+    }
+
+    info!("built load plan, {} steps, {} warnings", plan.steps.len(), plan.warnings.len());
+    Ok(plan)
+  }
+
+  pub fn to_json(&self) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(self)?)
+  }
+
+  pub fn from_json(s: &str) -> anyhow::Result<Self> {
+    serde_json::from_str(s).map_err(|e| anyhow!("invalid load plan, {}", e))
+  }
+
+  // Profile 下的路径换成相对路径，Profile 所在盘符或位置变化时不算改动
+  fn relative_steps(&self) -> Vec<PluginLoadStep> {
+    let rel = |p: &PathBuf| p.strip_prefix(&self.profile)
+      .map(|r| r.to_path_buf())
+      .unwrap_or_else(|_| p.clone());
+    self.steps.iter().map(|s| match s {
+      PluginLoadStep::Extract { plugin, to } => PluginLoadStep::Extract {
+        plugin: rel(plugin),
+        to: rel(to),
+      },
+      PluginLoadStep::Link { plugin, from, to } => PluginLoadStep::Link {
+        plugin: rel(plugin),
+        from: rel(from),
+        to: rel(to),
+      },
+      PluginLoadStep::RunScripts { plugin, scripts } => PluginLoadStep::RunScripts {
+        plugin: rel(plugin),
+        scripts: scripts.as_ref().map(|v| v.iter().map(|s| PluginPlannedScript {
+          path: rel(&s.path),
+          runner: s.runner.clone(),
+        }).collect()),
+      },
+      PluginLoadStep::Skip { plugin, reason } => PluginLoadStep::Skip {
+        plugin: rel(plugin),
+        reason: match reason {
+          PluginSkipReason::Superseded(p) => PluginSkipReason::Superseded(rel(p)),
+          r => r.clone(),
+        },
+      },
+    }).collect()
+  }
+
+  /*
+   * 基于最长公共子序列，步骤顺序的变化也会体现为删除与新增
+   * 按相对于各自 Profile 的路径比较，返回的步骤保持原样
+   */
+  pub fn diff(&self, other: &Self) -> Vec<PluginLoadPlanChange> {
+    let (a, b) = (&self.relative_steps(), &other.relative_steps());
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+      for j in (0..b.len()).rev() {
+        lcs[i][j] = if a[i] == b[j] {
+          lcs[i + 1][j + 1] + 1
+        } else {
+          lcs[i + 1][j].max(lcs[i][j + 1])
+        };
+      }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut changes = vec![];
+    while i < a.len() || j < b.len() {
+      if i < a.len() && j < b.len() && a[i] == b[j] {
+        i += 1;
+        j += 1;
+      } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+        changes.push(PluginLoadPlanChange::Added(other.steps[j].clone()));
+        j += 1;
+      } else {
+        changes.push(PluginLoadPlanChange::Removed(self.steps[i].clone()));
+        i += 1;
+      }
+    }
+    changes
+  }
+
+  /*
+   * 按步骤执行，同一插件的步骤相邻
+   * 单个插件失败时跳过它余下的步骤，不影响其他插件
+   */
+  pub async fn execute(
+    &self,
+    config: &PluginLoadConfig,
+    boostrepo: &BoostRepoPluginMap<'_>,
+    runner: Option<Arc<dyn PluginScriptRunner>>,
+  ) -> Vec<PluginLoadReport> {
+    let mut reports: Vec<PluginLoadReport> = vec![];
+    let mut done = HashSet::new();
+
+    for (i, step) in self.steps.iter().enumerate() {
+      let plugin = step.plugin();
+      if !done.insert(plugin.to_path_buf()) {
+        continue;
+      }
+      let steps = self.steps[i..].iter()
+        .take_while(|s| s.plugin() == plugin)
+        .collect::<Vec<_>>();
+
+      if let PluginLoadStep::Skip { reason, .. } = step {
+        info!("skip {:?}, {:?}", plugin, reason);
+        reports.push(PluginLoadReport {
+          plugin: plugin.to_path_buf(),
+          state: PluginLoadState::Pending,
+          skipped: Some(reason.clone()),
+          error: None,
+        });
+        continue;
+      }
+
+      let (state, error) = match Self::execute_plugin(plugin, &steps, config, boostrepo, runner.clone()).await {
+        Ok(s) => s,
+        Err(e) => (PluginLoadState::Rejected, Some(e.to_string())),
+      };
+      reports.push(PluginLoadReport {
+        plugin: plugin.to_path_buf(),
+        state,
+        skipped: None,
+        error,
+      });
+    }
+    reports
+  }
+
+  async fn execute_plugin(
+    plugin: &Path,
+    steps: &[&PluginLoadStep],
+    config: &PluginLoadConfig,
+    boostrepo: &BoostRepoPluginMap<'_>,
+    runner: Option<Arc<dyn PluginScriptRunner>>,
+  ) -> anyhow::Result<(PluginLoadState, Option<String>)> {
+    let entry = PluginEntry::new(plugin.to_path_buf()).await?;
+    let mut session = PluginLoadSession::new(&entry, boostrepo);
+    session.with_config(Some(config.clone()));
+    if let Some(r) = runner {
+      session.with_runner(r);
+    }
+
+    for step in steps {
+      let r = match step {
+        PluginLoadStep::Extract { .. } => {
+          session.target = PluginLoadTarget::Normal;
+          match session.release_as_normal().await {
+            Ok(_) => session.place_as_normal().await,
+            Err(e) => Err(e),
+          }
+        }
+        PluginLoadStep::Link { .. } => session.link_as_localboost().await,
+        PluginLoadStep::RunScripts { .. } => session.run_scripts().await,
+        PluginLoadStep::Skip { .. } => Ok(()),
+      };
+      if let Err(e) = r {
+        warn!("step `{}` failed, {}", step, e);
+        session.state = PluginLoadState::Rejected;
+        session.error = Some(e.to_string());
+        break;
+      }
+    }
+    Ok((session.state, session.error))
+  }
+}
+
+impl fmt::Display for PluginLoadPlan {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, s) in self.steps.iter().enumerate() {
+      writeln!(f, "{:>3}. {}", i + 1, s)?;
+    }
+    for w in &self.warnings {
+      writeln!(f, "warning: {}", w)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::cmp::Ordering;
+  use std::path::Path;
+  use edgeless_core::found::{ProfileEntry, ProfileType};
+  use tokio::fs;
+  use crate::testing::touch;
+  #[cfg(unix)]
+  use crate::testing::fake_seven_zip;
+
+  use uuid::Uuid;
+
+  use super::{compare_version, PluginLoadPlan, PluginLoadPlanChange, PluginLoadPlanOptions, PluginLoadStep, PluginSkipReason};
+  use crate::found::localboost::{BoostRepoEntries, BoostRepoEntry};
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    assert_eq!(compare_version("90.0.10", "90.0.9"), Ordering::Greater);
+    assert_eq!(compare_version("1.0", "1.0.1"), Ordering::Less);
+    assert_eq!(compare_version("1.0b", "1.0a"), Ordering::Greater);
+
+    let dir = tempfile::tempdir()?;
+    let profile = ProfileEntry::from_path(dir.path().join("Edgeless"), ProfileType::Default);
+    let res = profile.path.join("Resource");
+    touch(&res.join("Chrome_90.0_Cno.7z"), "").await?;
+    touch(&res.join("Chrome_91.0_Cno.7z"), "").await?;
+    touch(&res.join("Chrome_91.0_Cno.json"), r#"{ "depends": ["VCRuntime"] }"#).await?;
+    touch(&res.join("VCRuntime_1.0_Cno.7z"), "").await?;
+    touch(&res.join("Office_1.0_Cno.7zl"), "").await?;
+    touch(&res.join("Old_1.0_Cno.7zf"), "").await?;
+
+    let repo_path = dir.path().join("Stick").join("Edgeless");
+    touch(&repo_path.join("BoostRepo/Office_1.0_Cno/office.wcs"), "").await?;
+    let repo = BoostRepoEntry::new(ProfileEntry::from_path(repo_path.clone(), ProfileType::BoostRepo)).await?;
+
+    let dest = dir.path().join("Target");
+    let mut options = PluginLoadPlanOptions::new(dest.clone());
+    let before = PluginLoadPlan::build(&profile, &options).await?;
+    assert!(!dest.exists());
+
+    options.boostrepo = BoostRepoEntries(vec![repo]);
+    let plan = PluginLoadPlan::build(&profile, &options).await?;
+    assert_eq!(plan.to_string().lines().count(), plan.steps.len() + plan.warnings.len());
+
+    let vc = plan.steps.iter().position(|s| s.plugin().ends_with("VCRuntime_1.0_Cno.7z")).unwrap();
+    let chrome = plan.steps.iter().position(|s| s.plugin().ends_with("Chrome_91.0_Cno.7z")).unwrap();
+    assert!(vc < chrome);
+    assert!(plan.steps.contains(&PluginLoadStep::Skip {
+      plugin: res.join("Chrome_90.0_Cno.7z"),
+      reason: PluginSkipReason::Superseded(res.join("Chrome_91.0_Cno.7z")),
+    }));
+    assert!(plan.steps.contains(&PluginLoadStep::Skip {
+      plugin: res.join("Old_1.0_Cno.7zf"),
+      reason: PluginSkipReason::Disabled,
+    }));
+    assert!(plan.steps.contains(&PluginLoadStep::Link {
+      plugin: res.join("Office_1.0_Cno.7zl"),
+      from: repo_path.join("BoostRepo/Office_1.0_Cno"),
+      to: dest.clone(),
+    }));
+    assert!(plan.to_string().contains("office.wcs with pecmd"));
+
+    assert_eq!(PluginLoadPlan::from_json(&plan.to_json()?)?, plan);
+    assert!(plan.diff(&plan).is_empty());
+    let changes = before.diff(&plan);
+    assert!(changes.contains(&PluginLoadPlanChange::Removed(PluginLoadStep::Extract {
+      plugin: res.join("Office_1.0_Cno.7zl"),
+      to: dest.join("__release__").join(format!("Office_1.0_Cno_{}", Uuid::nil().to_simple())),
+    })));
+    assert!(changes.iter().any(|c| matches!(c, PluginLoadPlanChange::Added(PluginLoadStep::Link { .. }))));
+
+    // Profile 换了位置（如盘符变化），计划不变
+    let moved = ProfileEntry::from_path(dir.path().join("Moved"), ProfileType::Default);
+    fs::rename(&profile.path, &moved.path).await?;
+    let after = PluginLoadPlan::build(&moved, &options).await?;
+    assert_ne!(after.steps, plan.steps);
+    assert!(plan.diff(&after).is_empty());
+    touch(&moved.path.join("Resource/New_1.0_Cno.7z"), "").await?;
+    let after = PluginLoadPlan::build(&moved, &options).await?;
+    assert!(plan.diff(&after).iter().all(|c| matches!(c, PluginLoadPlanChange::Added(s) if s.plugin() == moved.path.join("Resource/New_1.0_Cno.7z"))));
+
+    Ok(())
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn execute() -> anyhow::Result<()> {
+    use std::sync::Arc;
+    use crate::loader::script::PluginRecordingScriptRunner;
+    use crate::loader::{PluginLoadConfig, PluginLoadState};
+
+    let dir = tempfile::tempdir()?;
+    let exe = fake_seven_zip(dir.path()).await?;
+
+    let profile = ProfileEntry::from_path(dir.path().join("Edgeless"), ProfileType::Default);
+    let res = profile.path.join("Resource");
+    touch(&res.join("Chrome_91.0_Cno.7z"), "mkdir Chrome\necho > chrome.cmd\n").await?;
+    touch(&res.join("Broken_1.0_Cno.7z"), "exit 2\n").await?;
+    touch(&res.join("Office_1.0_Cno.7zl"), "").await?;
+    touch(&res.join("Old_1.0_Cno.7zf"), "").await?;
+    let repo_path = dir.path().join("Stick").join("Edgeless");
+    touch(&repo_path.join("BoostRepo/Office_1.0_Cno/office.wcs"), "").await?;
+    touch(&repo_path.join("BoostRepo/Office_1.0_Cno/Office/office.exe"), "").await?;
+
+    let dest = dir.path().join("Target");
+    let config = PluginLoadConfig::new(dest.clone()).await?.with_seven_zip(exe);
+    let mut options = PluginLoadPlanOptions::from_config(&config);
+    options.boostrepo = BoostRepoEntries(vec![
+      BoostRepoEntry::new(ProfileEntry::from_path(repo_path, ProfileType::BoostRepo)).await?,
+    ]);
+    let plan = PluginLoadPlan::build(&profile, &options).await?;
+    let chrome = crate::found::PluginEntry::new(res.join("Chrome_91.0_Cno.7z")).await?;
+    assert!(plan.steps.contains(&PluginLoadStep::Extract {
+      plugin: chrome.path.clone(),
+      to: config.release().join(config.mangle(&chrome)),
+    }));
+
+    let runner = Arc::new(PluginRecordingScriptRunner::new());
+    let map = options.boostrepo.get_plugins();
+    let reports = plan.execute(&config, &map, Some(runner.clone())).await;
+    assert_eq!(reports.len(), 4);
+
+    let state = |name: &str| reports.iter().find(|r| r.plugin.ends_with(name)).map(|r| r.state);
+    assert_eq!(state("Chrome_91.0_Cno.7z"), Some(PluginLoadState::Resolved));
+    assert_eq!(state("Office_1.0_Cno.7zl"), Some(PluginLoadState::Resolved));
+    assert_eq!(state("Broken_1.0_Cno.7z"), Some(PluginLoadState::Rejected));
+    assert_eq!(state("Old_1.0_Cno.7zf"), Some(PluginLoadState::Pending));
+
+    let mut ran = runner.names();
+    ran.sort();
+    assert_eq!(ran, vec!["chrome.cmd".to_string(), "office.wcs".to_string()]);
+    assert!(dest.join("Chrome").is_dir());
+    assert!(dest.join("Office/office.exe").exists());
+
+    Ok(())
+  }
+
+  // 生成计划后被替换的插件包在执行时重新校验，`enforce` 下拒绝加载
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn execute_replaced() -> anyhow::Result<()> {
+    use ed25519_dalek::{Signer, SigningKey};
+    use edgeless_core::options::PluginTrustPolicy;
+    use edgeless_core::options::define::PATH_OPTION_PLUGIN_TRUSTED_KEYS;
+    use crate::found::PluginEntry;
+    use crate::found::trust::PluginTrustStore;
+    use crate::loader::{PluginLoadConfig, PluginLoadState};
+
+    let dir = tempfile::tempdir()?;
+    let exe = fake_seven_zip(dir.path()).await?;
+
+    let profile = ProfileEntry::from_path(dir.path().join("Edgeless"), ProfileType::Default);
+    let key = SigningKey::from_bytes(&[7u8; 32]);
+    touch(&profile.path.join(PATH_OPTION_PLUGIN_TRUSTED_KEYS.as_path()).join("pipeline.pub"), hex::encode(key.verifying_key().to_bytes())).await?;
+    let res = profile.path.join("Resource");
+    for n in ["Chrome", "Notepad"] {
+      let plugin = res.join(format!("{}_1.0_Cno.7z", n));
+      touch(&plugin, format!("mkdir {}\n", n)).await?;
+      let digest = hex::decode(PluginEntry::new(plugin.clone()).await?.sha256().await?)?;
+      touch(&plugin.with_extension("sig"), hex::encode(key.sign(&digest).to_bytes())).await?;
+    }
+
+    let dest = dir.path().join("Target");
+    let mut options = PluginLoadPlanOptions::new(dest.clone());
+    options.trust_policy = PluginTrustPolicy::Enforce;
+    let plan = PluginLoadPlan::build(&profile, &options).await?;
+    assert!(!plan.steps.iter().any(|s| matches!(s, PluginLoadStep::Skip { .. })));
+    touch(&res.join("Chrome_1.0_Cno.7z"), "mkdir Evil\n").await?;
+
+    let config = PluginLoadConfig::new(dest.clone()).await?
+      .with_seven_zip(exe)
+      .with_trust(PluginTrustPolicy::Enforce, PluginTrustStore::from_profile(&profile).await?);
+    let map = options.boostrepo.get_plugins();
+    let reports = plan.execute(&config, &map, None).await;
+    let report = |name: &str| reports.iter().find(|r| r.plugin.ends_with(name)).unwrap();
+    assert_eq!(report("Chrome_1.0_Cno.7z").state, PluginLoadState::Rejected);
+    assert!(report("Chrome_1.0_Cno.7z").error.as_ref().unwrap().contains("not trusted"));
+    assert_eq!(report("Notepad_1.0_Cno.7z").state, PluginLoadState::Resolved);
+    assert!(!dest.join("Evil").exists());
+    assert!(dest.join("Notepad").is_dir());
+
+    Ok(())
+  }
+}
@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use bindings_7z::SevenZipEvent;
+use futures::future::join_all;
+use crate::found::graph::PluginGraph;
+use crate::found::localboost::BoostRepoPluginMap;
+use crate::found::PluginEntry;
+use super::link::PluginFileSystem;
+use super::script::PluginScriptRunner;
+use super::{PluginLoadConfig, PluginLoadSession, PluginLoadState, PluginLoadTarget};
+
+use log::info;
+use tokio::sync::{mpsc, watch, Mutex, RwLock, Semaphore};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PluginLoadProgress {
+  pub total: usize,
+  pub released: usize,
+  pub finished: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PluginLoadTiming {
+  // 解压耗时
+  pub release: Duration,
+  // 等待前一个插件放置与依赖脚本完成的时间
+  pub wait: Duration,
+  // 移动或链接到加载目录
+  pub place: Duration,
+  pub scripts: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginScheduleResult {
+  pub plugin: PathBuf,
+  pub state: PluginLoadState,
+  pub error: Option<String>,
+  pub timing: PluginLoadTiming,
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginScheduleReport {
+  // 按加载顺序
+  pub results: Vec<PluginScheduleResult>,
+  pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub enum PluginLoadEvent {
+  // 解压进度，0 到 100
+  Extracting(PathBuf, u8),
+  Released(PathBuf, Duration),
+  Resolved(PathBuf, PluginLoadTiming),
+  Rejected(PathBuf, String),
+  Progress(PluginLoadProgress),
+}
+
+/*
+ * 并发加载调度
+ * 1. 解压并发进行，数量受 `concurrency` 限制
+ * 2. 放置（移动或链接）按加载顺序逐个进行，保证同名文件的归属确定
+ * 3. 脚本在依赖的插件脚本完成后执行，`exclusive` 插件的脚本独占执行；
+ *    声明 `watch` 的插件靠前后快照对比归属新建的文件，配置了监视目录时也独占执行
+ */
+#[derive(Debug, Clone)]
+pub struct PluginLoadScheduler {
+  pub config: PluginLoadConfig,
+  pub concurrency: usize,
+  pub runner: Option<Arc<dyn PluginScriptRunner>>,
+  pub filesystem: Option<Arc<dyn PluginFileSystem>>,
+}
+
+struct Shared {
+  limit: Semaphore,
+  scripts: RwLock<()>,
+  events: Option<mpsc::UnboundedSender<PluginLoadEvent>>,
+  total: usize,
+  released: AtomicUsize,
+  finished: AtomicUsize,
+  progress: Mutex<()>,
+}
+
+impl Shared {
+  fn emit(&self, e: PluginLoadEvent) {
+    if let Some(tx) = &self.events {
+      let _ = tx.send(e);
+    }
+  }
+
+  async fn progress(&self) {
+    let _g = self.progress.lock().await;
+    self.emit(PluginLoadEvent::Progress(PluginLoadProgress {
+      total: self.total,
+      released: self.released.load(Ordering::SeqCst),
+      finished: self.finished.load(Ordering::SeqCst),
+    }));
+  }
+}
+
+async fn wait_done(rx: &mut watch::Receiver<bool>) {
+  // 发送端提前释放也视为完成
+  let _ = rx.wait_for(|done| *done).await;
+}
+
+impl PluginLoadScheduler {
+  pub fn new(config: PluginLoadConfig) -> Self {
+    let cpus = std::thread::available_parallelism()
+      .map(|n| n.get())
+      .unwrap_or(1);
+    Self {
+      // 解压主要受限于 U 盘读取速度，过多并发反而更慢
+      concurrency: config.concurrency().unwrap_or_else(|| cpus.clamp(1, 4)),
+      config,
+      runner: None,
+      filesystem: None,
+    }
+  }
+
+  pub fn with_concurrency(mut self, n: usize) -> Self {
+    self.concurrency = n.max(1);
+    self
+  }
+
+  pub fn with_runner(mut self, runner: Arc<dyn PluginScriptRunner>) -> Self {
+    self.runner = Some(runner);
+    self
+  }
+
+  pub fn with_filesystem(mut self, filesystem: Arc<dyn PluginFileSystem>) -> Self {
+    self.filesystem = Some(filesystem);
+    self
+  }
+
+  pub async fn run<'a, I>(&self, entries: I, boostrepo: &'a BoostRepoPluginMap<'a>) -> PluginScheduleReport
+  where
+    I: IntoIterator<Item = &'a PluginEntry>,
+  {
+    self.run_with_events(entries, boostrepo, None).await
+  }
+
+  pub async fn run_with_events<'a, I>(
+    &self,
+    entries: I,
+    boostrepo: &'a BoostRepoPluginMap<'a>,
+    events: Option<mpsc::UnboundedSender<PluginLoadEvent>>,
+  ) -> PluginScheduleReport
+  where
+    I: IntoIterator<Item = &'a PluginEntry>,
+  {
+    let start = Instant::now();
+    let entries = entries.into_iter()
+      .filter(|e| !matches!(e.extension.map(PluginLoadTarget::from), None | Some(PluginLoadTarget::Disabled)))
+      .collect::<Vec<_>>();
+    let graph = PluginGraph::new(entries.iter().copied());
+    let order = graph.load_order().order;
+    let index = order.iter()
+      .enumerate()
+      .map(|(i, e)| (e.path.clone(), i))
+      .collect::<HashMap<_, _>>();
+
+    let shared = Shared {
+      limit: Semaphore::new(self.concurrency),
+      scripts: RwLock::new(()),
+      events,
+      total: order.len(),
+      released: AtomicUsize::new(0),
+      finished: AtomicUsize::new(0),
+      progress: Mutex::new(()),
+    };
+    info!("schedule {} plugins, concurrency {}", order.len(), self.concurrency);
+
+    let (placed_tx, placed_rx): (Vec<_>, Vec<_>) = order.iter().map(|_| watch::channel(false)).unzip();
+    let (done_tx, done_rx): (Vec<_>, Vec<_>) = order.iter().map(|_| watch::channel(false)).unzip();
+
+    let tasks = order.iter()
+      .zip(placed_tx.into_iter().zip(done_tx))
+      .enumerate()
+      .map(|(i, (entry, (placed, done)))| {
+        // 成环的依赖只等待排在前面的插件，避免互相等待
+        let depends = graph.depends_of(entry)
+          .into_iter()
+          .filter_map(|d| index.get(&d.path).copied())
+          .filter(|&d| d < i)
+          .map(|d| done_rx[d].clone())
+          .collect::<Vec<_>>();
+        let previous = i.checked_sub(1).map(|p| placed_rx[p].clone());
+        self.load_one(entry, boostrepo, &shared, previous, depends, placed, done)
+      });
+    let results = join_all(tasks).await;
+
+    PluginScheduleReport {
+      results,
+      elapsed: start.elapsed(),
+    }
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  async fn load_one<'a>(
+    &self,
+    entry: &'a PluginEntry,
+    boostrepo: &'a BoostRepoPluginMap<'a>,
+    shared: &Shared,
+    previous: Option<watch::Receiver<bool>>,
+    depends: Vec<watch::Receiver<bool>>,
+    placed: watch::Sender<bool>,
+    done: watch::Sender<bool>,
+  ) -> PluginScheduleResult {
+    let mut session = PluginLoadSession::new(entry, boostrepo);
+    session.with_config(Some(self.config.clone()));
+    if let Some(r) = &self.runner {
+      session.with_runner(r.clone());
+    }
+    if let Some(f) = &self.filesystem {
+      session.with_filesystem(f.clone());
+    }
+    let mut timing = PluginLoadTiming::default();
+
+    // 解压，进度随解压转发，7z 结束后通道关闭
+    let t = Instant::now();
+    let released = {
+      let _permit = shared.limit.acquire().await;
+      if matches!(session.target, PluginLoadTarget::Localboost) && session.find_localboost().is_none() {
+        session.target = PluginLoadTarget::Normal;
+      }
+      let (tx, mut rx) = mpsc::unbounded_channel();
+      let release = async {
+        match session.target {
+          PluginLoadTarget::Normal => session.release_as_normal_with_events(Some(tx)).await,
+          _ => Ok(()),
+        }
+      };
+      let forward = async {
+        while let Some(SevenZipEvent::Progress(p)) = rx.recv().await {
+          shared.emit(PluginLoadEvent::Extracting(entry.path.clone(), p));
+        }
+      };
+      tokio::join!(release, forward).0
+    };
+    timing.release = t.elapsed();
+    // LocalBoost 不解压；解压失败在最后通过 `Rejected` 报告
+    if released.is_ok() && matches!(session.target, PluginLoadTarget::Normal) {
+      shared.released.fetch_add(1, Ordering::SeqCst);
+      shared.emit(PluginLoadEvent::Released(entry.path.clone(), timing.release));
+    }
+    shared.progress().await;
+
+    // 放置
+    let t = Instant::now();
+    if let Some(mut p) = previous {
+      wait_done(&mut p).await;
+    }
+    timing.wait = t.elapsed();
+    let t = Instant::now();
+    let placed_r = match released {
+      Ok(_) => match session.target {
+        PluginLoadTarget::Localboost => session.link_as_localboost().await,
+        _ => session.place_as_normal().await,
+      },
+      Err(e) => Err(e),
+    };
+    timing.place = t.elapsed();
+    let _ = placed.send(true);
+
+    // 脚本
+    let t = Instant::now();
+    for mut d in depends {
+      wait_done(&mut d).await;
+    }
+    timing.wait += t.elapsed();
+    let t = Instant::now();
+    let exclusive = entry.is_exclusive() || (entry.is_watched() && !self.config.watch_dirs().is_empty());
+    let r = match placed_r {
+      Ok(_) if exclusive => {
+        let _g = shared.scripts.write().await;
+        session.run_scripts().await
+      }
+      Ok(_) => {
+        let _g = shared.scripts.read().await;
+        session.run_scripts().await
+      }
+      Err(e) => Err(e),
+    };
+    timing.scripts = t.elapsed();
+
+    if let Err(e) = &r {
+      session.reject(e).await;
+      shared.emit(PluginLoadEvent::Rejected(entry.path.clone(), e.to_string()));
+    } else {
+      shared.emit(PluginLoadEvent::Resolved(entry.path.clone(), timing));
+    }
+    let _ = done.send(true);
+    shared.finished.fetch_add(1, Ordering::SeqCst);
+    shared.progress().await;
+
+    PluginScheduleResult {
+      plugin: entry.path.clone(),
+      state: session.state,
+      error: session.error.clone(),
+      timing,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use std::sync::{Arc, Mutex};
+  use std::time::Duration;
+  use tokio::fs;
+  use tokio::sync::mpsc;
+  use crate::testing::touch;
+  #[cfg(unix)]
+  use crate::testing::fake_seven_zip;
+
+  use super::{PluginLoadEvent, PluginLoadScheduler};
+  use crate::found::PluginEntry;
+  use crate::loader::script::{PluginScriptContext, PluginScriptFuture, PluginScriptOutput, PluginScriptRunner};
+  use crate::loader::{PluginLoadConfig, PluginLoadState, PluginScriptEntry};
+
+  // 记录脚本的开始与结束，用于检查执行顺序与并发
+  #[derive(Debug, Default)]
+  struct Tracer(Mutex<Vec<String>>);
+
+  impl PluginScriptRunner for Tracer {
+    fn run<'a>(&'a self, script: &'a PluginScriptEntry, _: &'a PluginScriptContext) -> PluginScriptFuture<'a> {
+      let name = script.path_original.file_stem().unwrap().to_string_lossy().to_string();
+      Box::pin(async move {
+        self.0.lock().unwrap().push(format!("+{}", name));
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        self.0.lock().unwrap().push(format!("-{}", name));
+        Ok(PluginScriptOutput {
+          code: Some(0),
+          ..Default::default()
+        })
+      })
+    }
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let exe = fake_seven_zip(dir.path()).await?;
+
+    let res = dir.path().join("Resource");
+    for n in ["VCRuntime", "Chrome", "Notepad", "Driver"] {
+      touch(&res.join(format!("{}_1.0_Cno.7z", n)), &format!("mkdir -p {0} Shared\necho > Shared/{0}.dll\necho > {0}.cmd\n", n)).await?;
+    }
+    touch(&res.join("Broken_1.0_Cno.7z"), "exit 2\n").await?;
+    touch(&res.join("Old_1.0_Cno.7zf"), "").await?;
+    touch(&res.join("Chrome_1.0_Cno.json"), r#"{ "depends": ["VCRuntime"] }"#).await?;
+    touch(&res.join("Driver_1.0_Cno.json"), r#"{ "exclusive": true }"#).await?;
+    let entries = PluginEntry::scan_dir(res.clone()).await?;
+
+    let dest = dir.path().join("Edgeless");
+    let config = PluginLoadConfig::new(dest.clone()).await?.with_seven_zip(exe);
+    let tracer = Arc::new(Tracer::default());
+    let scheduler = PluginLoadScheduler::new(config).with_concurrency(3).with_runner(tracer.clone());
+    let lb = Default::default();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let report = scheduler.run_with_events(entries.iter(), &lb, Some(tx)).await;
+
+    assert_eq!(report.results.len(), 5);
+    let state = |n: &str| report.results.iter()
+      .find(|r| r.plugin.ends_with(format!("{}_1.0_Cno.7z", n)))
+      .map(|r| r.state);
+    assert_eq!(state("Chrome"), Some(PluginLoadState::Resolved));
+    assert_eq!(state("Driver"), Some(PluginLoadState::Resolved));
+    assert_eq!(state("Broken"), Some(PluginLoadState::Rejected));
+    assert!(dest.join("Shared/Notepad.dll").exists());
+
+    let trace = tracer.0.lock().unwrap().clone();
+    let at = |s: &str| trace.iter().position(|t| t == s).unwrap();
+    assert!(at("-VCRuntime") < at("+Chrome"));
+    // 独占脚本运行期间没有其他脚本
+    assert_eq!(at("-Driver"), at("+Driver") + 1);
+
+    let mut last = None;
+    let (mut released, mut rejected, mut extracted) = (vec![], vec![], vec![]);
+    while let Ok(e) = rx.try_recv() {
+      match e {
+        PluginLoadEvent::Progress(p) => last = Some(p),
+        PluginLoadEvent::Extracting(p, 100) => extracted.push(p),
+        PluginLoadEvent::Released(p, _) => released.push(p),
+        PluginLoadEvent::Rejected(p, _) => rejected.push(p),
+        _ => {}
+      }
+    }
+    assert_eq!(released.len(), 4);
+    extracted.sort();
+    released.sort();
+    assert_eq!(extracted, released);
+    assert_eq!(rejected, [res.join("Broken_1.0_Cno.7z")]);
+    let last = last.unwrap();
+    assert_eq!((last.total, last.released, last.finished), (5, 4, 5));
+
+    Ok(())
+  }
+
+  // 信任策略为 `enforce` 时，未签名的插件在解压前被拒绝
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn untrusted() -> anyhow::Result<()> {
+    use edgeless_core::options::PluginTrustPolicy;
+    use crate::found::trust::PluginTrustStore;
+
+    let dir = tempfile::tempdir()?;
+    let exe = fake_seven_zip(dir.path()).await?;
+    let res = dir.path().join("Resource");
+    touch(&res.join("Chrome_1.0_Cno.7z"), "mkdir Chrome\n").await?;
+    let entries = PluginEntry::scan_dir(res.clone()).await?;
+
+    let dest = dir.path().join("Edgeless");
+    let config = PluginLoadConfig::new(dest.clone()).await?
+      .with_seven_zip(exe)
+      .with_trust(PluginTrustPolicy::Enforce, PluginTrustStore::default());
+    let lb = Default::default();
+    let report = PluginLoadScheduler::new(config).run(entries.iter(), &lb).await;
+    assert_eq!(report.results[0].state, PluginLoadState::Rejected);
+    assert!(report.results[0].error.as_ref().unwrap().contains("not trusted"));
+    assert!(!dest.join("Chrome").exists());
+
+    Ok(())
+  }
+
+  // 在监视目录中创建快捷方式，`Broken` 的脚本失败
+  #[derive(Debug)]
+  struct Shortcuts(std::path::PathBuf);
+
+  impl PluginScriptRunner for Shortcuts {
+    fn run<'a>(&'a self, script: &'a PluginScriptEntry, _: &'a PluginScriptContext) -> PluginScriptFuture<'a> {
+      let name = script.path_original.file_stem().unwrap().to_string_lossy().to_string();
+      Box::pin(async move {
+        fs::write(self.0.join(format!("{}.lnk", name)), "").await?;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Ok(PluginScriptOutput {
+          code: Some(if name == "Broken" { 1 } else { 0 }),
+          ..Default::default()
+        })
+      })
+    }
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn rollback_concurrent() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let exe = fake_seven_zip(dir.path()).await?;
+
+    let res = dir.path().join("Resource");
+    for n in ["Chrome", "Broken", "Notepad"] {
+      touch(&res.join(format!("{}_1.0_Cno.7z", n)), &format!("echo > {}.cmd
+", n)).await?;
+    }
+    for n in ["Chrome", "Broken"] {
+      touch(&res.join(format!("{}_1.0_Cno.json", n)), r#"{ "watch": true }"#).await?;
+    }
+    let entries = PluginEntry::scan_dir(res.clone()).await?;
+
+    let desktop = dir.path().join("Desktop");
+    fs::create_dir_all(&desktop).await?;
+    let config = PluginLoadConfig::new(dir.path().join("Edgeless")).await?
+      .with_seven_zip(exe)
+      .with_watch_dirs(vec![desktop.clone()]);
+    let scheduler = PluginLoadScheduler::new(config)
+      .with_concurrency(2)
+      .with_runner(Arc::new(Shortcuts(desktop.clone())));
+    let lb = Default::default();
+    let report = scheduler.run(entries.iter(), &lb).await;
+
+    let state = |n: &str| report.results.iter()
+      .find(|r| r.plugin.ends_with(format!("{}_1.0_Cno.7z", n)))
+      .map(|r| r.state);
+    assert_eq!(state("Chrome"), Some(PluginLoadState::Resolved));
+    assert_eq!(state("Broken"), Some(PluginLoadState::Rejected));
+    // 失败插件的回滚只删除自己创建的快捷方式
+    assert!(desktop.join("Chrome.lnk").exists());
+    assert!(!desktop.join("Broken.lnk").exists());
+    // 未声明 `watch` 的插件与其他脚本并发执行，新建的文件不归属任何插件
+    assert!(desktop.join("Notepad.lnk").exists());
+    let notepad = report.results.iter().find(|r| r.plugin.ends_with("Notepad_1.0_Cno.7z")).unwrap();
+    assert_eq!(notepad.state, PluginLoadState::Resolved);
+
+    Ok(())
+  }
+}
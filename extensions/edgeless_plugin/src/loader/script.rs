@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Display};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use bindings_pecmd::Pecmd;
+use bindings_pecmd::command::PecmdCommand;
+use bindings_pecmd::interpreter::{Effect, Interpreter};
+use bindings_pecmd::vfs::{self, VirtualFs};
+use edgeless_core::options::define::PATH_BIN_PECMD;
+use super::{PluginLoadState, PluginScriptEntry, PluginScriptType};
+
+use anyhow::anyhow;
+use log::{info, warn};
+use tokio::io::AsyncReadExt;
+use tokio::process::{Child, Command};
+
+// 注入到脚本的环境变量
+pub const ENV_PLUGIN: &str = "EDGELESS_PLUGIN";
+pub const ENV_PLUGIN_DIR: &str = "EDGELESS_PLUGIN_DIR";
+pub const ENV_RELEASE_DIR: &str = "EDGELESS_RELEASE_DIR";
+pub const ENV_PROFILE: &str = "EDGELESS_PROFILE";
+
+pub type PluginScriptFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<PluginScriptOutput>> + Send + 'a>>;
+
+// 脚本的执行环境，`cwd` 为插件的加载目录
+#[derive(Debug, Clone, Default)]
+pub struct PluginScriptContext {
+  pub cwd: PathBuf,
+  pub env: Vec<(String, PathBuf)>,
+  // 插件放入加载目录的文件与链接，为空时视为整个加载目录
+  pub files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PluginScriptOutput {
+  // 被终止时没有退出码
+  pub code: Option<i32>,
+  pub stdout: String,
+  pub stderr: String,
+  pub elapsed: Duration,
+  pub timed_out: bool,
+}
+
+impl PluginScriptOutput {
+  pub fn success(&self) -> bool {
+    !self.timed_out && self.code == Some(0)
+  }
+
+  pub fn state(&self) -> PluginLoadState {
+    match self.success() {
+      true => PluginLoadState::Resolved,
+      false => PluginLoadState::Rejected,
+    }
+  }
+}
+
+impl Display for PluginScriptOutput {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.timed_out {
+      return write!(f, "timed out after {:?}", self.elapsed);
+    }
+    match self.code {
+      Some(code) => write!(f, "exit code {}", code)?,
+      None => write!(f, "terminated")?,
+    }
+    let stderr = self.stderr.trim();
+    if !stderr.is_empty() {
+      write!(f, ", {}", stderr)?;
+    }
+    Ok(())
+  }
+}
+
+/*
+ * 插件脚本的执行器
+ * 退出码非零时返回 `Ok`，由调用方根据 `PluginScriptOutput` 判断；无法启动时返回 `Err`
+ * 超时由调用方丢弃返回的 future 实现，启动的进程应在丢弃时连同子进程一起结束
+ */
+pub trait PluginScriptRunner: Debug + Send + Sync {
+  fn run<'a>(&'a self, script: &'a PluginScriptEntry, context: &'a PluginScriptContext) -> PluginScriptFuture<'a>;
+}
+
+impl PluginScriptType {
+  pub fn from_path(path: &Path) -> Option<Self> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+      "cmd" | "bat" => Some(Self::Batch),
+      "wcs" => Some(Self::Pecmd),
+      _ => None,
+    }
+  }
+}
+
+// 结束进程树，脚本用 start 等方式启动的子进程一并结束
+#[cfg(windows)]
+fn kill_tree(pid: u32) -> std::io::Result<()> {
+  std::process::Command::new("taskkill")
+    .args(["/T", "/F", "/PID", &pid.to_string()])
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .map(|_| ())
+}
+
+// 脚本在独立的进程组中运行，进程组 id 即脚本进程的 id
+#[cfg(unix)]
+fn kill_tree(pid: u32) -> std::io::Result<()> {
+  std::process::Command::new("kill")
+    .args(["-KILL", "--", &format!("-{}", pid)])
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .map(|_| ())
+}
+
+/*
+ * 未退出就被丢弃（超时）的脚本进程
+ * 先结束整个进程树，再由 `kill_on_drop` 兜底结束脚本进程本身
+ */
+struct ScriptProcess(Child);
+
+impl Drop for ScriptProcess {
+  fn drop(&mut self) {
+    // 已等待退出的进程没有 id
+    if let Some(pid) = self.0.id() {
+      warn!("kill script process tree {}", pid);
+      if let Err(e) = kill_tree(pid) {
+        warn!("failed to kill script process tree {}, {}", pid, e);
+      }
+    }
+  }
+}
+
+async fn read_all<R: tokio::io::AsyncRead + Unpin>(pipe: Option<R>) -> std::io::Result<Vec<u8>> {
+  let mut buf = vec![];
+  if let Some(mut pipe) = pipe {
+    pipe.read_to_end(&mut buf).await?;
+  }
+  Ok(buf)
+}
+
+async fn collect(mut command: Command, context: &PluginScriptContext) -> anyhow::Result<PluginScriptOutput> {
+  let start = Instant::now();
+  command
+    .current_dir(&context.cwd)
+    .envs(context.env.iter().map(|(k, v)| (k, v)))
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .kill_on_drop(true);
+  #[cfg(unix)]
+  command.process_group(0);
+  let mut child = ScriptProcess(command.spawn()?);
+  let (stdout, stderr) = (child.0.stdout.take(), child.0.stderr.take());
+  let (status, stdout, stderr) = tokio::try_join!(child.0.wait(), read_all(stdout), read_all(stderr))?;
+  Ok(PluginScriptOutput {
+    code: status.code(),
+    stdout: String::from_utf8_lossy(&stdout).to_string(),
+    stderr: String::from_utf8_lossy(&stderr).to_string(),
+    elapsed: start.elapsed(),
+    timed_out: false,
+  })
+}
+
+fn script_path(script: &PluginScriptEntry) -> anyhow::Result<&str> {
+  script.path_mangled.to_str()
+    .ok_or(anyhow!("invalid script path {:?}", script.path_mangled))
+}
+
+// 批处理交给 `cmd /c`
+#[derive(Debug, Clone)]
+pub struct PluginBatchScriptRunner {
+  pub shell: PathBuf,
+}
+
+impl Default for PluginBatchScriptRunner {
+  fn default() -> Self {
+    Self {
+      shell: PathBuf::from("cmd"),
+    }
+  }
+}
+
+impl PluginScriptRunner for PluginBatchScriptRunner {
+  fn run<'a>(&'a self, script: &'a PluginScriptEntry, context: &'a PluginScriptContext) -> PluginScriptFuture<'a> {
+    Box::pin(async move {
+      let path = script_path(script)?;
+      info!("run batch script {:?}", path);
+      let mut command = Command::new(&self.shell);
+      command.args(["/c", path]);
+      collect(command, context).await
+    })
+  }
+}
+
+// PECMD 脚本交给 `pecmd LOAD`
+#[derive(Debug, Clone)]
+pub struct PluginPecmdScriptRunner {
+  pub pecmd: PathBuf,
+}
+
+impl Default for PluginPecmdScriptRunner {
+  fn default() -> Self {
+    Self {
+      pecmd: PATH_BIN_PECMD.clone(),
+    }
+  }
+}
+
+impl PluginScriptRunner for PluginPecmdScriptRunner {
+  fn run<'a>(&'a self, script: &'a PluginScriptEntry, context: &'a PluginScriptContext) -> PluginScriptFuture<'a> {
+    Box::pin(async move {
+      info!("run pecmd script {:?}", script.path_mangled);
+      let load = PecmdCommand::Load(script.path_mangled.clone());
+      let command = Pecmd::new(self.pecmd.clone())?.command_for(&load)?;
+      collect(command, context).await
+    })
+  }
+}
+
+// 按脚本类型分派
+#[derive(Debug, Clone, Default)]
+pub struct PluginDefaultScriptRunner {
+  pub batch: PluginBatchScriptRunner,
+  pub pecmd: PluginPecmdScriptRunner,
+}
+
+impl PluginScriptRunner for PluginDefaultScriptRunner {
+  fn run<'a>(&'a self, script: &'a PluginScriptEntry, context: &'a PluginScriptContext) -> PluginScriptFuture<'a> {
+    match script.script_type {
+      PluginScriptType::Batch => self.batch.run(script, context),
+      PluginScriptType::Pecmd => self.pecmd.run(script, context),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct PluginScriptRecord {
+  pub script: PluginScriptEntry,
+  pub context: PluginScriptContext,
+}
+
+/*
+ * 只记录调用、不执行的执行器，用于测试
+ * 按原文件名指定结果，未指定的脚本退出码为 0
+ */
+#[derive(Debug, Default)]
+pub struct PluginRecordingScriptRunner {
+  records: Mutex<Vec<PluginScriptRecord>>,
+  outcomes: HashMap<String, PluginScriptOutput>,
+  delay: Option<Duration>,
+}
+
+impl PluginRecordingScriptRunner {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_outcome(mut self, name: &str, output: PluginScriptOutput) -> Self {
+    self.outcomes.insert(name.to_string(), output);
+    self
+  }
+
+  pub fn with_exit_code(self, name: &str, code: i32) -> Self {
+    self.with_outcome(name, PluginScriptOutput {
+      code: Some(code),
+      ..Default::default()
+    })
+  }
+
+  // 每个脚本模拟运行的时间
+  pub fn with_delay(mut self, delay: Duration) -> Self {
+    self.delay = Some(delay);
+    self
+  }
+
+  pub fn records(&self) -> Vec<PluginScriptRecord> {
+    self.records.lock().unwrap().clone()
+  }
+
+  // 已执行脚本的原文件名
+  pub fn names(&self) -> Vec<String> {
+    self.records.lock().unwrap()
+      .iter()
+      .map(|r| r.script.original_name())
+      .collect()
+  }
+}
+
+impl PluginScriptRunner for PluginRecordingScriptRunner {
+  fn run<'a>(&'a self, script: &'a PluginScriptEntry, context: &'a PluginScriptContext) -> PluginScriptFuture<'a> {
+    Box::pin(async move {
+      self.records.lock().unwrap().push(PluginScriptRecord {
+        script: script.clone(),
+        context: context.clone(),
+      });
+      if let Some(delay) = self.delay {
+        tokio::time::sleep(delay).await;
+      }
+      Ok(self.outcomes.get(&script.original_name())
+        .cloned()
+        .unwrap_or(PluginScriptOutput {
+          code: Some(0),
+          elapsed: self.delay.unwrap_or_default(),
+          ..Default::default()
+        }))
+    })
+  }
+}
+
+// 模拟执行时加载目录在虚拟文件系统中的位置
+pub const SIMULATED_DEST: &str = "X:\\Program Files\\Edgeless";
+
+/*
+ * 在虚拟文件系统上解释执行 PECMD 脚本，不依赖 Windows，用于测试插件包
+ * 插件自己的文件映射到 `SIMULATED_DEST`，每个脚本的副作用按原文件名记录
+ * 读取文件在阻塞线程中进行；批处理不模拟，直接视为成功
+ */
+#[derive(Debug, Default)]
+pub struct PluginSimulatedScriptRunner {
+  effects: Mutex<Vec<(String, Vec<Effect>)>>,
+}
+
+impl PluginSimulatedScriptRunner {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn effects(&self) -> Vec<(String, Vec<Effect>)> {
+    self.effects.lock().unwrap().clone()
+  }
+
+  // 宿主机路径换成虚拟路径，加载目录以外的保持原样
+  fn virtual_path(cwd: &Path, path: &Path) -> String {
+    match path.strip_prefix(cwd) {
+      Ok(rel) => vfs::normalize(&vfs::join(SIMULATED_DEST, &rel.to_string_lossy())),
+      Err(_) => path.to_string_lossy().to_string(),
+    }
+  }
+
+  fn load_files(context: &PluginScriptContext) -> anyhow::Result<VirtualFs> {
+    let mut fs = VirtualFs::new();
+    if context.files.is_empty() {
+      fs.load_dir(&context.cwd, SIMULATED_DEST)?;
+      return Ok(fs);
+    }
+    fs.create_dir_all(SIMULATED_DEST);
+    for f in &context.files {
+      let at = Self::virtual_path(&context.cwd, f);
+      match std::fs::metadata(f) {
+        Ok(m) if m.is_dir() => fs.load_dir(f, &at)?,
+        Ok(m) => fs.write(&at, m.len()),
+        Err(e) => warn!("failed to load {:?}, {}", f, e),
+      }
+    }
+    Ok(fs)
+  }
+
+  fn simulate(script: &PluginScriptEntry, context: &PluginScriptContext) -> anyhow::Result<(PluginScriptOutput, Vec<Effect>)> {
+    let start = Instant::now();
+    let fs = Self::load_files(context)?;
+    let mut interpreter = Interpreter::new(fs).with_cur_dir(SIMULATED_DEST);
+    for (k, v) in &context.env {
+      interpreter = interpreter.with_var(k, &Self::virtual_path(&context.cwd, v));
+    }
+
+    let content = std::fs::read(&script.path_mangled)?;
+    let res = interpreter.run_source(&String::from_utf8_lossy(&content));
+    let stdout = interpreter.effects().iter().map(|e| format!("{}\n", e)).collect();
+    let mut stderr = interpreter.diagnostics().iter().map(|d| format!("{}\n", d)).collect::<String>();
+    if let Err(e) = &res {
+      stderr.push_str(&e.to_string());
+    }
+    let output = PluginScriptOutput {
+      code: Some(if res.is_ok() { 0 } else { 1 }),
+      stdout,
+      stderr,
+      elapsed: start.elapsed(),
+      timed_out: false,
+    };
+    Ok((output, interpreter.effects().to_vec()))
+  }
+}
+
+impl PluginScriptRunner for PluginSimulatedScriptRunner {
+  fn run<'a>(&'a self, script: &'a PluginScriptEntry, context: &'a PluginScriptContext) -> PluginScriptFuture<'a> {
+    Box::pin(async move {
+      match script.script_type {
+        PluginScriptType::Pecmd => {
+          info!("simulate pecmd script {:?}", script.path_mangled);
+          let (s, c) = (script.clone(), context.clone());
+          let (output, effects) = tokio::task::spawn_blocking(move || Self::simulate(&s, &c)).await??;
+          self.effects.lock().unwrap().push((script.original_name(), effects));
+          Ok(output)
+        }
+        PluginScriptType::Batch => {
+          warn!("batch script {:?} is not simulated", script.path_mangled);
+          Ok(PluginScriptOutput {
+            code: Some(0),
+            ..Default::default()
+          })
+        }
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use super::{PluginScriptOutput, PluginScriptRunner, PluginScriptContext, PluginRecordingScriptRunner};
+  use crate::loader::{PluginLoadState, PluginScriptEntry, PluginScriptType};
+
+  #[tokio::test]
+  async fn it_works() -> anyhow::Result<()> {
+    assert!(matches!(PluginScriptType::from_path(Path::new("Chrome.wcs")), Some(PluginScriptType::Pecmd)));
+    assert!(matches!(PluginScriptType::from_path(Path::new("setup.CMD")), Some(PluginScriptType::Batch)));
+    assert!(matches!(PluginScriptType::from_path(Path::new("setup.bat")), Some(PluginScriptType::Batch)));
+    assert!(PluginScriptType::from_path(Path::new("readme.txt")).is_none());
+    assert!(PluginScriptType::from_path(Path::new("Chrome")).is_none());
+
+    let runner = PluginRecordingScriptRunner::new().with_exit_code("fail.cmd", 3);
+    let context = PluginScriptContext::default();
+    let script = |name: &str| PluginScriptEntry {
+      script_type: PluginScriptType::Batch,
+      path_original: Path::new("Release").join(name),
+      path_mangled: Path::new("Dest").join(format!("{}~x", name)),
+    };
+    let ok = runner.run(&script("setup.cmd"), &context).await?;
+    assert_eq!(ok.state(), PluginLoadState::Resolved);
+    let failed = runner.run(&script("fail.cmd"), &context).await?;
+    assert_eq!(failed.state(), PluginLoadState::Rejected);
+    assert_eq!(failed.to_string(), "exit code 3");
+    assert_eq!(runner.names(), vec!["setup.cmd", "fail.cmd"]);
+
+    let timed_out = PluginScriptOutput {
+      code: Some(0),
+      timed_out: true,
+      ..Default::default()
+    };
+    assert!(!timed_out.success());
+
+    Ok(())
+  }
+
+  // 用 sh 代替 cmd，检查输出捕获与环境变量注入
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn batch() -> anyhow::Result<()> {
+    use super::PluginBatchScriptRunner;
+
+    let dir = tempfile::tempdir()?;
+    let shell = dir.path().join("cmd");
+    tokio::fs::write(&shell, "#!/bin/sh\nshift\nexec sh \"$@\"\n").await?;
+    {
+      use std::os::unix::fs::PermissionsExt;
+      tokio::fs::set_permissions(&shell, std::fs::Permissions::from_mode(0o755)).await?;
+    }
+    let path = dir.path().join("setup~x.cmd");
+    tokio::fs::write(&path, "echo $EDGELESS_PLUGIN_DIR\necho oops >&2\nexit 4\n").await?;
+
+    let runner = PluginBatchScriptRunner { shell };
+    let context = PluginScriptContext {
+      cwd: dir.path().to_path_buf(),
+      env: vec![(super::ENV_PLUGIN_DIR.to_string(), dir.path().join("Edgeless"))],
+      ..Default::default()
+    };
+    let script = PluginScriptEntry {
+      script_type: PluginScriptType::Batch,
+      path_original: dir.path().join("setup.cmd"),
+      path_mangled: path,
+    };
+    let out = runner.run(&script, &context).await?;
+    assert_eq!(out.code, Some(4));
+    assert_eq!(out.stdout.trim(), dir.path().join("Edgeless").to_string_lossy());
+    assert_eq!(out.to_string(), "exit code 4, oops");
+
+    // 超时丢弃后，脚本在后台启动的进程也被结束
+    let late = dir.path().join("late.txt");
+    tokio::fs::write(&script.path_mangled, format!("(sleep 1; echo late > '{}') &\nsleep 5\n", late.display())).await?;
+    let run = runner.run(&script, &context);
+    assert!(tokio::time::timeout(std::time::Duration::from_millis(300), run).await.is_err());
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    assert!(!late.exists());
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn simulated() -> anyhow::Result<()> {
+    use super::{PluginSimulatedScriptRunner, ENV_PLUGIN_DIR};
+
+    let dir = tempfile::tempdir()?;
+    tokio::fs::create_dir_all(dir.path().join("Chrome")).await?;
+    tokio::fs::write(dir.path().join("Chrome/chrome.exe"), "").await?;
+    tokio::fs::create_dir_all(dir.path().join("Other")).await?;
+    tokio::fs::write(dir.path().join("Other/other.exe"), "").await?;
+    let path = dir.path().join("Chrome~x.wcs");
+    tokio::fs::write(&path, "LINK %Desktop%\\Chrome,%EDGELESS_PLUGIN_DIR%\\chrome.exe\r\nIFEX a,{\r\n").await?;
+
+    let runner = PluginSimulatedScriptRunner::new();
+    let context = PluginScriptContext {
+      cwd: dir.path().to_path_buf(),
+      env: vec![(ENV_PLUGIN_DIR.to_string(), dir.path().join("Chrome"))],
+      files: vec![dir.path().join("Chrome"), path.clone()],
+    };
+    let script = PluginScriptEntry {
+      script_type: PluginScriptType::Pecmd,
+      path_original: dir.path().join("Chrome.wcs"),
+      path_mangled: path.clone(),
+    };
+    let out = runner.run(&script, &context).await?;
+    assert_eq!(out.code, Some(1));
+
+    // 其他插件的文件不在虚拟文件系统中
+    tokio::fs::write(&path, "LINK %Desktop%\\Chrome,%EDGELESS_PLUGIN_DIR%\\chrome.exe\r\nIFEX %CurDir%\\Other\\other.exe,MDIR %CurDir%\\Seen\r\n").await?;
+    let out = runner.run(&script, &context).await?;
+    assert!(out.success(), "{}", out);
+    assert!(out.stderr.is_empty(), "{}", out.stderr);
+    assert_eq!(out.stdout.trim(), "link X:\\Users\\Default\\Desktop\\Chrome -> X:\\Program Files\\Edgeless\\Chrome\\chrome.exe");
+    assert_eq!(runner.effects().len(), 2);
+    assert_eq!(runner.effects()[1].0, "Chrome.wcs");
+
+    Ok(())
+  }
+}
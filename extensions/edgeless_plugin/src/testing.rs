@@ -0,0 +1,40 @@
+// 测试共用的文件与假 7z
+use std::path::Path;
+use tokio::fs;
+
+pub async fn touch(p: &Path, content: impl AsRef<[u8]>) -> anyhow::Result<()> {
+  fs::create_dir_all(p.parent().unwrap()).await?;
+  fs::write(p, content).await?;
+  Ok(())
+}
+
+/*
+ * 用 shell 脚本模拟 7z，按开关名解析参数，不依赖参数位置
+ * x: "压缩包"本身是脚本，在 `-o` 指定的目录中执行，成功后报告 100% 进度
+ * t: 内容含 `broken` 时报告数据错误
+ */
+#[cfg(unix)]
+pub async fn fake_seven_zip(dir: &Path) -> anyhow::Result<std::path::PathBuf> {
+  use std::os::unix::fs::PermissionsExt;
+
+  let script = [
+    "#!/bin/sh",
+    "cmd=\"$1\"; shift",
+    "while [ $# -gt 0 ]; do",
+    "  case \"$1\" in",
+    "    --) shift; archive=\"$1\"; break;;",
+    "    -o*) out=\"${1#-o}\";;",
+    "  esac",
+    "  shift",
+    "done",
+    "case \"$cmd\" in",
+    "  x) mkdir -p \"$out\" && cd \"$out\" && sh \"$archive\" >/dev/null && echo '100%';;",
+    "  t) if grep -q broken \"$archive\"; then echo 'ERROR: Data Error' >&2; exit 2; fi;;",
+    "esac",
+    "",
+  ].join("\n");
+  let exe = dir.join("7z");
+  touch(&exe, script).await?;
+  fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).await?;
+  Ok(exe)
+}
@@ -1,5 +1,4 @@
 use anyhow::anyhow;
-use std::{ffi::{OsString, OsStr}};
 
 use rand::{Rng, thread_rng};
 
@@ -17,7 +16,7 @@ pub fn u8_to_ascii(c: &[u8]) -> anyhow::Result<String> {
     let mut s = String::new();
     for i in c {
         if i.is_ascii() {
-        let i = i.clone();
+        let i = *i;
         s.push(i.into());
         } else {
         return Err(anyhow!("{} is a invaild ascii number", i));
@@ -26,25 +25,109 @@ pub fn u8_to_ascii(c: &[u8]) -> anyhow::Result<String> {
     Ok(s)
 }
 
+// 通配符匹配，支持 `*` 与 `?`，忽略大小写
+pub fn wildcard_match(pattern: &str, s: &str) -> bool {
+    let p = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let s = s.to_lowercase().chars().collect::<Vec<_>>();
+    let (mut pi, mut si) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while si < s.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == s[si]) {
+            pi += 1;
+            si += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, si));
+            pi += 1;
+        } else if let Some((sp, ss)) = star {
+            pi = sp + 1;
+            si = ss + 1;
+            star = Some((sp, ss + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
 
+// 转为以 0 结尾的 UTF-16 字符串，用于 Windows API
 pub fn u2w(u8str: &str) -> Vec<u16> {
-    use std::os::windows::prelude::OsStrExt;
-	OsStr::new(u8str).encode_wide().chain(Some(0).into_iter()).collect::<Vec<_>>()
+    u8str.encode_utf16().chain(Some(0)).collect::<Vec<_>>()
+}
+
+// 读取到第一个 0 为止，无效的代理项替换为 U+FFFD
+pub fn w2u_slice(wstr: &[u16]) -> String {
+    let len = wstr.iter().position(|c| *c == 0).unwrap_or(wstr.len());
+    String::from_utf16_lossy(&wstr[..len])
+}
+
+/// 读取 Windows API 返回的以 0 结尾的 UTF-16 字符串
+///
+/// # Safety
+///
+/// `wstr` 必须非空，指向以 0 结尾、在调用期间有效且可读的 `u16` 序列
+pub unsafe fn w2u(wstr: *const u16) -> String {
+    let len = (0..).position(|i| *wstr.add(i) == 0).unwrap();
+    w2u_slice(std::slice::from_raw_parts(wstr, len))
+}
+
+// 文件属性查询，非 Windows 平台上没有对应属性时返回 false
+pub trait FileAttributes {
+    fn is_hidden(&self) -> bool;
+    fn is_system(&self) -> bool;
+}
+
+#[cfg(windows)]
+impl FileAttributes for std::fs::Metadata {
+    fn is_hidden(&self) -> bool {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        self.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+    }
+
+    fn is_system(&self) -> bool {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+        self.file_attributes() & FILE_ATTRIBUTE_SYSTEM != 0
+    }
 }
 
-pub fn w2u(wstr: *const u16) -> String {
-    use std::os::windows::prelude::OsStringExt;
-    unsafe {
-        let len = (0..std::isize::MAX).position(|i| *wstr.offset(i) == 0).unwrap();
-        let slice = std::slice::from_raw_parts(wstr, len);
-        OsString::from_wide(slice).to_string_lossy().into_owned()
+#[cfg(not(windows))]
+impl FileAttributes for std::fs::Metadata {
+    fn is_hidden(&self) -> bool {
+        false
+    }
+
+    fn is_system(&self) -> bool {
+        false
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{u2w, w2u, w2u_slice, wildcard_match};
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn wildcard() {
+        assert!(wildcard_match("*.7z", "Chrome_90.0_Cno.7z"));
+        assert!(wildcard_match("*_beta_*", "Tool_BETA_Cno.7z"));
+        assert!(wildcard_match("Old", "old"));
+        assert!(wildcard_match("a?c*", "abc"));
+        assert!(!wildcard_match("*.7z", "Chrome.7zf"));
+        assert!(!wildcard_match("a?c", "ac"));
+    }
+
+    #[test]
+    fn utf16() {
+        let w = u2w("插件 Chrome");
+        assert_eq!(w.last(), Some(&0));
+        assert_eq!(w2u_slice(&w), "插件 Chrome");
+        assert_eq!(unsafe { w2u(w.as_ptr()) }, "插件 Chrome");
+        assert_eq!(w2u_slice(&u2w("😀")), "😀");
+        assert_eq!(w2u_slice(&[0x41, 0xd800, 0x42]), "A\u{fffd}B");
+    }
 }